@@ -2,11 +2,14 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use murmur8tion::{
     hardware::{Chip8, DynamicMachine, Machine},
     model::{CosmacVip, Model},
-    screen::{CosmacVipScreen, DynamicScreen, Screen},
+    screen::{self, CosmacVipScreen, DynamicScreen, IndexedFrame, Palette, Screen},
 };
 
 const TEST_ROM: &[u8] = &[0x12, 0x00, 0x00, 0x00];
 const DRAW_TEST_ROM: &[u8] = &[0xA2, 0x06, 0xD0, 0x02, 0x12, 0x02, 0x00, 0x00];
+const ARITHMETIC_TEST_ROM: &[u8] = &[
+    0x70, 0x01, 0x81, 0x04, 0x82, 0x16, 0xF1, 0x1E, 0x40, 0x00, 0x63, 0x00, 0x12, 0x00,
+];
 
 pub fn dynamic_machine_dispatch(c: &mut Criterion) {
     let mut machine = black_box(DynamicMachine::new_cosmac_vip(
@@ -106,11 +109,71 @@ pub fn dyn_machine(c: &mut Criterion) {
     });
 }
 
+pub fn screen_rendering(c: &mut Criterion) {
+    let palette = Palette::default();
+    let screens = [
+        ("cosmac vip", DynamicScreen::new_cosmac_vip()),
+        ("legacy schip", DynamicScreen::new_legacy_super_chip()),
+        ("modern schip", DynamicScreen::new_modern_super_chip()),
+        ("xo-chip", DynamicScreen::new_xochip()),
+    ];
+    for (name, mut screen) in screens {
+        let _ = screen.set_hires(true);
+        for i in 0..16 {
+            screen.draw_sprite(i * 8, i * 4, &screen::FONT[i as usize]);
+        }
+        let screen = black_box(screen);
+
+        c.bench_function(&format!("{name} to_image"), |b| {
+            b.iter(|| screen.to_image(&palette))
+        });
+
+        let size = screen.width() as usize * screen.height() as usize;
+        let mut indices = vec![0; size];
+        let mut rgba = vec![0; size * 4];
+        let colors = screen.index_colors(&palette);
+        c.bench_function(&format!("{name} render indices"), |b| {
+            b.iter(|| screen.render_indices(&mut indices))
+        });
+        c.bench_function(&format!("{name} render indices to rgba"), |b| {
+            b.iter(|| {
+                screen.render_indices(&mut indices);
+                screen::indices_to_rgba(&indices, &colors, &mut rgba);
+            })
+        });
+
+        let mut frame = IndexedFrame::default();
+        frame.update(screen.as_ref(), &palette);
+        c.bench_function(&format!("{name} indexed frame unchanged"), |b| {
+            b.iter(|| frame.update(screen.as_ref(), &palette))
+        });
+    }
+}
+
+pub fn decode_cache(c: &mut Criterion) {
+    for (name, enabled) in [("match", false), ("decode cache", true)] {
+        let mut machine = black_box(DynamicMachine::new_cosmac_vip(
+            CosmacVip::default(),
+            ARITHMETIC_TEST_ROM,
+        ));
+        machine.set_decode_cache(enabled);
+        c.bench_function(&format!("1000 instructions {name}"), |b| {
+            b.iter(|| {
+                for _ in 0..1000 {
+                    let _ = machine.tick();
+                }
+            })
+        });
+    }
+}
+
 criterion_group!(
     benches,
     dynamic_machine_dispatch,
     dyn_model_dispatch,
     dyn_model_enum_screen,
     dyn_machine,
+    screen_rendering,
+    decode_cache,
 );
 criterion_main!(benches);
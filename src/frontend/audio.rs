@@ -1,18 +1,154 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
 
+use async_channel::{Receiver, Sender};
 use bevy::{
-    asset::Asset,
     audio::{Decodable, Source},
-    ecs::system::Resource,
-    reflect::TypePath,
+    prelude::*,
 };
+use bevy_egui::{egui, EguiContexts};
 use rodio::queue::{SourcesQueueInput, SourcesQueueOutput};
 
+use crate::hardware::{AudioChange, AudioFrame, AudioState, Machine};
+
+use super::settings::{Settings, SettingsFile};
+
+pub const MUTE_KEY: KeyCode = KeyCode::F9;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct AudioSettings {
+    /// Linear gain applied to the synth's full-scale output.
+    pub volume: f32,
+    pub muted: bool,
+    /// Soften the square waves, roughly like the small speakers on the original hardware.
+    pub low_pass: bool,
+    pub low_pass_cutoff: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            volume: 0.15,
+            muted: false,
+            low_pass: false,
+            low_pass_cutoff: 2000.0,
+        }
+    }
+}
+
+impl AudioSettings {
+    fn load(settings: &SettingsFile) -> Self {
+        let default = Self::default();
+        Self {
+            volume: settings
+                .get("audio.volume")
+                .map_or(default.volume, |volume: f32| volume.clamp(0.0, 1.0)),
+            muted: settings.get("audio.muted").unwrap_or(default.muted),
+            low_pass: settings.get("audio.low_pass").unwrap_or(default.low_pass),
+            low_pass_cutoff: settings
+                .get("audio.low_pass_cutoff")
+                .map_or(default.low_pass_cutoff, |cutoff: f32| {
+                    cutoff.clamp(200.0, 8000.0)
+                }),
+        }
+    }
+
+    fn store(&self, settings: &mut SettingsFile) {
+        settings.set("audio.volume", self.volume);
+        settings.set("audio.muted", self.muted);
+        settings.set("audio.low_pass", self.low_pass);
+        settings.set("audio.low_pass_cutoff", self.low_pass_cutoff);
+    }
+}
+
+pub fn audio_plugin(app: &mut App) {
+    app.init_resource::<Settings>();
+    let audio_settings = AudioSettings::load(app.world().resource::<Settings>());
+    app.insert_resource(audio_settings)
+        .add_systems(Update, (toggle_mute, save_audio_settings).chain());
+}
+
+fn toggle_mute(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut settings: ResMut<AudioSettings>,
+) {
+    // Typing in a text field shouldn't mute the sound
+    if keys.just_pressed(MUTE_KEY) && !contexts.ctx_mut().wants_keyboard_input() {
+        settings.muted = !settings.muted;
+    }
+}
+
+fn save_audio_settings(
+    audio_settings: Res<AudioSettings>,
+    mut settings: ResMut<Settings>,
+    mut last_saved: Local<Option<AudioSettings>>,
+) {
+    settings.save_if_changed(
+        &mut last_saved,
+        audio_settings.as_ref(),
+        |settings, audio_settings| audio_settings.store(settings),
+    );
+}
+
+pub fn show_audio_options(
+    ui: &mut egui::Ui,
+    settings: &mut AudioSettings,
+) -> egui::CollapsingResponse<()> {
+    egui::CollapsingHeader::new("Audio").show(ui, |ui| {
+        ui.add(
+            egui::Slider::new(&mut settings.volume, 0.0..=1.0)
+                .custom_formatter(|volume, _| format!("{:.0}%", volume * 100.0))
+                .text("Volume"),
+        );
+        ui.checkbox(&mut settings.muted, "Mute")
+            .on_hover_text(format!("{MUTE_KEY:?}"));
+        ui.checkbox(&mut settings.low_pass, "Low-pass filter")
+            .on_hover_text(
+                "Approximates the muffled sound of the COSMAC VIP's and HP 48's speakers",
+            );
+        ui.add_enabled(
+            settings.low_pass,
+            egui::Slider::new(&mut settings.low_pass_cutoff, 200.0..=8000.0)
+                .logarithmic(true)
+                .suffix(" Hz")
+                .text("Cutoff"),
+        );
+    })
+}
+
+/// Two cascaded one-pole low-pass filters, for a gentle 12dB/octave rolloff.
+#[derive(Debug, Clone, Default)]
+struct LowPass {
+    stages: [f32; 2],
+}
+
+impl LowPass {
+    fn process(&mut self, samples: &mut [f32], cutoff: f32) {
+        let coefficient =
+            1.0 - (-2.0 * std::f32::consts::PI * cutoff / OUTPUT_SAMPLE_RATE as f32).exp();
+        for sample in samples {
+            for stage in &mut self.stages {
+                *stage += coefficient * (*sample - *stage);
+                *sample = *stage;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.stages = [0.0; 2];
+    }
+}
+
 #[derive(Clone, Asset, TypePath, Resource)]
 pub struct Chip8Audio {
     synth: Chip8Synth,
+    low_pass: LowPass,
     queue_input: Arc<SourcesQueueInput<f32>>,
     queue_output: Arc<Mutex<Option<SourcesQueueOutput<f32>>>>,
+    capture: Arc<Mutex<Option<Sender<Vec<f32>>>>>,
 }
 
 impl Chip8Audio {
@@ -20,20 +156,81 @@ impl Chip8Audio {
         let (tx, rx) = rodio::queue::queue(true);
         Self {
             synth: Chip8Synth::new(),
+            low_pass: LowPass::default(),
             queue_input: tx,
             queue_output: Arc::new(Mutex::new(Some(rx))),
+            capture: Arc::new(Mutex::new(None)),
         }
     }
 
-    pub fn render_audio(&mut self, pitch: u8, pattern: [u8; 16], timestep: f64) {
-        let samples = self.synth.generate_samples(pitch, pattern, timestep);
-        let source = rodio::buffer::SamplesBuffer::new(1, OUTPUT_SAMPLE_RATE, samples);
-        self.queue_input.append(source);
+    /// Render and play a frame of audio. Captures get the synth's output before any of the
+    /// settings are applied.
+    pub fn render_audio(&mut self, frame: &AudioFrame, timestep: f64, settings: &AudioSettings) {
+        let mut samples = self.synth.render_frame(frame, timestep);
+        self.write_capture(&samples);
+        if settings.muted {
+            self.low_pass.reset();
+            return;
+        }
+        if settings.low_pass {
+            self.low_pass
+                .process(&mut samples, settings.low_pass_cutoff);
+        } else {
+            self.low_pass.reset();
+        }
+        for sample in &mut samples {
+            *sample *= settings.volume;
+        }
+        // Leave gaps in the queue while there's no sound, so that it can't build up latency
+        if samples.iter().any(|sample| *sample != 0.0) {
+            let source = rodio::buffer::SamplesBuffer::new(1, OUTPUT_SAMPLE_RATE, samples);
+            self.queue_input.append(source);
+        }
     }
 
     pub fn reset(&mut self) {
-        self.synth.reset()
+        self.synth.reset();
+        self.low_pass.reset();
+    }
+
+    /// Start writing every generated sample to a WAV file, replacing any capture in progress.
+    /// The file is written on its own thread, since it blocks for as long as the capture lasts,
+    /// and finished once the capture is stopped.
+    pub fn start_capture(&mut self, path: PathBuf) {
+        let (tx, rx) = async_channel::unbounded::<Vec<f32>>();
+        *self.capture.lock().unwrap() = Some(tx);
+        std::thread::spawn(move || match write_wav(&path, rx) {
+            Ok(()) => info!("Saved audio to {}", path.display()),
+            Err(error) => error!("Error saving audio to {}: {}", path.display(), error),
+        });
     }
+
+    pub fn stop_capture(&mut self) {
+        // Closing the channel lets the writer finish the file
+        self.capture.lock().unwrap().take();
+    }
+
+    fn write_capture(&mut self, samples: &[f32]) {
+        let mut capture = self.capture.lock().unwrap();
+        let Some(tx) = capture.as_ref() else {
+            return;
+        };
+        // The writer has already logged its error if it's gone
+        if tx.try_send(samples.to_vec()).is_err() {
+            *capture = None;
+        }
+    }
+}
+
+fn write_wav(path: &Path, rx: Receiver<Vec<f32>>) -> hound::Result<()> {
+    std::fs::create_dir_all(path.parent().unwrap_or(path))?;
+    let mut writer = hound::WavWriter::create(path, WAV_SPEC)?;
+    while let Ok(samples) = rx.recv_blocking() {
+        for sample in samples {
+            writer.write_sample(sample)?;
+        }
+    }
+    writer.finalize()
 }
 
 impl Decodable for Chip8Audio {
@@ -55,7 +252,7 @@ impl Decodable for Chip8Audio {
             .unwrap()
             .take()
             .expect("Chip8Audio decoded a second time");
-        Box::new(rx.amplify(0.15))
+        Box::new(rx)
     }
 }
 
@@ -69,42 +266,392 @@ const HIGH_PITCH: [u8; 16] = [
     0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0,
 ];
 pub const DEFAULT_PATTERN: [u8; 16] = HIGH_PITCH;
+/// A 1kHz square wave at the default pitch, closer to the VIP's fixed-frequency tone generator.
+pub const VIP_PATTERN: [u8; 16] = [0xCC; 16];
+
+pub const OUTPUT_SAMPLE_RATE: u32 = 44100;
 
-const OUTPUT_SAMPLE_RATE: u32 = 44100;
+/// Mono 32-bit float samples, exactly as the synth generates them.
+pub const WAV_SPEC: hound::WavSpec = hound::WavSpec {
+    channels: 1,
+    sample_rate: OUTPUT_SAMPLE_RATE,
+    bits_per_sample: 32,
+    sample_format: hound::SampleFormat::Float,
+};
 
+fn sample_count(timestep: f64) -> usize {
+    (timestep * OUTPUT_SAMPLE_RATE as f64).round() as usize
+}
+
+const KERNEL_HALF_WIDTH: usize = 8;
+const KERNEL_WIDTH: usize = 2 * KERNEL_HALF_WIDTH;
+const KERNEL_PHASES: usize = 64;
+// Relative to the Nyquist frequency, leaving some room for the kernel's transition band
+const KERNEL_CUTOFF: f64 = 0.9;
+
+/// Band-limited impulses, one for each sub-sample phase (plus one extra so that rounding up to the
+/// next sample doesn't need special handling). Each one is a Blackman-windowed sinc, normalized so
+/// that it sums to exactly 1, which means that integrating it gives a band-limited step.
+static STEP_KERNEL: LazyLock<Box<[[f32; KERNEL_WIDTH]; KERNEL_PHASES + 1]>> = LazyLock::new(|| {
+    let mut kernel = Box::new([[0.0; KERNEL_WIDTH]; KERNEL_PHASES + 1]);
+    for (phase, taps) in kernel.iter_mut().enumerate() {
+        let offset = phase as f64 / KERNEL_PHASES as f64;
+        let impulse = (0..KERNEL_WIDTH)
+            .map(|i| {
+                let x = i as f64 - (KERNEL_HALF_WIDTH - 1) as f64 - offset;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    let x = std::f64::consts::PI * KERNEL_CUTOFF * x;
+                    x.sin() / x
+                };
+                let w = std::f64::consts::PI * x / KERNEL_HALF_WIDTH as f64;
+                let window = 0.42 + 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+                sinc * window.max(0.0)
+            })
+            .collect::<Vec<_>>();
+        let sum = impulse.iter().sum::<f64>();
+        for (tap, value) in taps.iter_mut().zip(impulse) {
+            *tap = (value / sum) as f32;
+        }
+    }
+    kernel
+});
+
+/// Renders the sound hardware's output with band-limited step synthesis: every time the output
+/// level changes, a band-limited step is mixed in at the exact (sub-sample) time it happens,
+/// instead of sampling the 1-bit pattern directly. This avoids aliasing, and lets changes to the
+/// pattern, pitch and sound timer take effect partway through a frame.
+///
+/// Steps are delayed by `KERNEL_HALF_WIDTH - 1` samples, and the end of the last step of each
+/// frame carries over into the next one.
 #[derive(Debug, Clone)]
-struct Chip8Synth {
-    counter: f64,
+pub struct Chip8Synth {
+    state: AudioState,
+    /// The position in the pattern, in bits.
+    phase: f64,
+    level: f32,
+    accumulator: f32,
+    carry: [f32; KERNEL_WIDTH],
+}
+
+impl Default for Chip8Synth {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chip8Synth {
-    fn new() -> Self {
-        Self { counter: 0.0 }
+    pub fn new() -> Self {
+        Self {
+            state: AudioState {
+                pattern: DEFAULT_PATTERN,
+                pitch: 64,
+                playing: false,
+            },
+            phase: 0.0,
+            level: 0.0,
+            accumulator: 0.0,
+            carry: [0.0; KERNEL_WIDTH],
+        }
     }
 
-    fn generate_samples(&mut self, pitch: u8, pattern: [u8; 16], timestep: f64) -> Vec<f32> {
-        let needed_samples = (timestep * OUTPUT_SAMPLE_RATE as f64).round() as usize;
-        let rate = pitch_to_rate(pitch);
-        let pattern = u128::from_be_bytes(pattern);
-        let mut samples = Vec::new();
-        for _ in 0..needed_samples {
-            self.counter += rate / OUTPUT_SAMPLE_RATE as f64;
-            self.counter %= 128.0;
-            let index = self.counter.round() as u8;
-            if pattern & (0b1 << (127 - index)) != 0 {
-                samples.push(1.0);
+    /// Render the audio for a machine's last frame.
+    pub fn generate_frame(&mut self, machine: &impl Machine, timestep: f64) -> Vec<f32> {
+        self.render_frame(&machine.audio_frame(), timestep)
+    }
+
+    pub fn render_frame(&mut self, frame: &AudioFrame, timestep: f64) -> Vec<f32> {
+        let needed_samples = sample_count(timestep);
+        let mut deltas = vec![0.0; needed_samples + KERNEL_WIDTH];
+        deltas[..KERNEL_WIDTH].copy_from_slice(&self.carry);
+
+        let samples_per_cycle = if frame.cycles > 0 {
+            needed_samples as f64 / frame.cycles as f64
+        } else {
+            0.0
+        };
+        // Start from where the machine is, which matters for the first frame of a new machine
+        self.sync(&mut deltas, 0.0, frame.start);
+        let mut time = 0.0;
+        for event in &frame.events {
+            let event_time = (event.cycle as f64 * samples_per_cycle).min(needed_samples as f64);
+            self.advance(&mut deltas, time, event_time);
+            self.apply(&mut deltas, event_time, event.change);
+            time = event_time;
+        }
+        self.advance(&mut deltas, time, needed_samples as f64);
+
+        // If anything was missed, catch up at the end of the frame
+        self.sync(&mut deltas, needed_samples as f64, frame.end);
+
+        self.carry.copy_from_slice(&deltas[needed_samples..]);
+        deltas.truncate(needed_samples);
+        for sample in &mut deltas {
+            self.accumulator += *sample;
+            *sample = self.accumulator;
+        }
+        deltas
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Play the pattern from `from` to `to` (in samples), adding a step whenever the level changes.
+    fn advance(&mut self, deltas: &mut [f32], from: f64, to: f64) {
+        if !self.state.playing {
+            return;
+        }
+        let bits_per_sample = pitch_to_rate(self.state.pitch) / OUTPUT_SAMPLE_RATE as f64;
+        let mut time = from;
+        loop {
+            let next_bit = self.phase.floor() + 1.0;
+            let next_time = time + (next_bit - self.phase) / bits_per_sample;
+            if next_time > to {
+                let phase = self.phase + (to - time) * bits_per_sample;
+                if phase < next_bit {
+                    self.phase = phase;
+                    return;
+                }
+                // Rounding put the end on the next bit, so it still has to start (and wrap)
+                time = to;
             } else {
-                samples.push(-1.0);
+                time = next_time;
             }
+            self.phase = next_bit % 128.0;
+            self.update_level(deltas, time);
         }
-        samples
     }
 
-    fn reset(&mut self) {
-        self.counter = 0.0;
+    /// Change anything that's different from `state`.
+    fn sync(&mut self, deltas: &mut [f32], time: f64, state: AudioState) {
+        if self.state.pattern != state.pattern {
+            self.apply(deltas, time, AudioChange::Pattern(state.pattern));
+        }
+        if self.state.pitch != state.pitch {
+            self.apply(deltas, time, AudioChange::Pitch(state.pitch));
+        }
+        if self.state.playing != state.playing {
+            self.apply(deltas, time, AudioChange::Playing(state.playing));
+        }
+    }
+
+    fn apply(&mut self, deltas: &mut [f32], time: f64, change: AudioChange) {
+        match change {
+            AudioChange::Pattern(pattern) => self.state.pattern = pattern,
+            AudioChange::Pitch(pitch) => self.state.pitch = pitch,
+            AudioChange::Playing(playing) => {
+                if playing && !self.state.playing {
+                    self.phase = 0.0;
+                }
+                self.state.playing = playing;
+            }
+        }
+        self.update_level(deltas, time);
+    }
+
+    fn update_level(&mut self, deltas: &mut [f32], time: f64) {
+        let level = if !self.state.playing {
+            0.0
+        } else if u128::from_be_bytes(self.state.pattern) & (1 << (127 - self.phase as u32)) != 0 {
+            1.0
+        } else {
+            -1.0
+        };
+        if level != self.level {
+            add_step(deltas, time, level - self.level);
+            self.level = level;
+        }
+    }
+}
+
+fn add_step(deltas: &mut [f32], time: f64, delta: f32) {
+    let sample = time.floor();
+    let phase = ((time - sample) * KERNEL_PHASES as f64).round() as usize;
+    for (value, tap) in deltas[sample as usize..].iter_mut().zip(STEP_KERNEL[phase]) {
+        *value += delta * tap;
     }
 }
 
 fn pitch_to_rate(pitch: u8) -> f64 {
     4000.0 * 2.0f64.powf((pitch as f64 - 64.0) / 48.0)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{sample_count, Chip8Synth, DEFAULT_PATTERN, KERNEL_HALF_WIDTH, KERNEL_WIDTH};
+    use crate::{
+        hardware::{AudioChange, AudioEvent, AudioFrame, AudioState, DynamicMachine, Machine},
+        headless::{self, HeadlessOptions},
+        model::DynamicModel,
+    };
+
+    const TIMESTEP: f64 = 1.0 / 60.0;
+
+    // Starts a long beep at pitch 0x70, then loops forever
+    const BEEP_ROM: &[u8] = &[0x60, 0xFF, 0xF0, 0x18, 0x61, 0x70, 0xF1, 0x3A, 0x12, 0x08];
+
+    fn frame(events: &[(u32, AudioChange)], playing: bool) -> AudioFrame {
+        let started = events
+            .iter()
+            .find_map(|(_, change)| match change {
+                AudioChange::Playing(playing) => Some(!playing),
+                _ => None,
+            })
+            .unwrap_or(playing);
+        AudioFrame {
+            start: AudioState {
+                pattern: DEFAULT_PATTERN,
+                pitch: 64,
+                playing: started,
+            },
+            events: events
+                .iter()
+                .map(|&(cycle, change)| AudioEvent { cycle, change })
+                .collect(),
+            cycles: 1000,
+            end: AudioState {
+                pattern: DEFAULT_PATTERN,
+                pitch: 64,
+                playing,
+            },
+        }
+    }
+
+    #[test]
+    fn test_audio_events() {
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, BEEP_ROM);
+        machine.run_frame(10).unwrap();
+        let audio = machine.audio_frame();
+        assert_eq!(
+            audio.events,
+            vec![
+                AudioEvent {
+                    cycle: 1,
+                    change: AudioChange::Playing(true)
+                },
+                AudioEvent {
+                    cycle: 3,
+                    change: AudioChange::Pitch(0x70)
+                },
+            ]
+        );
+        assert_eq!(audio.cycles, 10);
+        assert_eq!(audio.end.pitch, 0x70);
+        assert!(audio.end.playing);
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut synth = Chip8Synth::new();
+        let samples = synth.render_frame(&frame(&[], false), TIMESTEP);
+        assert_eq!(samples.len(), sample_count(TIMESTEP));
+        assert!(samples.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn test_mid_frame_start() {
+        let mut synth = Chip8Synth::new();
+        let samples =
+            synth.render_frame(&frame(&[(500, AudioChange::Playing(true))], true), TIMESTEP);
+        let start = samples.len() / 2;
+        assert!(samples[..start].iter().all(|sample| *sample == 0.0));
+        assert!(samples[start..]
+            .chunks(2 * KERNEL_HALF_WIDTH)
+            .all(|chunk| chunk.iter().any(|sample| sample.abs() > 0.9)));
+
+        let samples = synth.render_frame(
+            &frame(&[(250, AudioChange::Playing(false))], false),
+            TIMESTEP,
+        );
+        let stop = samples.len() / 4 + 2 * KERNEL_HALF_WIDTH;
+        assert!(samples[stop..].iter().all(|sample| sample.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_band_limited_square_wave() {
+        let mut synth = Chip8Synth::new();
+        let mut samples =
+            synth.render_frame(&frame(&[(0, AudioChange::Playing(true))], true), TIMESTEP);
+        for _ in 0..59 {
+            samples.extend(synth.render_frame(&frame(&[], true), TIMESTEP));
+        }
+
+        // The default pattern at the default pitch is a 500Hz square wave
+        // Ignore the ringing around each step
+        let crossings = samples
+            .iter()
+            .filter(|sample| sample.abs() > 0.5)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .filter(|pair| (*pair[0] < 0.0) != (*pair[1] < 0.0))
+            .count();
+        assert!(
+            (995..=1001).contains(&crossings),
+            "{crossings} zero crossings"
+        );
+
+        // Band-limited steps overshoot a little, but never by much, and take more than one sample
+        assert!(samples.iter().all(|sample| sample.abs() < 1.3));
+        assert!(samples
+            .iter()
+            .any(|sample| sample.abs() > 0.1 && sample.abs() < 0.9));
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        assert!(mean.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_phase_wraps_on_bit_boundary() {
+        let mut synth = Chip8Synth::new();
+        let mut deltas = vec![0.0; 24 + KERNEL_WIDTH];
+        let mut pattern = [0; 16];
+        pattern[0] = 0x80;
+        synth.apply(&mut deltas, 0.0, AudioChange::Pattern(pattern));
+        synth.apply(&mut deltas, 0.0, AudioChange::Playing(true));
+
+        // Just short of the end of the last bit, but the phase rounds to exactly 128
+        synth.phase = 127.00001;
+        synth.advance(&mut deltas, 0.0, 11.024889749999963);
+        assert_eq!(synth.phase, 0.0);
+        assert_eq!(synth.level, 1.0);
+        synth.advance(&mut deltas, 11.024889749999963, 24.0);
+        assert_eq!(synth.level, -1.0);
+    }
+
+    #[test]
+    fn test_model_pattern() {
+        // Starts a long beep, then loops forever
+        let rom = [0x60, 0xFF, 0xF0, 0x18, 0x12, 0x04];
+        let mut machine = DynamicMachine::new(DynamicModel::COSMAC_VIP, &rom);
+        machine.run_frame(10).unwrap();
+        let mut synth = Chip8Synth::new();
+        let samples = synth.generate_frame(&machine, TIMESTEP);
+
+        // The VIP's pattern is a 1kHz square wave, from the very first frame
+        let crossings = samples
+            .iter()
+            .filter(|sample| sample.abs() > 0.5)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .filter(|pair| (*pair[0] < 0.0) != (*pair[1] < 0.0))
+            .count();
+        assert!((26..=30).contains(&crossings), "{crossings} zero crossings");
+    }
+
+    #[test]
+    fn test_headless_audio() {
+        let mut options = HeadlessOptions::new(DynamicModel::XO_CHIP);
+        options.frames = Some(10);
+        let mut synth = Chip8Synth::new();
+        let mut samples = Vec::new();
+        headless::run(BEEP_ROM, &options, |machine| {
+            samples.extend(synth.generate_frame(machine, TIMESTEP))
+        })
+        .unwrap();
+
+        assert_eq!(samples.len(), sample_count(TIMESTEP) * 10);
+        assert!(samples.iter().any(|sample| *sample > 0.9));
+        assert!(samples.iter().any(|sample| *sample < -0.9));
+    }
+}
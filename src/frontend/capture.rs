@@ -0,0 +1,304 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::*, tasks::IoTaskPool};
+use bevy_egui::egui;
+use image::{ImageFormat, Rgba, RgbaImage};
+
+use crate::{
+    recording::{AnimationFormat, FrameRecorder},
+    upscale::UpscaleFilter,
+};
+
+use super::{
+    audio::Chip8Audio,
+    debug::{DebugOptions, GridSize},
+    machine::Machine,
+    EmulatorData, EmulatorEvent,
+};
+
+pub const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+
+#[derive(Resource, Debug, Clone)]
+pub struct Capture {
+    /// Each emulated pixel becomes a `scale` by `scale` square in the saved image, rounded up to a
+    /// multiple of the upscaling filter's factor.
+    pub scale: u32,
+    /// Draw the debug grid, as currently configured, over the screenshot.
+    pub include_grid: bool,
+    pub directory: PathBuf,
+    pub clip_format: AnimationFormat,
+    /// The clip currently being recorded, if any.
+    pub clip: Option<FrameRecorder>,
+    /// Where audio is currently being captured to, if anywhere.
+    pub wav_path: Option<PathBuf>,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            include_grid: false,
+            directory: PathBuf::from("screenshots"),
+            clip_format: AnimationFormat::default(),
+            clip: None,
+            wav_path: None,
+        }
+    }
+}
+
+impl Capture {
+    fn file_path(&self, rom_name: Option<&str>, extension: &str) -> PathBuf {
+        let rom_name = rom_name
+            .map(|name| name.rsplit_once('.').map_or(name, |(stem, _)| stem))
+            .unwrap_or("screenshot");
+        self.directory
+            .join(format!("{rom_name}-{}.{extension}", timestamp()))
+    }
+}
+
+pub fn capture_plugin(app: &mut App) {
+    app.init_resource::<Capture>().add_systems(
+        PostUpdate,
+        (take_screenshot, toggle_clip_recording, toggle_audio_capture),
+    );
+}
+
+fn take_screenshot(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut ui_events: EventReader<EmulatorEvent>,
+    machine: Res<Machine>,
+    emulator_data: Res<EmulatorData>,
+    debug_options: Res<DebugOptions>,
+    settings: Res<Capture>,
+) {
+    let requested = ui_events
+        .read()
+        .any(|event| matches!(event, EmulatorEvent::Screenshot));
+    if !requested && !keys.just_pressed(SCREENSHOT_KEY) {
+        return;
+    }
+
+    let grid = if settings.include_grid {
+        debug_options.debug_grid()
+    } else {
+        GridSize::None
+    };
+    let image = screenshot_image(
+        &machine.render_frame(&emulator_data.palette),
+        settings.scale,
+        emulator_data.upscale,
+        grid,
+    );
+    let path = settings.file_path(emulator_data.rom_name.as_deref(), "png");
+
+    IoTaskPool::get()
+        .spawn(async move {
+            let result = std::fs::create_dir_all(path.parent().unwrap_or(&path))
+                .map_err(image::ImageError::IoError)
+                .and_then(|_| image.save_with_format(&path, ImageFormat::Png));
+            match result {
+                Ok(()) => info!("Saved screenshot to {}", path.display()),
+                Err(error) => error!("Error saving screenshot to {}: {}", path.display(), error),
+            }
+        })
+        .detach();
+}
+
+fn toggle_clip_recording(
+    mut ui_events: EventReader<EmulatorEvent>,
+    machine: Res<Machine>,
+    emulator_data: Res<EmulatorData>,
+    mut capture: ResMut<Capture>,
+) {
+    if !ui_events
+        .read()
+        .any(|event| matches!(event, EmulatorEvent::ToggleClipRecording))
+    {
+        return;
+    }
+
+    let Some(clip) = capture.clip.take() else {
+        capture.clip = Some(
+            FrameRecorder::new(&emulator_data.palette, emulator_data.frame_rate)
+                .with_screen_size(machine.instruction_set(), emulator_data.upscale),
+        );
+        return;
+    };
+    let format = capture.clip_format;
+    let path = capture.file_path(emulator_data.rom_name.as_deref(), format.extension());
+    IoTaskPool::get()
+        .spawn(async move {
+            let result = std::fs::create_dir_all(path.parent().unwrap_or(&path))
+                .and_then(|_| std::fs::File::create(&path))
+                .map_err(|error| error.to_string())
+                .and_then(|file| {
+                    clip.encode(format, std::io::BufWriter::new(file))
+                        .map_err(|error| error.to_string())
+                });
+            match result {
+                Ok(()) => info!("Saved {format} clip to {}", path.display()),
+                Err(error) => error!("Error saving clip to {}: {}", path.display(), error),
+            }
+        })
+        .detach();
+}
+
+fn toggle_audio_capture(
+    mut ui_events: EventReader<EmulatorEvent>,
+    emulator_data: Res<EmulatorData>,
+    mut capture: ResMut<Capture>,
+    mut audio: ResMut<Chip8Audio>,
+) {
+    if !ui_events
+        .read()
+        .any(|event| matches!(event, EmulatorEvent::ToggleAudioCapture))
+    {
+        return;
+    }
+
+    if capture.wav_path.take().is_some() {
+        audio.stop_capture();
+        return;
+    }
+
+    let path = capture.file_path(emulator_data.rom_name.as_deref(), "wav");
+    audio.start_capture(path.clone());
+    capture.wav_path = Some(path);
+}
+
+/// Scale a frame up by an integer factor, optionally drawing the debug grid on the first row and
+/// column of each grid cell. The scale is rounded up to a multiple of the upscaling filter's own
+/// factor, so that none of its detail is lost and every filtered pixel stays the same size.
+pub fn screenshot_image(
+    frame: &RgbaImage,
+    scale: u32,
+    filter: UpscaleFilter,
+    grid: GridSize,
+) -> RgbaImage {
+    let factor = filter.factor();
+    let scale = scale.max(1).div_ceil(factor) * factor;
+    let filtered = filter.apply(frame);
+    let repeat = scale / factor;
+    let mut image = RgbaImage::from_fn(frame.width() * scale, frame.height() * scale, |x, y| {
+        *filtered.get_pixel(x / repeat, y / repeat)
+    });
+
+    for x in 1..frame.width() {
+        if let Some(color) = grid.line_color(x) {
+            for y in 0..image.height() {
+                image.put_pixel(x * scale, y, Rgba(color.to_array()));
+            }
+        }
+    }
+    for y in 1..frame.height() {
+        if let Some(color) = grid.line_color(y) {
+            for x in 0..image.width() {
+                image.put_pixel(x, y * scale, Rgba(color.to_array()));
+            }
+        }
+    }
+
+    image
+}
+
+/// The current UTC time as `YYYYMMDD-HHMMSS.mmm`.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs();
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Convert days since the epoch to a civil date (from Howard Hinnant's date algorithms)
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}.{:03}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        now.subsec_millis()
+    )
+}
+
+pub fn show_capture_options(
+    ui: &mut egui::Ui,
+    settings: &mut Capture,
+) -> egui::CollapsingResponse<()> {
+    egui::CollapsingHeader::new("Screenshots and Clips").show(ui, |ui| {
+        ui.add(egui::Slider::new(&mut settings.scale, 1..=16).text("Screenshot scale"));
+        ui.checkbox(&mut settings.include_grid, "Include debug grid");
+        ui.horizontal(|ui| {
+            ui.label("Directory:");
+            let mut directory = settings.directory.to_string_lossy().into_owned();
+            if ui.text_edit_singleline(&mut directory).changed() {
+                settings.directory = PathBuf::from(directory);
+            }
+        });
+        ui.add_enabled_ui(settings.clip.is_none(), |ui| {
+            egui::ComboBox::from_label("Clip format")
+                .selected_text(settings.clip_format.to_string())
+                .show_ui(ui, |ui| {
+                    for format in [AnimationFormat::Gif, AnimationFormat::Apng] {
+                        ui.selectable_value(&mut settings.clip_format, format, format.to_string());
+                    }
+                });
+        });
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::screenshot_image;
+    use crate::{frontend::debug::GridSize, upscale::UpscaleFilter};
+
+    #[test]
+    fn test_screenshot_scale() {
+        let frame = RgbaImage::from_fn(4, 4, |x, y| {
+            if x == y {
+                Rgba([255; 4])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+        // Scales that aren't a multiple of the filter's factor round up to the next one, so that
+        // each filtered pixel becomes the same size square
+        for (filter, scale, expected) in [
+            (UpscaleFilter::Scale3x, 4, 6),
+            (UpscaleFilter::Hq2x, 3, 4),
+            (UpscaleFilter::Xbr2x, 5, 6),
+            (UpscaleFilter::Hq4x, 1, 4),
+            (UpscaleFilter::None, 3, 3),
+        ] {
+            let image = screenshot_image(&frame, scale, filter, GridSize::None);
+            assert_eq!(image.dimensions(), (4 * expected, 4 * expected), "{filter}");
+            let filtered = filter.apply(&frame);
+            let repeat = expected / filter.factor();
+            for (x, y, pixel) in image.enumerate_pixels() {
+                assert_eq!(
+                    pixel,
+                    filtered.get_pixel(x / repeat, y / repeat),
+                    "{filter} at ({x}, {y})"
+                );
+            }
+        }
+    }
+}
@@ -1,27 +1,28 @@
 use std::{
     collections::BTreeSet,
     f32,
-    fmt::{Display, UpperHex},
-    ops::SubAssign,
+    fmt::Display,
+    ops::{Range, SubAssign},
 };
 
+use arbitrary_int::u4;
 use bevy::prelude::*;
 use bevy_egui::{
-    egui::{self, style::ScrollAnimation, Ui},
+    egui::{self, emath, style::ScrollAnimation, Ui},
     EguiContexts,
 };
 use bevy_inspector_egui::bevy_inspector;
 use range_vec::RangeVec;
 
 use crate::{
-    hardware::{self, Machine as HardwareMachine},
+    hardware::{self, Register},
     instruction::{ExecuteInstruction, InstructionSet, OctoSyntax},
     model::Quirks,
 };
 
 use super::{
     layout::ScaleToDisplay,
-    machine::{Machine, ToMachine},
+    machine::{ErrorPolicy, Machine, MovieStatus, ToMachine},
     ui::style,
     EmulatorData, EmulatorEvent, Frame, FRAME_ASPECT_RATIO,
 };
@@ -55,16 +56,51 @@ impl Display for GridSize {
     }
 }
 
+impl GridSize {
+    /// The color of the grid line before pixel `i`, if there is one.
+    pub fn line_color(self, i: u32) -> Option<egui::Color32> {
+        if i.is_multiple_of(8) && self >= GridSize::Eight {
+            Some(egui::Color32::RED)
+        } else if i.is_multiple_of(4) && self >= GridSize::Four {
+            Some(egui::Color32::BLUE)
+        } else if i.is_multiple_of(2) && self >= GridSize::Two {
+            // CSS green
+            Some(egui::Color32::from_rgb(0, 128, 0))
+        } else {
+            None
+        }
+    }
+}
+
+impl DebugOptions {
+    pub fn debug_grid(&self) -> GridSize {
+        self.debug_grid
+    }
+}
+
+/// Bytes for the memory view to scroll to and highlight, the next time it's shown.
+#[derive(Resource, Default)]
+pub struct MemoryJump(pub Option<Range<usize>>);
+
 pub fn debug_plugin(app: &mut App) {
     app.init_resource::<DebugOptions>()
+        .init_resource::<MemoryJump>()
         .add_systems(Startup, setup);
 }
 
 pub fn show_debug_options(
     ui: &mut Ui,
     debug_options: &mut DebugOptions,
+    error_policy: &mut ErrorPolicy,
 ) -> egui::CollapsingResponse<()> {
     egui::CollapsingHeader::new("Debugging Options").show(ui, |ui| {
+        egui::ComboBox::from_label("On emulator error")
+            .selected_text(error_policy.to_string())
+            .show_ui(ui, |ui| {
+                for policy in ErrorPolicy::ALL {
+                    ui.selectable_value(error_policy, policy, policy.to_string());
+                }
+            });
         egui::ComboBox::from_label("Debug grid")
             .selected_text(debug_options.debug_grid.to_string())
             .show_ui(ui, |ui| {
@@ -124,49 +160,22 @@ pub fn render_grid_egui(
     );
     let grid_spacing = ratio.0 * transform.scale.xy() / frame.size.as_vec2();
 
-    // CSS green
-    let green = egui::Color32::from_rgb(0, 128, 0);
-
     for i in 1..frame.size.x {
-        if i % 8 == 0 {
+        if let Some(color) = debug_options.debug_grid.line_color(i) {
             painter.vline(
                 rect.left() + (i as f32 * grid_spacing.x),
                 rect.y_range(),
-                (2.0, egui::Color32::RED),
-            );
-        } else if debug_options.debug_grid >= GridSize::Four && i % 4 == 0 {
-            painter.vline(
-                rect.left() + (i as f32 * grid_spacing.x),
-                rect.y_range(),
-                (2.0, egui::Color32::BLUE),
-            );
-        } else if debug_options.debug_grid >= GridSize::Two && i % 2 == 0 {
-            painter.vline(
-                rect.left() + (i as f32 * grid_spacing.x),
-                rect.y_range(),
-                (2.0, green),
+                (2.0, color),
             );
         }
     }
 
     for i in 1..frame.size.y {
-        if i % 8 == 0 {
+        if let Some(color) = debug_options.debug_grid.line_color(i) {
             painter.hline(
                 rect.x_range(),
                 rect.top() + (i as f32 * grid_spacing.y),
-                (2.0, egui::Color32::RED),
-            );
-        } else if debug_options.debug_grid >= GridSize::Four && i % 4 == 0 {
-            painter.hline(
-                rect.x_range(),
-                rect.top() + (i as f32 * grid_spacing.y),
-                (2.0, egui::Color32::BLUE),
-            );
-        } else if debug_options.debug_grid >= GridSize::Two && i % 2 == 0 {
-            painter.hline(
-                rect.x_range(),
-                rect.top() + (i as f32 * grid_spacing.y),
-                (2.0, green),
+                (2.0, color),
             );
         }
     }
@@ -208,6 +217,14 @@ pub fn debugger_ui(
             machine.tx.try_send(ToMachine::Step).unwrap();
         }
 
+        if large_button(ui, "⏭", true, false)
+            .on_hover_text("Next Frame")
+            .clicked()
+        {
+            emulator_data.paused = true;
+            emulator_events.send(EmulatorEvent::FrameAdvance);
+        }
+
         if large_button(ui, "⟲", true, false)
             .on_hover_text("Reset")
             .clicked()
@@ -228,10 +245,10 @@ pub fn debugger_ui(
         machine.tx.try_send(ToMachine::ClearBreakpoints).unwrap();
     }
 
-    let memory = machine.machine.memory();
-    let pc = machine.machine.cpu().pc;
-    let quirks = machine.machine.quirks();
-    let instruction_set = machine.machine.instruction_set();
+    let memory = machine.memory();
+    let pc = machine.cpu().pc;
+    let quirks = machine.quirks();
+    let instruction_set = machine.instruction_set();
 
     let pc_usize = pc as usize;
     let is_odd = state.is_odd.unwrap_or(pc % 2 == 1);
@@ -274,7 +291,7 @@ pub fn debugger_ui(
                             is_long_operand,
                             long_operand,
                             instruction,
-                        }) = get_opcode(memory, address, quirks, instruction_set)
+                        }) = get_opcode(&memory, address, quirks, instruction_set)
                         {
                             let color = if is_long_operand {
                                 style::NEUTRAL_MID
@@ -319,6 +336,71 @@ pub fn debugger_ui(
     });
 }
 
+/// Shows the error the machine halted on, with ways to carry on from it.
+pub fn error_banner_ui(
+    ui: InMut<Ui>,
+    machine: Res<Machine>,
+    mut emulator_data: ResMut<EmulatorData>,
+    mut emulator_events: EventWriter<EmulatorEvent>,
+) {
+    let Some(error) = machine.error.as_ref() else {
+        return;
+    };
+    ui.0.horizontal(|ui| {
+        ui.vertical(|ui| {
+            ui.colored_label(
+                style::ACCENT_LIGHT,
+                format!("Emulator error: {}", error.error.kind()),
+            );
+            let Some(context) = error.error.context() else {
+                return;
+            };
+            let instruction = match (context.opcode, context.disassembly.as_deref()) {
+                (Some(opcode), Some(instruction)) => format!("{opcode:04X}  {instruction}"),
+                (Some(opcode), None) => format!("{opcode:04X}  ????"),
+                (None, _) => "outside memory".to_owned(),
+            };
+            ui.label(format!("At {:04X}: {instruction}", context.pc));
+            ui.label(format!(
+                "{}, I = {:04X}, SP = {}",
+                context.instruction_set, context.i, context.sp
+            ));
+            ui.label(if context.backtrace.is_empty() {
+                "Call stack: empty".to_owned()
+            } else {
+                let call_stack = context
+                    .backtrace
+                    .iter()
+                    .map(|address| format!("{address:04X}"))
+                    .collect::<Vec<_>>();
+                format!("Call stack: {}", call_stack.join(" ← "))
+            });
+        });
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.button("Reset").clicked() {
+                emulator_events.send(EmulatorEvent::ResetMachine);
+            }
+            if ui
+                .add_enabled(
+                    machine.movie_status == MovieStatus::Idle,
+                    egui::Button::new("Skip Instruction"),
+                )
+                .clicked()
+            {
+                machine.tx.try_send(ToMachine::SkipInstruction).unwrap();
+            }
+            if ui
+                .button("Resume")
+                .on_hover_text("Run the failed instruction again, after changing what made it fail")
+                .clicked()
+            {
+                emulator_data.paused = false;
+            }
+        });
+    });
+}
+
 fn breakpoint_button(
     ui: &mut Ui,
     breakpoints: &mut BTreeSet<usize>,
@@ -469,6 +551,10 @@ pub struct MemoryState {
     bytes_per_row: usize,
     last_memory: RangeVec<u8>,
     change_counters: RangeVec<u8>,
+    /// The address of the byte being edited, and what's been typed so far.
+    editing: Option<(usize, String)>,
+    /// The bytes of the last jump, and the time it was made, until it fades or a byte is clicked.
+    highlight: Option<(Range<usize>, f64)>,
 }
 
 impl Default for MemoryState {
@@ -477,6 +563,8 @@ impl Default for MemoryState {
             bytes_per_row: 8,
             last_memory: RangeVec::new(),
             change_counters: RangeVec::new(),
+            editing: None,
+            highlight: None,
         }
     }
 }
@@ -510,11 +598,18 @@ impl MemoryState {
     }
 }
 
-pub fn memory_ui(ui: InMut<Ui>, machine: Option<Res<Machine>>, mut state: Local<MemoryState>) {
-    let memory = machine
-        .as_ref()
-        .map(|machine| machine.machine.memory())
-        .unwrap_or(&[]);
+const BYTE_EDIT_ID: &str = "memory_byte_edit";
+const HIGHLIGHT_SECONDS: f64 = 3.0;
+
+/// Shows memory as hex, where clicking a byte lets you type a new value for it.
+pub fn memory_ui(
+    ui: InMut<Ui>,
+    machine: Option<Res<Machine>>,
+    mut memory_jump: ResMut<MemoryJump>,
+    mut state: Local<MemoryState>,
+) {
+    let memory = machine.as_ref().map(|machine| machine.memory());
+    let memory = memory.as_deref().map_or(&[][..], Vec::as_slice);
     let num_rows = if memory.is_empty() {
         0
     } else {
@@ -528,46 +623,116 @@ pub fn memory_ui(ui: InMut<Ui>, machine: Option<Res<Machine>>, mut state: Local<
         ui.selectable_value(&mut state.bytes_per_row, 32, "32");
     });
 
+    let mut pokes = Vec::new();
     ui.0.scope(|ui| {
         ui.spacing_mut().item_spacing.x = 8.0;
+        let font = egui::TextStyle::Body.resolve(ui.style());
+        let byte_width = ui.fonts(|fonts| fonts.glyph_width(&font, '0')) * 2.0;
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        let mut scroll_area = egui::ScrollArea::both().auto_shrink(false);
+        let now = ui.input(|input| input.time);
+        if state
+            .highlight
+            .as_ref()
+            .is_some_and(|(_, time)| now - time > HIGHLIGHT_SECONDS)
+        {
+            state.highlight = None;
+        }
+        if let Some(range) = memory_jump.0.take() {
+            // Bring the first row of the jump into the middle of the view
+            let row = range.start / state.bytes_per_row;
+            let offset = row as f32 * (row_height + ui.spacing().item_spacing.y)
+                - (ui.available_height() - row_height) / 2.0;
+            scroll_area = scroll_area.vertical_scroll_offset(offset.max(0.0));
+            state.highlight = Some((range, now));
+        }
 
-        egui::ScrollArea::both().auto_shrink(false).show_rows(
-            ui,
-            ui.text_style_height(&egui::TextStyle::Body),
-            num_rows,
-            |ui, rows| {
-                let range = rows.start * state.bytes_per_row..rows.end * state.bytes_per_row;
-                state.update(&memory[range.clone()], range.start);
-
-                for (i, chunk) in memory
-                    .chunks(state.bytes_per_row)
-                    .enumerate()
-                    .skip(rows.start)
-                    .take(rows.end - rows.start)
-                {
-                    ui.horizontal(|ui| {
-                        ui.label(format!("{:#06X}", i * state.bytes_per_row));
-                        for (j, byte) in chunk.iter().enumerate() {
-                            let base_color = if j % 2 == 0 {
-                                style::FOREGROUND_LIGHT
-                            } else {
-                                style::FOREGROUND_MID
-                            };
-                            let color = match state.get_counter(i, j) {
-                                counter @ ..=30 => base_color
-                                    .lerp_to_gamma(style::ACCENT_LIGHT, counter as f32 / 30.0),
-                                _ => base_color,
-                            };
-                            ui.colored_label(color, format!("{:02X}", byte));
+        scroll_area.show_rows(ui, row_height, num_rows, |ui, rows| {
+            let range = rows.start * state.bytes_per_row..rows.end * state.bytes_per_row;
+            state.update(&memory[range.clone()], range.start);
+
+            for (i, chunk) in memory
+                .chunks(state.bytes_per_row)
+                .enumerate()
+                .skip(rows.start)
+                .take(rows.end - rows.start)
+            {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{:#06X}", i * state.bytes_per_row));
+                    for (j, byte) in chunk.iter().enumerate() {
+                        let address = i * state.bytes_per_row + j;
+                        let base_color = if state
+                            .highlight
+                            .as_ref()
+                            .is_some_and(|(highlight, _)| highlight.contains(&address))
+                        {
+                            style::ACCENT_MID
+                        } else if j % 2 == 0 {
+                            style::FOREGROUND_LIGHT
+                        } else {
+                            style::FOREGROUND_MID
+                        };
+                        let color = match state.get_counter(i, j) {
+                            counter @ ..=30 => {
+                                base_color.lerp_to_gamma(style::ACCENT_LIGHT, counter as f32 / 30.0)
+                            }
+                            _ => base_color,
+                        };
+                        match state.editing.as_mut() {
+                            Some((edit_address, text)) if *edit_address == address => {
+                                let response = ui.add(
+                                    egui::TextEdit::singleline(text)
+                                        .id(egui::Id::new(BYTE_EDIT_ID))
+                                        .char_limit(2)
+                                        .desired_width(byte_width)
+                                        .margin(egui::Margin::ZERO),
+                                );
+                                if response.lost_focus() {
+                                    if ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                                        if let Ok(byte) = u8::from_str_radix(text, 16) {
+                                            pokes.push((address, byte));
+                                        }
+                                    }
+                                    state.editing = None;
+                                }
+                            }
+                            _ => {
+                                let response = ui
+                                    .add(
+                                        egui::Label::new(
+                                            egui::RichText::new(format!("{:02X}", byte))
+                                                .color(color),
+                                        )
+                                        .sense(egui::Sense::click()),
+                                    )
+                                    .on_hover_text(format!("{address:#06X}"));
+                                if response.clicked() {
+                                    state.highlight = None;
+                                    state.editing = Some((address, format!("{byte:02X}")));
+                                    ui.memory_mut(|memory| {
+                                        memory.request_focus(egui::Id::new(BYTE_EDIT_ID))
+                                    });
+                                }
+                            }
                         }
-                    });
-                }
+                    }
+                });
+            }
 
-                let range_start = range.start;
-                state.update(&memory[range], range_start);
-            },
-        );
+            let range_start = range.start;
+            state.update(&memory[range], range_start);
+        });
     });
+
+    if let Some(machine) = machine.as_ref() {
+        for (address, byte) in pokes {
+            machine
+                .tx
+                .try_send(ToMachine::Poke(address as u16, vec![byte]))
+                .unwrap();
+        }
+    }
 }
 
 pub struct Counters(hardware::Cpu);
@@ -586,11 +751,14 @@ pub fn registers_ui(
     machine: Option<Res<Machine>>,
     mut last_cpu: Local<hardware::Cpu>,
     mut counters: Local<Counters>,
+    mut push_address: Local<u16>,
 ) {
     let cpu = machine
-        .map(|machine| machine.machine.cpu().clone())
+        .as_ref()
+        .map(|machine| machine.cpu().clone())
         .unwrap_or_default();
     let counters = &mut counters.0;
+    let mut edits = Vec::new();
 
     ui.0.vertical(|ui| {
         egui::Grid::new("cpu_v_registers")
@@ -598,14 +766,17 @@ pub fn registers_ui(
             .show(ui, |ui| {
                 for i in 0..16 {
                     let reg = (i % 4 * 4) + (i / 4);
-                    show_register(
+                    let register = Register::V(u4::new(reg as u8));
+                    if let Some(value) = show_register(
                         ui,
-                        format!("v{:X}", reg),
+                        register.to_string(),
                         2,
                         cpu.v[reg],
                         &mut last_cpu.v[reg],
                         &mut counters.v[reg],
-                    );
+                    ) {
+                        edits.push(ToMachine::SetRegister(register, value.into()));
+                    }
                     if i % 4 == 3 {
                         ui.end_row();
                     }
@@ -613,17 +784,76 @@ pub fn registers_ui(
             });
 
         ui.add_space(ui.style().spacing.item_spacing.y);
-        show_register(ui, "PC:", 4, cpu.pc, &mut last_cpu.pc, &mut counters.pc);
+        if let Some(value) = show_register(ui, "PC:", 4, cpu.pc, &mut last_cpu.pc, &mut counters.pc)
+        {
+            edits.push(ToMachine::SetRegister(Register::Pc, value));
+        }
+
+        ui.add_space(ui.style().spacing.item_spacing.y);
+        if let Some(value) = show_register(ui, "I:", 4, cpu.i, &mut last_cpu.i, &mut counters.i) {
+            edits.push(ToMachine::SetRegister(Register::I, value));
+        }
 
         ui.add_space(ui.style().spacing.item_spacing.y);
-        show_register(ui, "I:", 4, cpu.i, &mut last_cpu.i, &mut counters.i);
+        if let Some(value) = show_register(ui, "DT:", 2, cpu.dt, &mut last_cpu.dt, &mut counters.dt)
+        {
+            edits.push(ToMachine::SetRegister(Register::Dt, value.into()));
+        }
+        if let Some(value) = show_register(ui, "ST:", 2, cpu.st, &mut last_cpu.st, &mut counters.st)
+        {
+            edits.push(ToMachine::SetRegister(Register::St, value.into()));
+        }
 
         ui.add_space(ui.style().spacing.item_spacing.y);
-        show_register(ui, "DT:", 2, cpu.dt, &mut last_cpu.dt, &mut counters.dt);
-        show_register(ui, "ST:", 2, cpu.st, &mut last_cpu.st, &mut counters.st);
+        ui.horizontal(|ui| {
+            ui.colored_label(style::FOREGROUND_MID, "SP:");
+            let mut sp = cpu.sp.value();
+            if ui
+                .add(
+                    egui::DragValue::new(&mut sp)
+                        .hexadecimal(1, false, true)
+                        .range(0..=15),
+                )
+                .changed()
+            {
+                edits.push(ToMachine::SetRegister(Register::Sp, sp.into()));
+            }
+        });
+        for (level, address) in cpu.stack[1..=cpu.sp.value() as usize]
+            .iter()
+            .enumerate()
+            .rev()
+        {
+            ui.colored_label(
+                style::FOREGROUND_LIGHT,
+                format!("{:X}: {address:04X}", level + 1),
+            );
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut *push_address).hexadecimal(4, false, true));
+            if ui
+                .add_enabled(cpu.sp.value() < 15, egui::Button::new("Push"))
+                .clicked()
+            {
+                edits.push(ToMachine::PushStack(*push_address));
+            }
+            if ui
+                .add_enabled(cpu.sp.value() > 0, egui::Button::new("Pop"))
+                .clicked()
+            {
+                edits.push(ToMachine::PopStack);
+            }
+        });
     });
+
+    if let Some(machine) = machine {
+        for edit in edits {
+            machine.tx.try_send(edit).unwrap();
+        }
+    }
 }
 
+/// Shows a register, highlighted when it changes, returning its new value if it was edited.
 fn show_register<V>(
     ui: &mut Ui,
     label: impl Into<egui::RichText>,
@@ -631,21 +861,26 @@ fn show_register<V>(
     value: V,
     last_value: &mut V,
     counter: &mut V,
-) where
-    V: Copy + Display + UpperHex + Eq + Ord + SubAssign + From<u8> + Into<f32>,
+) -> Option<V>
+where
+    V: Copy + Eq + Ord + SubAssign + From<u8> + Into<f32> + emath::Numeric,
 {
     if value != *last_value {
         *counter = 30.into();
     } else if *counter > 0.into() {
         *counter -= 1.into();
     }
-    // println!("value: {value}, last_value: {last_value}, counter: {counter}");
     *last_value = value;
     let color =
         style::FOREGROUND_LIGHT.lerp_to_gamma(style::ACCENT_LIGHT, (*counter).into() / 30.0);
 
     ui.horizontal(|ui| {
         ui.colored_label(style::FOREGROUND_MID, label);
-        ui.colored_label(color, format!("{1:00$X}", digits, value));
-    });
+        ui.visuals_mut().override_text_color = Some(color);
+        let mut new_value = value;
+        ui.add(egui::DragValue::new(&mut new_value).hexadecimal(digits, false, true))
+            .changed()
+            .then_some(new_value)
+    })
+    .inner
 }
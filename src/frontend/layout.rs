@@ -9,7 +9,8 @@ use egui_tiles::{Container, Linear, LinearDir, SimplificationOptions, Tile, Tile
 
 use super::{
     debug,
-    ui::{draw_main_ui, style},
+    machine::Machine,
+    ui::{draw_main_ui, key_bindings_ui, keypad_ui, sprite_viewer_ui, style},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -19,6 +20,9 @@ pub enum EmulatorTab {
     Debugger,
     Memory,
     Registers,
+    Sprites,
+    Keypad,
+    KeyBindings,
     BevyInspector,
     EguiInspector,
 }
@@ -31,6 +35,9 @@ impl Display for EmulatorTab {
             EmulatorTab::Debugger => write!(f, "Debugger"),
             EmulatorTab::Memory => write!(f, "Memory"),
             EmulatorTab::Registers => write!(f, "Registers"),
+            EmulatorTab::Sprites => write!(f, "Sprites"),
+            EmulatorTab::Keypad => write!(f, "Keypad"),
+            EmulatorTab::KeyBindings => write!(f, "Key Bindings"),
             EmulatorTab::BevyInspector => write!(f, "Bevy Inspector"),
             EmulatorTab::EguiInspector => write!(f, "Egui Inspector"),
         }
@@ -85,6 +92,21 @@ impl egui_tiles::Behavior<EmulatorTab> for Behavior<'_> {
                             .run_system_cached_with(debug::registers_ui, ui)
                             .expect("failed to draw registers UI");
                     }
+                    EmulatorTab::Sprites => {
+                        self.world
+                            .run_system_cached_with(sprite_viewer_ui, ui)
+                            .expect("failed to draw sprite viewer UI");
+                    }
+                    EmulatorTab::Keypad => {
+                        self.world
+                            .run_system_cached_with(keypad_ui, ui)
+                            .expect("failed to draw keypad UI");
+                    }
+                    EmulatorTab::KeyBindings => {
+                        self.world
+                            .run_system_cached_with(key_bindings_ui, ui)
+                            .expect("failed to draw key bindings UI");
+                    }
                     EmulatorTab::BevyInspector => {
                         self.world
                             .run_system_cached_with(debug::bevy_inspector_ui, ui)
@@ -253,6 +275,9 @@ fn setup(mut commands: Commands) {
             EmulatorTab::Debugger,
             EmulatorTab::Memory,
             EmulatorTab::Registers,
+            EmulatorTab::Sprites,
+            EmulatorTab::Keypad,
+            EmulatorTab::KeyBindings,
             EmulatorTab::BevyInspector,
             EmulatorTab::EguiInspector,
         ],
@@ -278,6 +303,19 @@ fn draw_ui(world: &mut World) {
             available_panes,
             add_pane: &mut add_pane,
         };
+        if behavior.world.resource::<Machine>().error.is_some() {
+            egui::TopBottomPanel::top("error_banner")
+                .frame(
+                    egui::Frame::side_top_panel(&egui_context.get_mut().style())
+                        .fill(style::ACCENT_DARK),
+                )
+                .show(egui_context.get_mut(), |ui| {
+                    behavior
+                        .world
+                        .run_system_cached_with(debug::error_banner_ui, ui)
+                        .expect("failed to draw error banner");
+                });
+        }
         egui::CentralPanel::default()
             .frame(egui::Frame::none())
             .show(egui_context.get_mut(), |ui| {
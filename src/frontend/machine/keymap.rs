@@ -1,11 +1,126 @@
+use std::fmt::Display;
+
 use arbitrary_int::u4;
-use bevy::{ecs::system::Resource, input::keyboard::KeyCode, utils::HashMap};
+use bevy::{
+    input::{
+        gamepad::{GamepadAxisChangedEvent, GamepadButtonChangedEvent},
+        keyboard::{Key, KeyCode, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+    reflect::{DynamicEnum, DynamicVariant},
+    utils::HashMap,
+};
 
-#[derive(Resource)]
-pub struct KeyMapping {
-    pub keys: HashMap<KeyCode, u4>,
+use crate::{
+    frontend::{
+        settings::{Settings, SettingsFile},
+        EmulatorData,
+    },
+    hardware::KeyEvent,
+};
+
+/// The hex keys in the order they appear on the COSMAC VIP keypad, row by row.
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KeyBinding {
+    /// Matches a key by its position on the keyboard, regardless of layout.
+    Physical(KeyCode),
+    /// Matches a key by what it types in the current keyboard layout.
+    Logical(Key),
+}
+
+impl KeyBinding {
+    pub fn matches(&self, event: &KeyboardInput) -> bool {
+        match self {
+            KeyBinding::Physical(key_code) => event.key_code == *key_code,
+            KeyBinding::Logical(key) => event.logical_key == *key,
+        }
+    }
+
+    /// How the binding is written in the settings file, unless it's a key that can't be named.
+    fn to_setting(&self) -> Option<String> {
+        match self {
+            KeyBinding::Physical(KeyCode::Unidentified(_)) => None,
+            KeyBinding::Physical(key_code) => Some(format!("physical:{key_code:?}")),
+            KeyBinding::Logical(Key::Character(character)) => Some(format!("char:{character}")),
+            KeyBinding::Logical(Key::Unidentified(_) | Key::Dead(_)) => None,
+            KeyBinding::Logical(key) => Some(format!("logical:{key:?}")),
+        }
+    }
+
+    fn from_setting(setting: &str) -> Option<Self> {
+        // Apart from characters, the keys that can be saved are unit variants named by their Debug
+        // output, so they can be rebuilt by name
+        let unit_variant = |name: &str| DynamicEnum::new(name, DynamicVariant::Unit);
+        let (kind, name) = setting.split_once(':')?;
+        match kind {
+            "physical" => KeyCode::from_reflect(&unit_variant(name)).map(KeyBinding::Physical),
+            "char" if !name.is_empty() => Some(KeyBinding::Logical(Key::Character(name.into()))),
+            "logical" => Key::from_reflect(&unit_variant(name)).map(KeyBinding::Logical),
+            _ => None,
+        }
+    }
+}
+
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyBinding::Physical(key_code) => write!(f, "{key_code:?}"),
+            KeyBinding::Logical(Key::Character(character)) => write!(f, "'{character}'"),
+            KeyBinding::Logical(key) => write!(f, "{key:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyProfile {
+    /// Identifies the profile for [`KeyMapping::rom_profiles`], since its name can change.
+    pub id: u32,
+    pub name: String,
+    pub bindings: [Vec<KeyBinding>; 16],
+}
+
+impl KeyProfile {
+    fn physical(id: u32, name: &str, keys: [KeyCode; 16]) -> Self {
+        Self {
+            id,
+            name: name.to_owned(),
+            bindings: keys.map(|key| vec![KeyBinding::Physical(key)]),
+        }
+    }
+
+    fn logical(id: u32, name: &str, keys: [&str; 16]) -> Self {
+        Self {
+            id,
+            name: name.to_owned(),
+            bindings: keys.map(|key| vec![KeyBinding::Logical(Key::Character(key.into()))]),
+        }
+    }
+
+    pub fn key_for(&self, event: &KeyboardInput) -> Option<u4> {
+        self.bindings
+            .iter()
+            .position(|bindings| bindings.iter().any(|binding| binding.matches(event)))
+            .map(|key| u4::new(key as u8))
+    }
+
+    /// Bind `binding` to `key`, removing it from any other key it was bound to.
+    pub fn bind(&mut self, key: u4, binding: KeyBinding) {
+        for bindings in self.bindings.iter_mut() {
+            bindings.retain(|existing| *existing != binding);
+        }
+        self.bindings[key.value() as usize].push(binding);
+    }
 }
 
+// Hex key i is bound to DEFAULT_KEY_MAPPING[i], which lays the keypad out over 1234/QWER/ASDF/ZXCV.
 const DEFAULT_KEY_MAPPING: [KeyCode; 16] = [
     KeyCode::KeyX,
     KeyCode::Digit1,
@@ -25,14 +140,542 @@ const DEFAULT_KEY_MAPPING: [KeyCode; 16] = [
     KeyCode::KeyV,
 ];
 
+// The same keypad layout as the default, but matched by the characters on an AZERTY keyboard.
+const AZERTY_KEY_MAPPING: [&str; 16] = [
+    "x", "&", "é", "\"", "a", "z", "e", "q", "s", "d", "w", "c", "'", "r", "f", "v",
+];
+
+const NUMPAD_KEY_MAPPING: [KeyCode; 16] = [
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadDivide,
+    KeyCode::NumpadMultiply,
+    KeyCode::NumpadSubtract,
+    KeyCode::NumpadAdd,
+    KeyCode::NumpadEnter,
+    KeyCode::NumpadDecimal,
+];
+
+// Puts the usual movement keys (2/4/6/8) on WASD with 5 on space, and keeps every other key within
+// reach of the left hand.
+const ONE_HANDED_KEY_MAPPING: [KeyCode; 16] = [
+    KeyCode::KeyX,
+    KeyCode::KeyQ,
+    KeyCode::KeyW,
+    KeyCode::KeyE,
+    KeyCode::KeyA,
+    KeyCode::Space,
+    KeyCode::KeyD,
+    KeyCode::KeyZ,
+    KeyCode::KeyS,
+    KeyCode::KeyC,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::KeyR,
+    KeyCode::KeyF,
+];
+
+/// The profiles, the active profile's ID and the ROM links, as they're saved.
+type SavedKeyMapping = (Vec<KeyProfile>, u32, HashMap<String, u32>);
+
+const PROFILE_PREFIX: &str = "keys.profile.";
+const ROM_PROFILE_PREFIX: &str = "keys.rom.";
+
+#[derive(Resource)]
+pub struct KeyMapping {
+    pub profiles: Vec<KeyProfile>,
+    pub active: usize,
+    /// Maps ROM names to the ID of the profile that should be used for them.
+    pub rom_profiles: HashMap<String, u32>,
+    /// The hex key currently waiting for a new binding in the key binding editor.
+    pub capturing: Option<u4>,
+    /// Set by the key binding editor whenever it's drawn, so that a capture can be cancelled when
+    /// the editor is hidden, rather than swallowing keyboard input with nothing showing why.
+    pub editor_drawn: bool,
+}
+
 impl Default for KeyMapping {
     fn default() -> Self {
         Self {
-            keys: DEFAULT_KEY_MAPPING
-                .iter()
-                .enumerate()
-                .map(|(i, key)| (*key, u4::from_u8(i as u8)))
-                .collect(),
+            profiles: vec![
+                KeyProfile::physical(0, "QWERTY", DEFAULT_KEY_MAPPING),
+                KeyProfile::logical(1, "AZERTY", AZERTY_KEY_MAPPING),
+                KeyProfile::physical(2, "Numpad", NUMPAD_KEY_MAPPING),
+                KeyProfile::physical(3, "One-handed", ONE_HANDED_KEY_MAPPING),
+            ],
+            active: 0,
+            rom_profiles: HashMap::new(),
+            capturing: None,
+            editor_drawn: false,
+        }
+    }
+}
+
+impl KeyMapping {
+    pub fn active_profile(&self) -> &KeyProfile {
+        &self.profiles[self.active]
+    }
+
+    pub fn active_profile_mut(&mut self) -> &mut KeyProfile {
+        &mut self.profiles[self.active]
+    }
+
+    pub fn key_for(&self, event: &KeyboardInput) -> Option<u4> {
+        if self.capturing.is_some() {
+            return None;
         }
+        self.active_profile().key_for(event)
+    }
+
+    fn profile_index(&self, id: u32) -> Option<usize> {
+        self.profiles.iter().position(|profile| profile.id == id)
+    }
+
+    /// Add a profile, with a new ID and a name that isn't already used, and make it active.
+    pub fn add_profile(&mut self, mut profile: KeyProfile) {
+        profile.id = self
+            .profiles
+            .iter()
+            .map(|profile| profile.id + 1)
+            .max()
+            .unwrap_or(0);
+        let base_name = profile.name.clone();
+        let mut copy = 2;
+        while !self.is_name_free(&profile.name, None) {
+            profile.name = format!("{base_name} {copy}");
+            copy += 1;
+        }
+        self.profiles.push(profile);
+        self.active = self.profiles.len() - 1;
+    }
+
+    /// Remove the active profile, along with any ROMs' links to it.
+    pub fn remove_active_profile(&mut self) {
+        let removed = self.profiles.remove(self.active);
+        self.rom_profiles.retain(|_, id| *id != removed.id);
+        self.active = self.active.saturating_sub(1);
+    }
+
+    /// Whether no profile other than the one at `except` is called `name`.
+    pub fn is_name_free(&self, name: &str, except: Option<usize>) -> bool {
+        self.profiles
+            .iter()
+            .enumerate()
+            .all(|(i, profile)| Some(i) == except || profile.name != name)
+    }
+
+    /// Load the profiles and ROM links from the settings, or the default profiles if none were
+    /// saved.
+    pub fn load(settings: &SettingsFile) -> Self {
+        let mut key_mapping = Self::default();
+        let mut ids = settings
+            .keys()
+            .filter_map(|key| key.strip_prefix(PROFILE_PREFIX)?.strip_suffix(".name"))
+            .filter_map(|id| id.parse::<u32>().ok())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        if !ids.is_empty() {
+            key_mapping.profiles = ids
+                .into_iter()
+                .map(|id| KeyProfile {
+                    id,
+                    name: settings
+                        .get(&format!("{PROFILE_PREFIX}{id}.name"))
+                        .unwrap_or_default(),
+                    bindings: std::array::from_fn(|key| {
+                        settings
+                            .get::<String>(&format!("{PROFILE_PREFIX}{id}.key.{key:X}"))
+                            .unwrap_or_default()
+                            .split_whitespace()
+                            .filter_map(KeyBinding::from_setting)
+                            .collect()
+                    }),
+                })
+                .collect();
+        }
+        // ROM names go in the values, since they could have anything in them
+        key_mapping.rom_profiles = settings
+            .keys()
+            .filter(|key| key.starts_with(ROM_PROFILE_PREFIX))
+            .filter_map(|key| {
+                let link = settings.get::<String>(key)?;
+                let (id, rom_name) = link.split_once(' ')?;
+                let id = id.parse().ok()?;
+                key_mapping
+                    .profile_index(id)
+                    .map(|_| (rom_name.to_owned(), id))
+            })
+            .collect();
+        if let Some(index) = settings
+            .get("keys.active")
+            .and_then(|id| key_mapping.profile_index(id))
+        {
+            key_mapping.active = index;
+        }
+        key_mapping
+    }
+
+    /// What gets saved, for comparing with what was last saved.
+    fn saved_state(&self) -> SavedKeyMapping {
+        (
+            self.profiles.clone(),
+            self.active_profile().id,
+            self.rom_profiles.clone(),
+        )
+    }
+
+    fn store(settings: &mut SettingsFile, (profiles, active, rom_profiles): &SavedKeyMapping) {
+        let old_keys = settings
+            .keys()
+            .filter(|key| key.starts_with(PROFILE_PREFIX) || key.starts_with(ROM_PROFILE_PREFIX))
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        for key in old_keys {
+            settings.remove(&key);
+        }
+        for profile in profiles {
+            let id = profile.id;
+            settings.set(&format!("{PROFILE_PREFIX}{id}.name"), &profile.name);
+            for (key, bindings) in profile.bindings.iter().enumerate() {
+                let bindings = bindings
+                    .iter()
+                    .filter_map(KeyBinding::to_setting)
+                    .collect::<Vec<_>>();
+                settings.set(
+                    &format!("{PROFILE_PREFIX}{id}.key.{key:X}"),
+                    bindings.join(" "),
+                );
+            }
+        }
+        for (i, (rom_name, id)) in rom_profiles.iter().enumerate() {
+            settings.set(
+                &format!("{ROM_PROFILE_PREFIX}{i}"),
+                format!("{id} {rom_name}"),
+            );
+        }
+        settings.set("keys.active", active);
+    }
+}
+
+pub fn save_key_mapping(
+    key_mapping: Res<KeyMapping>,
+    mut settings: ResMut<Settings>,
+    mut last_saved: Local<Option<SavedKeyMapping>>,
+) {
+    settings.save_if_changed(
+        &mut last_saved,
+        &key_mapping.saved_state(),
+        KeyMapping::store,
+    );
+}
+
+/// Stop waiting for a key to bind if the key binding editor wasn't drawn this frame.
+pub fn cancel_hidden_capture(mut key_mapping: ResMut<KeyMapping>) {
+    let key_mapping = key_mapping.bypass_change_detection();
+    if !std::mem::take(&mut key_mapping.editor_drawn) {
+        key_mapping.capturing = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisBinding {
+    pub axis: GamepadAxis,
+    pub negative: Option<u4>,
+    pub positive: Option<u4>,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct GamepadMapping {
+    pub buttons: HashMap<GamepadButton, u4>,
+    pub axes: Vec<AxisBinding>,
+    /// How far an axis has to be pushed (from 0.0 to 1.0) before it counts as a key press.
+    pub dead_zone: f32,
+}
+
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        Self {
+            buttons: [
+                (GamepadButton::DPadUp, 0x2),
+                (GamepadButton::DPadLeft, 0x4),
+                (GamepadButton::DPadRight, 0x6),
+                (GamepadButton::DPadDown, 0x8),
+                (GamepadButton::South, 0x5),
+                (GamepadButton::East, 0xF),
+                (GamepadButton::West, 0xA),
+                (GamepadButton::North, 0xE),
+                (GamepadButton::Start, 0x1),
+                (GamepadButton::Select, 0x0),
+            ]
+            .into_iter()
+            .map(|(button, key)| (button, u4::new(key)))
+            .collect(),
+            axes: vec![
+                AxisBinding {
+                    axis: GamepadAxis::LeftStickX,
+                    negative: Some(u4::new(0x4)),
+                    positive: Some(u4::new(0x6)),
+                },
+                AxisBinding {
+                    axis: GamepadAxis::LeftStickY,
+                    negative: Some(u4::new(0x8)),
+                    positive: Some(u4::new(0x2)),
+                },
+            ],
+            dead_zone: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum GamepadInput {
+    Button(GamepadButton),
+    Axis(GamepadAxis),
+}
+
+/// The key each gamepad button and axis is holding down. Several inputs can hold the same key,
+/// like the d-pad and the stick both holding 2, so a key is only released when none of them
+/// hold it any more.
+#[derive(Debug, Default)]
+pub struct GamepadState(HashMap<(Entity, GamepadInput), u4>);
+
+impl GamepadState {
+    /// Make `input` hold `key`, or nothing, returning the resulting key events.
+    fn hold(&mut self, input: (Entity, GamepadInput), key: Option<u4>) -> Vec<(u4, KeyEvent)> {
+        let last_key = match key {
+            Some(key) => self.0.insert(input, key),
+            None => self.0.remove(&input),
+        };
+        if key == last_key {
+            return Vec::new();
+        }
+        let holders = |key| self.0.values().filter(|held| **held == key).count();
+        let release = last_key.filter(|key| holders(*key) == 0);
+        let press = key.filter(|key| holders(*key) == 1);
+        release
+            .map(|key| (key, KeyEvent::Release))
+            .into_iter()
+            .chain(press.map(|key| (key, KeyEvent::Press)))
+            .collect()
+    }
+}
+
+impl GamepadMapping {
+    pub fn button_events(
+        &self,
+        event: &GamepadButtonChangedEvent,
+        state: &mut GamepadState,
+    ) -> Vec<(u4, KeyEvent)> {
+        let key = match event.state {
+            ButtonState::Pressed => self.buttons.get(&event.button).copied(),
+            ButtonState::Released => None,
+        };
+        state.hold((event.entity, GamepadInput::Button(event.button)), key)
+    }
+
+    pub fn axis_events(
+        &self,
+        event: &GamepadAxisChangedEvent,
+        state: &mut GamepadState,
+    ) -> Vec<(u4, KeyEvent)> {
+        let Some(binding) = self.axes.iter().find(|binding| binding.axis == event.axis) else {
+            return Vec::new();
+        };
+        let key = if event.value >= self.dead_zone {
+            binding.positive
+        } else if event.value <= -self.dead_zone {
+            binding.negative
+        } else {
+            None
+        };
+        state.hold((event.entity, GamepadInput::Axis(event.axis)), key)
+    }
+}
+
+pub fn apply_rom_profile(
+    emulator_data: Res<EmulatorData>,
+    mut key_mapping: ResMut<KeyMapping>,
+    mut last_rom_name: Local<Option<String>>,
+) {
+    if emulator_data.rom_name == *last_rom_name {
+        return;
+    }
+    *last_rom_name = emulator_data.rom_name.clone();
+
+    if let Some(index) = emulator_data
+        .rom_name
+        .as_ref()
+        .and_then(|rom_name| key_mapping.rom_profiles.get(rom_name))
+        .and_then(|id| key_mapping.profile_index(*id))
+    {
+        key_mapping.active = index;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arbitrary_int::u4;
+    use bevy::{
+        input::{
+            gamepad::{GamepadAxisChangedEvent, GamepadButtonChangedEvent},
+            keyboard::Key,
+            ButtonState,
+        },
+        prelude::*,
+    };
+
+    use super::{GamepadMapping, GamepadState, KeyBinding, KeyMapping};
+    use crate::{frontend::settings::SettingsFile, hardware::KeyEvent};
+
+    #[test]
+    fn test_profiles_round_trip() {
+        let mut key_mapping = KeyMapping {
+            active: 1,
+            ..Default::default()
+        };
+        let mut profile = key_mapping.active_profile().clone();
+        profile.bind(u4::new(0x5), KeyBinding::Physical(KeyCode::Space));
+        profile.bind(u4::new(0x5), KeyBinding::Logical(Key::Enter));
+        key_mapping.add_profile(profile);
+        assert_eq!(key_mapping.active_profile().name, "AZERTY 2");
+        let id = key_mapping.active_profile().id;
+        key_mapping.rom_profiles.insert("Game = 1.ch8".into(), id);
+
+        // Renaming doesn't unlink the profile from its ROMs
+        key_mapping.active_profile_mut().name = "Mine".into();
+        let mut settings = SettingsFile::default();
+        KeyMapping::store(&mut settings, &key_mapping.saved_state());
+        let loaded = KeyMapping::load(&SettingsFile::parse(&settings.serialize()));
+        assert_eq!(loaded.profiles, key_mapping.profiles);
+        assert_eq!(loaded.active, key_mapping.active);
+        assert_eq!(loaded.rom_profiles, key_mapping.rom_profiles);
+
+        // Deleting a profile unlinks it
+        key_mapping.remove_active_profile();
+        assert!(key_mapping.rom_profiles.is_empty());
+        assert!(!key_mapping.is_name_free("QWERTY", None));
+        assert!(key_mapping.is_name_free("QWERTY", Some(0)));
+    }
+
+    #[test]
+    fn test_gamepad_buttons() {
+        let mapping = GamepadMapping::default();
+        let mut state = GamepadState::default();
+        let gamepad = Entity::from_raw(0);
+        let mut press = |button, button_state: ButtonState| {
+            mapping.button_events(
+                &GamepadButtonChangedEvent::new(gamepad, button, button_state, 1.0),
+                &mut state,
+            )
+        };
+
+        assert_eq!(
+            press(GamepadButton::DPadUp, ButtonState::Pressed),
+            vec![(u4::new(0x2), KeyEvent::Press)]
+        );
+        // Analog buttons send more events as they move, which don't press again
+        assert_eq!(press(GamepadButton::DPadUp, ButtonState::Pressed), vec![]);
+        assert_eq!(
+            press(GamepadButton::DPadUp, ButtonState::Released),
+            vec![(u4::new(0x2), KeyEvent::Release)]
+        );
+        assert_eq!(press(GamepadButton::Mode, ButtonState::Pressed), vec![]);
+    }
+
+    #[test]
+    fn test_gamepad_axes() {
+        let mapping = GamepadMapping::default();
+        let mut state = GamepadState::default();
+        let gamepad = Entity::from_raw(0);
+        let mut move_stick = |axis, value| {
+            mapping.axis_events(
+                &GamepadAxisChangedEvent::new(gamepad, axis, value),
+                &mut state,
+            )
+        };
+
+        // Inside the dead zone
+        assert_eq!(move_stick(GamepadAxis::LeftStickX, 0.3), vec![]);
+        assert_eq!(
+            move_stick(GamepadAxis::LeftStickX, 0.8),
+            vec![(u4::new(0x6), KeyEvent::Press)]
+        );
+        // Still held, no new events
+        assert_eq!(move_stick(GamepadAxis::LeftStickX, 0.9), vec![]);
+        assert_eq!(
+            move_stick(GamepadAxis::LeftStickX, -0.9),
+            vec![
+                (u4::new(0x6), KeyEvent::Release),
+                (u4::new(0x4), KeyEvent::Press)
+            ]
+        );
+        assert_eq!(
+            move_stick(GamepadAxis::LeftStickX, -0.1),
+            vec![(u4::new(0x4), KeyEvent::Release)]
+        );
+
+        assert_eq!(
+            move_stick(GamepadAxis::LeftStickY, 1.0),
+            vec![(u4::new(0x2), KeyEvent::Press)]
+        );
+        assert_eq!(move_stick(GamepadAxis::RightStickY, 1.0), vec![]);
+    }
+
+    #[test]
+    fn test_gamepad_shared_keys() {
+        let mapping = GamepadMapping::default();
+        let mut state = GamepadState::default();
+        let gamepad = Entity::from_raw(0);
+        let up = u4::new(0x2);
+        let dpad = |pressed, state: &mut GamepadState| {
+            let button_state = if pressed {
+                ButtonState::Pressed
+            } else {
+                ButtonState::Released
+            };
+            mapping.button_events(
+                &GamepadButtonChangedEvent::new(gamepad, GamepadButton::DPadUp, button_state, 1.0),
+                state,
+            )
+        };
+        let stick = |value, state: &mut GamepadState| {
+            mapping.axis_events(
+                &GamepadAxisChangedEvent::new(gamepad, GamepadAxis::LeftStickY, value),
+                state,
+            )
+        };
+
+        // Both the d-pad and the stick hold 2, which is only released when both let go
+        assert_eq!(dpad(true, &mut state), vec![(up, KeyEvent::Press)]);
+        assert_eq!(stick(1.0, &mut state), vec![]);
+        assert_eq!(dpad(false, &mut state), vec![]);
+        assert_eq!(stick(0.0, &mut state), vec![(up, KeyEvent::Release)]);
+
+        // The same for another gamepad's d-pad
+        let other = Entity::from_raw(1);
+        assert_eq!(stick(1.0, &mut state), vec![(up, KeyEvent::Press)]);
+        assert_eq!(
+            mapping.button_events(
+                &GamepadButtonChangedEvent::new(
+                    other,
+                    GamepadButton::DPadUp,
+                    ButtonState::Pressed,
+                    1.0
+                ),
+                &mut state,
+            ),
+            vec![]
+        );
+        assert_eq!(stick(0.0, &mut state), vec![]);
     }
 }
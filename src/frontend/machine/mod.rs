@@ -1,5 +1,9 @@
 use std::{
     collections::BTreeSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
     time::{Duration, Instant},
 };
 
@@ -7,50 +11,285 @@ use arbitrary_int::u4;
 use async_channel::{Receiver, Sender};
 use bevy::{
     diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
-    input::{keyboard::KeyboardInput, ButtonState},
+    input::{
+        gamepad::{GamepadAxisChangedEvent, GamepadButtonChangedEvent},
+        keyboard::KeyboardInput,
+        ButtonState,
+    },
     prelude::*,
     render::render_resource::Extent3d,
 };
-use image::RgbaImage;
-use keymap::KeyMapping;
+use bevy_egui::EguiContexts;
+use image::{Rgba, RgbaImage};
+use keymap::{GamepadMapping, GamepadState, KeyMapping};
 
 use crate::{
-    hardware::{self, DynamicMachine, KeyEvent, Machine as HardwareMachine},
-    model::{CosmacVip, Model},
+    hardware::{
+        self, AudioFrame, Cpu, DynamicMachine, KeyEvent, Machine as HardwareMachine, Register,
+    },
+    instruction::InstructionSet,
+    model::{CosmacVip, Model, Quirks},
+    movie::{Movie, MovieEvent, MoviePlayer, MovieRecorder},
+    postprocess::PostProcessMode,
+    screen::{IndexedFrame, Palette, Screen},
+    upscale::UpscaleFilter,
 };
 
-use super::{audio::Chip8Audio, rom::Rom, EmulatorData, EmulatorEvent, Frame};
+use super::{
+    audio::{AudioSettings, Chip8Audio},
+    capture::Capture,
+    rom::Rom,
+    settings::Settings,
+    EmulatorData, EmulatorEvent, Frame,
+};
+
+pub mod keymap;
 
-mod keymap;
+pub const FAST_FORWARD_KEY: KeyCode = KeyCode::Tab;
+pub const SLOW_MOTION_KEY: KeyCode = KeyCode::Backquote;
+pub const FRAME_ADVANCE_KEY: KeyCode = KeyCode::Period;
 
 pub const FRAME_TICK_TIME: DiagnosticPath = DiagnosticPath::const_new("frame_tick_time");
 pub const EMULATOR_FPS: DiagnosticPath = DiagnosticPath::const_new("emulator_fps");
 
+/// The UI's view of the machine running on the machine thread, as of the last frame.
 #[derive(Resource)]
 pub struct Machine {
     initialized: bool,
-    pub machine: DynamicMachine,
+    state: MachineState,
+    screen: Box<dyn Screen>,
+    memory: Arc<SharedMemory>,
     pub tx: Sender<ToMachine>,
     frame_rx: Receiver<FrameEvent>,
+    pub movie_status: MovieStatus,
+    pub recorded_movie: Option<Movie>,
+    /// The error the machine is halted on, until it's resumed, stepped or reset.
+    pub error: Option<MachineError>,
+}
+
+impl Machine {
+    pub fn cpu(&self) -> &Cpu {
+        &self.state.cpu
+    }
+
+    pub fn pressed_keys(&self) -> u16 {
+        self.state.pressed_keys
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.state.quirks
+    }
+
+    pub fn instruction_set(&self) -> InstructionSet {
+        self.state.instruction_set
+    }
+
+    pub fn render_frame(&self, palette: &Palette) -> RgbaImage {
+        self.screen.to_image(palette)
+    }
+
+    /// Render the screen into `frame`, returning whether it changed.
+    pub fn render_indexed(&self, frame: &mut IndexedFrame, palette: &Palette) -> bool {
+        frame.update(self.screen.as_ref(), palette)
+    }
+
+    /// The color of each combination of display planes on the machine's screen.
+    pub fn index_colors(&self, palette: &Palette) -> [Rgba<u8>; 16] {
+        self.screen.index_colors(palette)
+    }
+
+    /// A copy of the machine's memory. Copying it isn't free, so the machine thread only does so
+    /// for the frame after this is called. Anything showing the memory should call this every
+    /// frame, and will see it one frame late.
+    pub fn memory(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.memory.wanted.store(true, Ordering::Relaxed);
+        self.memory.copy.lock().unwrap()
+    }
+}
+
+/// The parts of the machine that are cheap enough to send to the UI after every frame.
+struct MachineState {
+    cpu: Cpu,
+    pressed_keys: u16,
+    quirks: Quirks,
+    instruction_set: InstructionSet,
+}
+
+impl MachineState {
+    fn new(machine: &impl HardwareMachine) -> Self {
+        Self {
+            cpu: machine.cpu().clone(),
+            pressed_keys: machine.pressed_keys(),
+            quirks: *machine.quirks(),
+            instruction_set: machine.instruction_set(),
+        }
+    }
+}
+
+/// The machine's memory, shared with the machine thread, which only copies into it when the UI
+/// has asked for it since the last frame.
+#[derive(Default)]
+struct SharedMemory {
+    wanted: AtomicBool,
+    copy: Mutex<Vec<u8>>,
+}
+
+impl SharedMemory {
+    fn update(&self, machine: &impl HardwareMachine) {
+        if !self.wanted.load(Ordering::Relaxed) {
+            return;
+        }
+        // Don't hold up the machine while the UI is drawing the memory, just try again next frame
+        if let Ok(mut copy) = self.copy.try_lock() {
+            self.wanted.store(false, Ordering::Relaxed);
+            copy.clear();
+            copy.extend_from_slice(machine.memory());
+        }
+    }
+}
+
+/// What the machine thread does when an instruction fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Pause on the instruction that failed, keeping the machine's state for debugging.
+    #[default]
+    Halt,
+    /// Skip the instruction that failed and carry on.
+    Skip,
+    /// Start the ROM again from the beginning.
+    Reset,
+}
+
+impl ErrorPolicy {
+    pub const ALL: [Self; 3] = [Self::Halt, Self::Skip, Self::Reset];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Halt => "halt",
+            Self::Skip => "skip",
+            Self::Reset => "reset",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|policy| policy.id() == id)
+    }
+}
+
+impl std::fmt::Display for ErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Halt => write!(f, "Halt and debug"),
+            Self::Skip => write!(f, "Skip the instruction"),
+            Self::Reset => write!(f, "Reset the machine"),
+        }
+    }
+}
+
+/// An error from the machine, and what the machine thread did about it.
+#[derive(Debug, Clone)]
+pub struct MachineError {
+    pub error: hardware::Error,
+    pub policy: ErrorPolicy,
+}
+
+impl MachineError {
+    fn new(error: hardware::Error) -> Self {
+        Self {
+            error,
+            policy: ErrorPolicy::Halt,
+        }
+    }
+}
+
+impl std::fmt::Display for MachineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
 }
 
 pub enum ToMachine {
     Input(u4, KeyEvent),
     ResetMachine(DynamicMachine),
     Pause(bool),
+    /// Run one instruction while paused.
     Step,
+    /// Run one whole frame while paused.
+    FrameAdvance,
+    /// Move past the next instruction without running it, while paused.
+    SkipInstruction,
+    SetErrorPolicy(ErrorPolicy),
+    /// Change a register, for debugging.
+    SetRegister(Register, u16),
+    PushStack(u16),
+    PopStack,
+    /// Write bytes to memory, for debugging.
+    Poke(u16, Vec<u8>),
     SetFrequency(f64),
+    /// Run this many times faster than the frame rate, or as fast as possible if it's infinite.
+    SetSpeed(f64),
     SetIpf(u32),
     SetBreakpoint(u16, bool),
     ClearBreakpoints,
+    /// Reset to the given machine and start recording a movie from it.
+    StartRecording(DynamicMachine, Movie),
+    /// Reset to the given machine and play a movie back on it.
+    PlayMovie(DynamicMachine, Movie),
+    /// Stop recording or playing a movie. A finished recording is sent back with the next frame.
+    StopMovie,
     Exit,
 }
 
 struct FrameEvent {
-    machine: Option<DynamicMachine>,
+    state: Option<MachineState>,
+    /// The screen, if it has changed since the last frame.
+    screen: Option<Box<dyn Screen>>,
     result: TickResult,
     frame_time: Duration,
     audio_status: AudioStatus,
+    movie_status: MovieStatus,
+    recorded_movie: Option<Movie>,
+    halted_on: Option<MachineError>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MovieStatus {
+    #[default]
+    Idle,
+    Recording {
+        frame: u64,
+    },
+    Playing {
+        frame: u64,
+        length: u64,
+    },
+}
+
+enum ActiveMovie {
+    Recording(MovieRecorder),
+    Playing(MoviePlayer),
+}
+
+impl ActiveMovie {
+    fn status(&self) -> MovieStatus {
+        match self {
+            ActiveMovie::Recording(recorder) => MovieStatus::Recording {
+                frame: recorder.frame(),
+            },
+            ActiveMovie::Playing(player) => MovieStatus::Playing {
+                frame: player.frame(),
+                length: player.movie().length(),
+            },
+        }
+    }
+
+    /// Stop the movie, returning it if it was being recorded.
+    fn stop(self) -> Option<Movie> {
+        match self {
+            ActiveMovie::Recording(recorder) => Some(recorder.finish()),
+            ActiveMovie::Playing(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,22 +297,44 @@ pub enum TickResult {
     Continue,
     Exit,
     HitBreakpoint,
-    Error(hardware::Error),
+    Error(MachineError),
+}
+
+/// What the UI needs from each frame the machine thread sends.
+struct MachineOutput {
+    audio_status: AudioStatus,
+    /// The newly emulated frame, if there is one and post-processing needs it.
+    image: Option<RgbaImage>,
 }
 
 enum AudioStatus {
-    Play(Duration),
+    Play(Duration, AudioFrame),
     Paused,
     Reset,
 }
 
 pub fn machine_plugin(app: &mut App) {
-    app.init_resource::<KeyMapping>()
+    app.init_resource::<Settings>();
+    let key_mapping = KeyMapping::load(app.world().resource::<Settings>());
+    app.insert_resource(key_mapping)
+        .init_resource::<GamepadMapping>()
         .register_diagnostic(Diagnostic::new(FRAME_TICK_TIME))
         .register_diagnostic(Diagnostic::new(EMULATOR_FPS))
         .add_systems(Startup, setup)
-        .add_systems(Update, handle_machine.pipe(render_machine_output))
-        .add_systems(PostUpdate, handle_ui_events);
+        .add_systems(
+            Update,
+            (
+                keymap::apply_rom_profile,
+                keymap::save_key_mapping,
+                speed_hotkeys,
+                handle_gamepad,
+                handle_machine.pipe(render_machine_output),
+            ),
+        )
+        .add_systems(
+            PostUpdate,
+            (handle_ui_events, keymap::cancel_hidden_capture),
+        );
     // .add_systems(FixedPreUpdate, handle_machine_input)
     // .add_systems(
     //     FixedUpdate,
@@ -107,6 +368,18 @@ fn handle_ui_events(
             .try_send(ToMachine::SetFrequency(ui_data.frame_rate))
             .unwrap();
     }
+    if ui_data.speed() != last_ui_data.speed() {
+        machine
+            .tx
+            .try_send(ToMachine::SetSpeed(ui_data.speed()))
+            .unwrap();
+    }
+    if ui_data.error_policy != last_ui_data.error_policy {
+        machine
+            .tx
+            .try_send(ToMachine::SetErrorPolicy(ui_data.error_policy))
+            .unwrap();
+    }
 
     for event in ui_events.read() {
         match event {
@@ -129,6 +402,25 @@ fn handle_ui_events(
                         .unwrap();
                 }
             }
+            EmulatorEvent::StartRecording => {
+                if let Some(rom) = rom.as_ref() {
+                    let movie = Movie::new(
+                        &rom.0,
+                        ui_data.machine_model.clone(),
+                        ui_data.cycles_per_frame,
+                        ui_data.frame_rate,
+                    );
+                    let new_machine = movie
+                        .new_machine(&rom.0)
+                        .expect("movie was created from this ROM");
+                    machine
+                        .tx
+                        .try_send(ToMachine::StartRecording(new_machine, movie))
+                        .unwrap();
+                }
+            }
+            EmulatorEvent::StopMovie => machine.tx.try_send(ToMachine::StopMovie).unwrap(),
+            EmulatorEvent::FrameAdvance => machine.tx.try_send(ToMachine::FrameAdvance).unwrap(),
             _ => {}
         }
     }
@@ -136,83 +428,211 @@ fn handle_ui_events(
     *last_ui_data = ui_data.as_ref().clone();
 }
 
+fn speed_hotkeys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut emulator_data: ResMut<EmulatorData>,
+    mut events: EventWriter<EmulatorEvent>,
+) {
+    // Typing in a text field shouldn't change the speed
+    if contexts.ctx_mut().wants_keyboard_input() {
+        emulator_data.fast_forward = false;
+        return;
+    }
+    emulator_data.fast_forward = keys.pressed(FAST_FORWARD_KEY);
+    if keys.just_pressed(SLOW_MOTION_KEY) {
+        emulator_data.slow_motion = !emulator_data.slow_motion;
+    }
+    if keys.just_pressed(FRAME_ADVANCE_KEY) {
+        emulator_data.paused = true;
+        events.send(EmulatorEvent::FrameAdvance);
+    }
+}
+
 fn setup(mut commands: Commands, emulator_data: Res<EmulatorData>) {
-    let (tx, frame_rx) =
-        spawn_machine_thread(emulator_data.frame_rate, emulator_data.cycles_per_frame);
+    let memory = Arc::new(SharedMemory::default());
+    let (tx, frame_rx) = spawn_machine_thread(
+        emulator_data.frame_rate,
+        emulator_data.cycles_per_frame,
+        memory.clone(),
+    );
+    let machine = DynamicMachine::new_cosmac_vip(CosmacVip::default(), &[]);
     commands.insert_resource(Machine {
         initialized: false,
-        machine: DynamicMachine::new_cosmac_vip(CosmacVip::default(), &[]),
+        state: MachineState::new(&machine),
+        screen: machine.clone_screen(),
+        memory,
         tx,
         frame_rx,
+        movie_status: MovieStatus::Idle,
+        recorded_movie: None,
+        error: None,
     });
 }
 
-fn spawn_machine_thread(frequency: f64, ipf: u32) -> (Sender<ToMachine>, Receiver<FrameEvent>) {
+fn spawn_machine_thread(
+    frequency: f64,
+    ipf: u32,
+    memory: Arc<SharedMemory>,
+) -> (Sender<ToMachine>, Receiver<FrameEvent>) {
     let (tx, rx) = async_channel::unbounded();
     let (frame_tx, frame_rx) = async_channel::unbounded();
     std::thread::spawn(move || {
-        let mut machine = None;
+        let mut machine: Option<DynamicMachine> = None;
+        // The machine as it was reset, to go back to when the error policy is to reset
+        let mut initial_machine: Option<DynamicMachine> = None;
         let mut result = TickResult::Continue;
+        let mut error_policy = ErrorPolicy::default();
+        let mut halted_on = None;
         let mut paused = false;
+        let mut frequency = frequency;
         let mut timestep = Duration::from_secs_f64(1.0 / frequency);
+        let mut speed = 1.0;
         let mut ipf = ipf;
         let mut breakpoints = BTreeSet::new();
+        let mut movie: Option<ActiveMovie> = None;
+        let mut recorded_movie = None;
+        // Reset whenever the machine is replaced, since blank screens all have the same version
+        let mut screen_version = None;
         let mut ts = Instant::now();
         let mut last_frame = ts;
         'outer: loop {
             let now = Instant::now();
             let frame_time = now - last_frame;
             last_frame = now;
+            let screen = machine.as_ref().and_then(|machine| {
+                let version = machine.screen_version();
+                (screen_version.replace(version) != Some(version)).then(|| machine.clone_screen())
+            });
+            if let Some(machine) = machine.as_ref() {
+                memory.update(machine);
+            }
             frame_tx
                 .try_send(FrameEvent {
-                    machine: machine.clone(),
+                    state: machine.as_ref().map(MachineState::new),
+                    screen,
                     result: result.clone(),
                     frame_time,
-                    audio_status: match (
-                        machine
-                            .as_ref()
-                            .is_some_and(|machine| machine.sound_active()),
-                        paused,
-                    ) {
-                        (true, false) => AudioStatus::Play(timestep),
-                        (true, true) => AudioStatus::Paused,
-                        _ => AudioStatus::Reset,
+                    audio_status: match (&machine, paused) {
+                        // Fast-forwarded audio would just be noise, so mute it
+                        (Some(_), false) if speed > 1.0 => AudioStatus::Paused,
+                        // In slow motion each frame's sound is stretched over the time the frame
+                        // takes, which keeps the pitch the same
+                        (Some(machine), false) => {
+                            AudioStatus::Play(timestep.div_f64(speed), machine.audio_frame())
+                        }
+                        (Some(_), true) => AudioStatus::Paused,
+                        (None, _) => AudioStatus::Reset,
                     },
+                    movie_status: movie
+                        .as_ref()
+                        .map_or(MovieStatus::Idle, ActiveMovie::status),
+                    recorded_movie: recorded_movie.take(),
+                    halted_on: halted_on.clone(),
                 })
                 .expect("Failed to send frame, receiver disconnected");
 
-            if matches!(result, TickResult::Error(_) | TickResult::Exit) {
-                machine = None;
-            }
             match result {
-                TickResult::Continue => {}
+                TickResult::Continue | TickResult::Error(_) => {}
                 TickResult::Exit => machine = None,
                 TickResult::HitBreakpoint => {
                     paused = true;
                 }
-                TickResult::Error(_) => {
-                    machine = None;
-                    result = TickResult::Exit;
-                }
             }
 
             let mut inputs = Vec::new();
             let mut tick_once = false;
+            let mut advance_frame = false;
             while let Ok(message) = rx.try_recv() {
                 match message {
-                    ToMachine::Input(key, event) => inputs.push((key, event)),
-                    ToMachine::ResetMachine(new_machine) => {
+                    ToMachine::Input(key, event) => match movie.as_mut() {
+                        // Live input would desync the playback
+                        Some(ActiveMovie::Playing(_)) => {}
+                        Some(ActiveMovie::Recording(recorder)) => {
+                            recorder.record(MovieEvent::Input(key, event));
+                            inputs.push((key, event));
+                        }
+                        None => inputs.push((key, event)),
+                    },
+                    ToMachine::ResetMachine(mut new_machine) => {
+                        new_machine.set_frame_rate(frequency);
+                        initial_machine = Some(new_machine.clone());
                         machine = Some(new_machine);
+                        screen_version = None;
+                        halted_on = None;
                         result = TickResult::Continue;
+                        recorded_movie = movie.take().and_then(ActiveMovie::stop);
+                    }
+                    ToMachine::Pause(pause) => {
+                        paused = pause;
+                        if !paused {
+                            halted_on = None;
+                        }
                     }
-                    ToMachine::Pause(pause) => paused = pause,
                     ToMachine::Step => {
-                        tick_once = true;
+                        // Stepping runs part of a frame, which can't be replayed
+                        tick_once = movie.is_none();
+                        halted_on = None;
+                    }
+                    ToMachine::FrameAdvance => {
+                        advance_frame = paused;
+                        halted_on = None;
+                    }
+                    ToMachine::SkipInstruction => {
+                        // Skipping isn't part of any frame's input, so it can't be replayed either
+                        if let Some(machine) = machine.as_mut().filter(|_| movie.is_none()) {
+                            machine.skip_instruction();
+                            halted_on = None;
+                        }
+                    }
+                    ToMachine::SetErrorPolicy(policy) => error_policy = policy,
+                    // Edits aren't recorded in movies, so they'd desync them
+                    ToMachine::SetRegister(register, value) => {
+                        if let Some(machine) = machine.as_mut().filter(|_| movie.is_none()) {
+                            machine.set_register(register, value);
+                        }
+                    }
+                    ToMachine::PushStack(address) => {
+                        if let Some(machine) = machine.as_mut().filter(|_| movie.is_none()) {
+                            if let Err(error) = machine.push_stack(address) {
+                                warn!("Couldn't push {address:#06X}: {error}");
+                            }
+                        }
+                    }
+                    ToMachine::PopStack => {
+                        if let Some(machine) = machine.as_mut().filter(|_| movie.is_none()) {
+                            if let Err(error) = machine.pop_stack() {
+                                warn!("Couldn't pop the stack: {error}");
+                            }
+                        }
                     }
-                    ToMachine::SetFrequency(frequency) => {
-                        timestep = Duration::from_secs_f64(1.0 / frequency)
+                    ToMachine::Poke(address, bytes) => {
+                        if let Some(machine) = machine.as_mut().filter(|_| movie.is_none()) {
+                            if let Err(error) = machine.poke(address, &bytes) {
+                                warn!("Couldn't write to memory: {error}");
+                            }
+                        }
+                    }
+                    ToMachine::SetSpeed(new_speed) => speed = new_speed,
+                    ToMachine::SetFrequency(new_frequency) => {
+                        frequency = new_frequency;
+                        timestep = Duration::from_secs_f64(1.0 / frequency);
+                        // Movies run at the frame rate they were recorded at, so only the real
+                        // time between frames changes
+                        if movie.is_none() {
+                            if let Some(machine) = machine.as_mut() {
+                                machine.set_frame_rate(frequency);
+                            }
+                        }
                     }
-                    ToMachine::SetIpf(new_ipf) => ipf = new_ipf,
+                    ToMachine::SetIpf(new_ipf) => match movie.as_mut() {
+                        Some(ActiveMovie::Playing(_)) => {}
+                        Some(ActiveMovie::Recording(recorder)) => {
+                            recorder.record(MovieEvent::SetIpf(new_ipf));
+                            ipf = new_ipf;
+                        }
+                        None => ipf = new_ipf,
+                    },
                     ToMachine::SetBreakpoint(address, enabled) => {
                         if enabled {
                             breakpoints.insert(address);
@@ -223,6 +643,34 @@ fn spawn_machine_thread(frequency: f64, ipf: u32) -> (Sender<ToMachine>, Receive
                     ToMachine::ClearBreakpoints => {
                         breakpoints.clear();
                     }
+                    ToMachine::StartRecording(new_machine, new_movie) => {
+                        recorded_movie = movie.take().and_then(ActiveMovie::stop);
+                        initial_machine = Some(new_machine.clone());
+                        machine = Some(new_machine);
+                        screen_version = None;
+                        halted_on = None;
+                        result = TickResult::Continue;
+                        ipf = new_movie.ipf;
+                        inputs.clear();
+                        movie = Some(ActiveMovie::Recording(MovieRecorder::new(new_movie)));
+                    }
+                    ToMachine::PlayMovie(new_machine, new_movie) => {
+                        recorded_movie = movie.take().and_then(ActiveMovie::stop);
+                        initial_machine = Some(new_machine.clone());
+                        machine = Some(new_machine);
+                        screen_version = None;
+                        halted_on = None;
+                        result = TickResult::Continue;
+                        ipf = new_movie.ipf;
+                        inputs.clear();
+                        movie = Some(ActiveMovie::Playing(MoviePlayer::new(new_movie)));
+                    }
+                    ToMachine::StopMovie => {
+                        recorded_movie = movie.take().and_then(ActiveMovie::stop);
+                        if let Some(machine) = machine.as_mut() {
+                            machine.set_frame_rate(frequency);
+                        }
+                    }
                     ToMachine::Exit => break 'outer,
                 }
             }
@@ -232,46 +680,145 @@ fn spawn_machine_thread(frequency: f64, ipf: u32) -> (Sender<ToMachine>, Receive
                     machine.event(key, event);
                 }
 
-                if !paused {
-                    machine.tick_timers();
+                let skip_frame = paused && !advance_frame;
+                // When uncapped, run as many frames as fit in one normal frame's time, and only
+                // send the last one, so the UI isn't flooded with frames
+                let deadline = Instant::now() + timestep;
+                loop {
+                    result = run_frame(
+                        machine,
+                        &mut movie,
+                        &mut ipf,
+                        &breakpoints,
+                        skip_frame,
+                        tick_once,
+                    );
+                    if speed.is_finite()
+                        || skip_frame
+                        || tick_once
+                        || advance_frame
+                        || !matches!(result, TickResult::Continue)
+                        || Instant::now() >= deadline
+                    {
+                        break;
+                    }
+                }
+
+                if let TickResult::Error(error) = &mut result {
+                    // Stepping is already debugging, and a skip or a reset wouldn't be in the
+                    // movie, so it couldn't be replayed
+                    error.policy = if tick_once || movie.is_some() {
+                        ErrorPolicy::Halt
+                    } else {
+                        error_policy
+                    };
+                    match error.policy {
+                        ErrorPolicy::Halt => {
+                            paused = true;
+                            halted_on = Some(error.clone());
+                        }
+                        ErrorPolicy::Skip => machine.skip_instruction(),
+                        ErrorPolicy::Reset => {
+                            if let Some(initial_machine) = initial_machine.as_ref() {
+                                *machine = initial_machine.clone();
+                                machine.set_frame_rate(frequency);
+                                screen_version = None;
+                            }
+                        }
+                    }
                 }
-                let (num_instructions, breakpoints) = if tick_once {
-                    (1, &BTreeSet::new())
-                } else if paused {
-                    (0, &BTreeSet::new())
-                } else {
-                    (ipf, &breakpoints)
-                };
-                result = match machine.tick_many(num_instructions, breakpoints) {
-                    Ok(false) => TickResult::Continue,
-                    Ok(true) => TickResult::HitBreakpoint,
-                    Err(hardware::Error::Exit) => TickResult::Exit,
-                    Err(error) => TickResult::Error(error),
-                };
             }
 
             let now = Instant::now();
-            while ts <= now {
-                ts += timestep;
+            if speed.is_finite() {
+                let timestep = timestep.div_f64(speed);
+                while ts <= now {
+                    ts += timestep;
+                }
+                spin_sleep::sleep_until(ts);
+            } else {
+                ts = now;
             }
-            spin_sleep::sleep_until(ts);
         }
     });
     (tx, frame_rx)
 }
 
+/// Run one frame, or just one instruction if `tick_once` is set.
+fn run_frame(
+    machine: &mut DynamicMachine,
+    movie: &mut Option<ActiveMovie>,
+    ipf: &mut u32,
+    breakpoints: &BTreeSet<u16>,
+    paused: bool,
+    tick_once: bool,
+) -> TickResult {
+    if !paused {
+        match movie.as_mut() {
+            Some(ActiveMovie::Recording(recorder)) => recorder.next_frame(),
+            Some(ActiveMovie::Playing(player)) => {
+                player.apply_frame(machine, ipf);
+                if player.finished() {
+                    info!("Movie playback finished");
+                    *movie = None;
+                }
+            }
+            None => {}
+        }
+        machine.start_frame();
+    }
+    let (num_instructions, breakpoints) = if tick_once {
+        (1, &BTreeSet::new())
+    } else if paused {
+        (0, &BTreeSet::new())
+    } else if movie.is_some() {
+        // A breakpoint would stop partway through a frame, which can't be replayed
+        (*ipf, &BTreeSet::new())
+    } else {
+        (*ipf, breakpoints)
+    };
+    match machine.tick_many(num_instructions, breakpoints) {
+        Ok(false) => TickResult::Continue,
+        Ok(true) => TickResult::HitBreakpoint,
+        Err(hardware::Error::Exit) => TickResult::Exit,
+        Err(error) => TickResult::Error(MachineError::new(error)),
+    }
+}
+
+fn handle_gamepad(
+    machine: Res<Machine>,
+    gamepad_mapping: Res<GamepadMapping>,
+    mut button_events: EventReader<GamepadButtonChangedEvent>,
+    mut axis_events: EventReader<GamepadAxisChangedEvent>,
+    mut state: Local<GamepadState>,
+) {
+    for event in button_events.read() {
+        for (key, event) in gamepad_mapping.button_events(event, &mut state) {
+            machine.tx.try_send(ToMachine::Input(key, event)).unwrap();
+        }
+    }
+    for event in axis_events.read() {
+        for (key, event) in gamepad_mapping.axis_events(event, &mut state) {
+            machine.tx.try_send(ToMachine::Input(key, event)).unwrap();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_machine(
     mut machine: ResMut<Machine>,
     key_mapping: Res<KeyMapping>,
     mut key_events: EventReader<KeyboardInput>,
     mut emulator_data: ResMut<EmulatorData>,
     mut diagnostics: Diagnostics,
+    mut capture: ResMut<Capture>,
+    mut frame: ResMut<Frame>,
     exit: EventReader<AppExit>,
-) -> Vec<(AudioStatus, u8, [u8; 16])> {
+) -> Vec<MachineOutput> {
     for (key, event) in key_events.read().filter_map(|event| {
-        key_mapping.keys.get(&event.key_code).map(|key| {
+        key_mapping.key_for(event).map(|key| {
             (
-                *key,
+                key,
                 match event.state {
                     ButtonState::Pressed => KeyEvent::Press,
                     ButtonState::Released => KeyEvent::Release,
@@ -285,63 +832,161 @@ fn handle_machine(
         machine.tx.try_send(ToMachine::Exit).unwrap();
     }
 
-    let mut machine_audio = Vec::new();
+    let mut outputs = Vec::new();
     while let Ok(event) = machine.frame_rx.try_recv() {
-        if let Some(event_machine) = event.machine {
+        let mut image = None;
+        if let Some(state) = event.state {
             machine.initialized = true;
-            machine.machine = event_machine;
+            machine.state = state;
+            if let Some(screen) = event.screen {
+                machine.screen = screen;
+            }
+            // Clips and post-processing need every frame, otherwise only the last one is shown
+            let post_processing = emulator_data.post_process != PostProcessMode::None;
+            let recording = capture.clip.is_some() && !emulator_data.paused;
+            if post_processing || recording {
+                machine.render_indexed(&mut frame.indexed, &emulator_data.palette);
+                let rendered = frame.indexed.to_image();
+                if let Some(clip) = capture.clip.as_mut().filter(|_| recording) {
+                    clip.push_frame(&emulator_data.upscale.apply(&rendered));
+                }
+                image = post_processing.then_some(rendered);
+            }
         }
+        machine.movie_status = event.movie_status;
+        if let Some(movie) = event.recorded_movie {
+            machine.recorded_movie = Some(movie);
+        }
+        machine.error = event.halted_on;
         match event.result {
             TickResult::Continue | TickResult::Exit => {}
             TickResult::HitBreakpoint => emulator_data.paused = true,
-            TickResult::Error(error) => error!("Emulator error: {error}"),
+            TickResult::Error(error) => match error.policy {
+                ErrorPolicy::Halt => {
+                    error!("Emulator error: {error}");
+                    emulator_data.paused = true;
+                }
+                ErrorPolicy::Skip => warn!("Skipped instruction after emulator error: {error}"),
+                ErrorPolicy::Reset => warn!("Reset machine after emulator error: {error}"),
+            },
         }
-        machine_audio.push((
-            event.audio_status,
-            machine.machine.pitch(),
-            *machine.machine.audio_pattern(),
-        ));
+        outputs.push(MachineOutput {
+            audio_status: event.audio_status,
+            image,
+        });
         if machine.initialized {
             diagnostics.add_measurement(&EMULATOR_FPS, || 1.0 / event.frame_time.as_secs_f64());
         }
     }
-    machine_audio
+    outputs
 }
 
 fn render_machine_output(
-    machine_audio: In<Vec<(AudioStatus, u8, [u8; 16])>>,
+    outputs: In<Vec<MachineOutput>>,
     machine: Res<Machine>,
     emulator_data: Res<EmulatorData>,
     mut frame: ResMut<Frame>,
     mut images: ResMut<Assets<Image>>,
     mut audio: ResMut<Chip8Audio>,
+    audio_settings: Res<AudioSettings>,
 ) {
-    if machine.initialized {
-        let image = images
-            .get_mut(&frame.handle)
-            .expect("Emulator frame not found");
-        frame.size = write_frame(image, machine.machine.render_frame(&emulator_data.palette));
-    }
+    let background = emulator_data
+        .palette
+        .background(machine.instruction_set() == InstructionSet::XoChip);
+    frame.post_processor.set_mode(emulator_data.post_process);
 
-    for (status, pitch, pattern) in machine_audio.0 {
-        match status {
-            AudioStatus::Play(timestep) => {
-                audio.render_audio(pitch, pattern, timestep.as_secs_f64())
+    let mut latest = None;
+    for output in outputs.0 {
+        if let Some(image) = output.image {
+            latest = Some(frame.post_processor.process(image, background));
+        }
+        match output.audio_status {
+            AudioStatus::Play(timestep, frame) => {
+                audio.render_audio(&frame, timestep.as_secs_f64(), &audio_settings)
             }
             AudioStatus::Paused => {}
             AudioStatus::Reset => audio.reset(),
         }
     }
+
+    let frame = frame.as_mut();
+    if let Some(latest) = latest {
+        let image = images
+            .get_mut(&frame.handle)
+            .expect("Emulator frame not found");
+        // The debug grid needs the size of the emulated screen, not the upscaled one
+        frame.size = UVec2::from(latest.dimensions());
+        let upscaled = emulator_data.upscale.apply(&latest);
+        write_frame(image, upscaled.dimensions(), upscaled.as_raw());
+        frame.written = None;
+    } else if machine.initialized && emulator_data.post_process == PostProcessMode::None {
+        // Without any post-processing, only touch the texture when the screen or the palette has
+        // changed, even if the machine has stopped
+        machine.render_indexed(&mut frame.indexed, &emulator_data.palette);
+        let written = (frame.indexed.generation(), emulator_data.upscale);
+        if frame.written == Some(written) {
+            return;
+        }
+        let image = images
+            .get_mut(&frame.handle)
+            .expect("Emulator frame not found");
+        frame.size = UVec2::from(frame.indexed.dimensions());
+        if emulator_data.upscale == UpscaleFilter::None {
+            write_frame(image, frame.indexed.dimensions(), frame.indexed.rgba());
+        } else {
+            let upscaled = emulator_data.upscale.apply(&frame.indexed.to_image());
+            write_frame(image, upscaled.dimensions(), upscaled.as_raw());
+        }
+        frame.written = Some(written);
+    }
 }
 
-fn write_frame(texture: &mut Image, frame: RgbaImage) -> UVec2 {
-    if texture.width() != frame.width() || texture.height() != texture.height() {
+fn write_frame(texture: &mut Image, (width, height): (u32, u32), pixels: &[u8]) {
+    if texture.width() != width || texture.height() != height {
         texture.resize(Extent3d {
-            width: frame.width(),
-            height: frame.height(),
+            width,
+            height,
             depth_or_array_layers: 1,
         });
     }
-    texture.data = frame.into_vec();
-    texture.size()
+    texture.data.clear();
+    texture.data.extend_from_slice(pixels);
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use super::{spawn_machine_thread, SharedMemory, ToMachine};
+    use crate::{hardware::DynamicMachine, model::DynamicModel};
+
+    #[test]
+    fn test_frame_advance_when_uncapped() {
+        // With two instructions per frame, v0 counts the frames: 7001 1200
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let (tx, rx) = spawn_machine_thread(60.0, 2, Arc::new(SharedMemory::default()));
+        for message in [
+            ToMachine::Pause(true),
+            ToMachine::SetSpeed(f64::INFINITY),
+            ToMachine::ResetMachine(DynamicMachine::new(DynamicModel::XO_CHIP, &rom)),
+        ] {
+            tx.send_blocking(message).unwrap();
+        }
+        while rx.recv_blocking().unwrap().state.is_none() {}
+
+        tx.send_blocking(ToMachine::FrameAdvance).unwrap();
+        // Frames keep arriving while paused, and the first one after the advance shows all of it
+        let timeout = Instant::now() + Duration::from_secs(5);
+        let frames = loop {
+            let state = rx.recv_blocking().unwrap().state.unwrap();
+            if state.cpu.v[0] != 0 || Instant::now() > timeout {
+                break state.cpu.v[0];
+            }
+        };
+        assert_eq!(frames, 1);
+        tx.send_blocking(ToMachine::Exit).unwrap();
+    }
 }
@@ -6,24 +6,37 @@ use bevy::{
     prelude::*,
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
 };
+use machine::ErrorPolicy;
+use settings::Settings;
 use ui::style;
 
 use crate::{
     model::{self, DynamicModel, Model},
-    screen::Palette,
+    postprocess::{PostProcessMode, PostProcessor},
+    screen::{IndexedFrame, Palette},
+    upscale::UpscaleFilter,
 };
 
 pub mod audio;
+mod capture;
 mod debug;
 mod layout;
 mod machine;
+mod movie;
+mod palettes;
 mod rom;
+mod settings;
 mod ui;
 
 #[derive(Resource)]
 struct Frame {
     handle: Handle<Image>,
     size: UVec2,
+    post_processor: PostProcessor,
+    indexed: IndexedFrame,
+    /// What's in the texture, if it was written from `indexed`: its generation and the upscaling
+    /// filter used.
+    written: Option<(u64, UpscaleFilter)>,
 }
 
 #[derive(Clone, Resource)]
@@ -35,6 +48,29 @@ struct EmulatorData {
     machine_model: DynamicModel,
     rom_name: Option<String>,
     palette: Palette,
+    post_process: PostProcessMode,
+    upscale: UpscaleFilter,
+    /// Whether fast-forward is being held.
+    fast_forward: bool,
+    /// How many times faster than normal to run while fast-forwarding, or infinity for as fast
+    /// as possible.
+    fast_forward_speed: f64,
+    slow_motion: bool,
+    slow_motion_speed: f64,
+    error_policy: ErrorPolicy,
+}
+
+impl EmulatorData {
+    /// The current speed, relative to the target frame rate. Fast-forward overrides slow motion.
+    fn speed(&self) -> f64 {
+        if self.fast_forward {
+            self.fast_forward_speed
+        } else if self.slow_motion {
+            self.slow_motion_speed
+        } else {
+            1.0
+        }
+    }
 }
 
 impl Default for EmulatorData {
@@ -47,6 +83,13 @@ impl Default for EmulatorData {
             machine_model: Default::default(),
             rom_name: None,
             palette: Default::default(),
+            post_process: Default::default(),
+            upscale: Default::default(),
+            fast_forward: false,
+            fast_forward_speed: 4.0,
+            slow_motion: false,
+            slow_motion_speed: 0.25,
+            error_policy: Default::default(),
         }
     }
 }
@@ -55,6 +98,16 @@ impl Default for EmulatorData {
 enum EmulatorEvent {
     PickRom,
     ResetMachine,
+    StartRecording,
+    StopMovie,
+    PlayMovie,
+    Screenshot,
+    ToggleClipRecording,
+    ToggleAudioCapture,
+    ImportPalette,
+    ExportPalette,
+    /// Run exactly one frame while paused.
+    FrameAdvance,
 }
 
 const EMULATOR_TICK_RATE: DiagnosticPath = DiagnosticPath::const_new("emulator_tick_rate");
@@ -62,17 +115,23 @@ const EMULATOR_TICK_RATE: DiagnosticPath = DiagnosticPath::const_new("emulator_t
 const FRAME_ASPECT_RATIO: Vec2 = Vec2::new(2.0, 1.0);
 
 pub fn emulator_plugin(app: &mut App) {
-    app.init_resource::<EmulatorData>()
+    app.init_resource::<Settings>()
+        .init_resource::<EmulatorData>()
         .add_event::<EmulatorEvent>()
         .add_audio_source::<Chip8Audio>()
-        .add_systems(Startup, setup)
+        .add_systems(Startup, (setup, load_emulator_settings))
+        .add_systems(Update, save_emulator_settings)
         .register_diagnostic(Diagnostic::new(EMULATOR_TICK_RATE))
         .add_plugins((
             layout::layout_plugin,
             machine::machine_plugin,
             ui::ui_plugin,
             rom::rom_plugin,
+            movie::movie_plugin,
             debug::debug_plugin,
+            capture::capture_plugin,
+            audio::audio_plugin,
+            palettes::palette_plugin,
         ));
 }
 
@@ -103,6 +162,9 @@ fn setup(
     commands.insert_resource(Frame {
         handle,
         size: UVec2::new(1, 1),
+        post_processor: Default::default(),
+        indexed: Default::default(),
+        written: None,
     });
 
     let audio = Chip8Audio::new();
@@ -110,3 +172,54 @@ fn setup(
     commands.spawn(AudioPlayer(beeper_handle));
     commands.insert_resource(audio);
 }
+
+fn load_emulator_settings(settings: Res<Settings>, mut emulator_data: ResMut<EmulatorData>) {
+    if let Some(filter) = settings
+        .get::<String>("display.upscale")
+        .and_then(|id| UpscaleFilter::from_id(&id))
+    {
+        emulator_data.upscale = filter;
+    }
+    // Infinity is saved as `inf`, which parses back to the same value
+    if let Some(speed) = settings
+        .get::<f64>("speed.fast_forward")
+        .filter(|speed| *speed > 1.0)
+    {
+        emulator_data.fast_forward_speed = speed;
+    }
+    if let Some(speed) = settings
+        .get::<f64>("speed.slow_motion")
+        .filter(|speed| *speed > 0.0 && *speed < 1.0)
+    {
+        emulator_data.slow_motion_speed = speed;
+    }
+    if let Some(policy) = settings
+        .get::<String>("debug.error_policy")
+        .and_then(|id| ErrorPolicy::from_id(&id))
+    {
+        emulator_data.error_policy = policy;
+    }
+}
+
+fn save_emulator_settings(
+    emulator_data: Res<EmulatorData>,
+    mut settings: ResMut<Settings>,
+    mut last_saved: Local<Option<(UpscaleFilter, f64, f64, ErrorPolicy)>>,
+) {
+    let current = (
+        emulator_data.upscale,
+        emulator_data.fast_forward_speed,
+        emulator_data.slow_motion_speed,
+        emulator_data.error_policy,
+    );
+    settings.save_if_changed(
+        &mut last_saved,
+        &current,
+        |settings, (upscale, fast_forward_speed, slow_motion_speed, error_policy)| {
+            settings.set("display.upscale", upscale.id());
+            settings.set("speed.fast_forward", fast_forward_speed);
+            settings.set("speed.slow_motion", slow_motion_speed);
+            settings.set("debug.error_policy", error_policy.id());
+        },
+    );
+}
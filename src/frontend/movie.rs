@@ -0,0 +1,123 @@
+use bevy::{
+    prelude::*,
+    tasks::{block_on, poll_once, IoTaskPool, Task},
+};
+
+use crate::{model::Model, movie::Movie};
+
+use super::{
+    machine::{Machine, ToMachine},
+    rom::Rom,
+    EmulatorData, EmulatorEvent,
+};
+
+const MOVIE_EXTENSION: &str = "c8movie";
+
+#[derive(Component)]
+struct PickMovie(Task<Option<Movie>>);
+
+pub fn movie_plugin(app: &mut App) {
+    app.add_systems(Update, movie_loaded.run_if(any_with_component::<PickMovie>))
+        .add_systems(Update, save_recorded_movie)
+        .add_systems(
+            PostUpdate,
+            start_pick_movie.run_if(on_event::<EmulatorEvent>),
+        );
+}
+
+fn start_pick_movie(mut commands: Commands, mut ui_events: EventReader<EmulatorEvent>) {
+    for event in ui_events.read() {
+        if matches!(event, EmulatorEvent::PlayMovie) {
+            let task = IoTaskPool::get().spawn(async {
+                let file = rfd::AsyncFileDialog::new()
+                    .set_title("Choose a movie file")
+                    .add_filter("Movies", &[MOVIE_EXTENSION])
+                    .pick_file()
+                    .await?;
+
+                let text = async_fs::read_to_string(file.path())
+                    .await
+                    .inspect_err(|error| {
+                        error!(
+                            "Error reading chosen file {}: {}",
+                            file.path().display(),
+                            error
+                        )
+                    })
+                    .ok()?;
+                Movie::parse(&text)
+                    .inspect_err(|error| {
+                        error!("Error parsing movie {}: {}", file.path().display(), error)
+                    })
+                    .ok()
+            });
+            commands.spawn(PickMovie(task));
+        }
+    }
+}
+
+fn movie_loaded(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut PickMovie)>,
+    mut ui_data: ResMut<EmulatorData>,
+    rom: Option<Res<Rom>>,
+    machine: Res<Machine>,
+) {
+    for (entity, mut task) in &mut tasks {
+        if let Some(maybe_movie) = block_on(poll_once(&mut task.0)) {
+            commands.entity(entity).despawn();
+            let Some(movie) = maybe_movie else {
+                continue;
+            };
+            let Some(rom) = rom.as_ref() else {
+                error!("Load the movie's ROM before playing it");
+                continue;
+            };
+            match movie.new_machine(&rom.0) {
+                Ok(new_machine) => {
+                    ui_data.machine_model = movie.model.clone();
+                    ui_data.cycles_per_frame = movie.ipf;
+                    ui_data.frame_rate = movie.frame_rate;
+                    ui_data.use_default_framerate =
+                        movie.frame_rate == movie.model.default_framerate();
+                    ui_data.paused = false;
+                    machine
+                        .tx
+                        .try_send(ToMachine::PlayMovie(new_machine, movie))
+                        .unwrap();
+                }
+                Err(error) => error!("Can't play movie: {error}"),
+            }
+        }
+    }
+}
+
+fn save_recorded_movie(mut machine: ResMut<Machine>, ui_data: Res<EmulatorData>) {
+    let Some(movie) = machine.recorded_movie.take() else {
+        return;
+    };
+    let file_name = format!(
+        "{}.{MOVIE_EXTENSION}",
+        ui_data
+            .rom_name
+            .as_deref()
+            .and_then(|name| name.rsplit_once('.').map(|(stem, _)| stem))
+            .unwrap_or("movie")
+    );
+    IoTaskPool::get()
+        .spawn(async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .set_title("Save movie")
+                .set_file_name(file_name)
+                .add_filter("Movies", &[MOVIE_EXTENSION])
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            if let Err(error) = async_fs::write(file.path(), movie.serialize()).await {
+                error!("Error saving movie to {}: {}", file.path().display(), error);
+            }
+        })
+        .detach();
+}
@@ -0,0 +1,262 @@
+use std::path::Path;
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, poll_once, IoTaskPool, Task},
+};
+use bevy_egui::egui;
+
+use crate::screen::{Palette, PRESETS};
+
+use super::{settings::Settings, EmulatorData, EmulatorEvent};
+
+const CUSTOM_PRESET_PREFIX: &str = "palette.custom.";
+
+/// Palettes the user has saved, by name.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct CustomPalettes {
+    pub presets: Vec<(String, Palette)>,
+    /// The name to save the current palette under.
+    pub new_name: String,
+}
+
+#[derive(Component)]
+struct ImportPalette(Task<Option<Palette>>);
+
+pub fn palette_plugin(app: &mut App) {
+    app.init_resource::<Settings>();
+    let custom_palettes = load_custom_palettes(app.world().resource::<Settings>());
+    app.insert_resource(custom_palettes)
+        .add_systems(
+            Update,
+            palette_imported.run_if(any_with_component::<ImportPalette>),
+        )
+        .add_systems(Update, save_custom_palettes)
+        .add_systems(
+            PostUpdate,
+            (start_import_palette, export_palette).run_if(on_event::<EmulatorEvent>),
+        );
+}
+
+fn load_custom_palettes(settings: &Settings) -> CustomPalettes {
+    let presets = settings
+        .keys()
+        .filter_map(|key| key.strip_prefix(CUSTOM_PRESET_PREFIX))
+        .filter_map(|name| {
+            let colors = settings.get::<String>(&format!("{CUSTOM_PRESET_PREFIX}{name}"))?;
+            Palette::parse_hex(&colors)
+                .inspect_err(|error| warn!("Ignoring saved palette {name}: {error}"))
+                .ok()
+                .map(|palette| (name.to_owned(), palette))
+        })
+        .collect();
+    CustomPalettes {
+        presets,
+        new_name: String::new(),
+    }
+}
+
+fn save_custom_palettes(
+    custom_palettes: Res<CustomPalettes>,
+    mut settings: ResMut<Settings>,
+    mut last_saved: Local<Option<Vec<(String, Palette)>>>,
+) {
+    settings.save_if_changed(
+        &mut last_saved,
+        &custom_palettes.presets,
+        |settings, presets| {
+            let old_names = settings
+                .keys()
+                .filter(|key| key.starts_with(CUSTOM_PRESET_PREFIX))
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            for key in old_names {
+                settings.remove(&key);
+            }
+            for (name, palette) in presets {
+                let colors = palette
+                    .to_hex()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                settings.set(&format!("{CUSTOM_PRESET_PREFIX}{name}"), colors);
+            }
+        },
+    );
+}
+
+fn start_import_palette(mut commands: Commands, mut ui_events: EventReader<EmulatorEvent>) {
+    for event in ui_events.read() {
+        if matches!(event, EmulatorEvent::ImportPalette) {
+            let task = IoTaskPool::get().spawn(async {
+                let file = rfd::AsyncFileDialog::new()
+                    .set_title("Import a palette")
+                    .add_filter("Palettes", &["gpl", "hex", "txt"])
+                    .pick_file()
+                    .await?;
+
+                let text = async_fs::read_to_string(file.path())
+                    .await
+                    .inspect_err(|error| {
+                        error!(
+                            "Error reading chosen file {}: {}",
+                            file.path().display(),
+                            error
+                        )
+                    })
+                    .ok()?;
+                let palette = if is_gpl(file.path()) {
+                    Palette::parse_gpl(&text)
+                } else {
+                    Palette::parse_hex(&text)
+                };
+                palette
+                    .inspect_err(|error| {
+                        error!("Error parsing palette {}: {}", file.path().display(), error)
+                    })
+                    .ok()
+            });
+            commands.spawn(ImportPalette(task));
+        }
+    }
+}
+
+fn palette_imported(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut ImportPalette)>,
+    mut ui_data: ResMut<EmulatorData>,
+) {
+    for (entity, mut task) in &mut tasks {
+        if let Some(maybe_palette) = block_on(poll_once(&mut task.0)) {
+            commands.entity(entity).despawn();
+            if let Some(palette) = maybe_palette {
+                ui_data.palette = palette;
+            }
+        }
+    }
+}
+
+fn export_palette(mut ui_events: EventReader<EmulatorEvent>, ui_data: Res<EmulatorData>) {
+    if !ui_events
+        .read()
+        .any(|event| matches!(event, EmulatorEvent::ExportPalette))
+    {
+        return;
+    }
+    let palette = ui_data.palette.clone();
+    IoTaskPool::get()
+        .spawn(async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .set_title("Export palette")
+                .set_file_name("palette.gpl")
+                .add_filter("GIMP palette", &["gpl"])
+                .add_filter("Hex palette", &["hex"])
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            let text = if is_gpl(file.path()) {
+                let name = file
+                    .path()
+                    .file_stem()
+                    .map_or("Palette".into(), |stem| stem.to_string_lossy());
+                palette.to_gpl(&name)
+            } else {
+                palette.to_hex()
+            };
+            if let Err(error) = async_fs::write(file.path(), text).await {
+                error!(
+                    "Error saving palette to {}: {}",
+                    file.path().display(),
+                    error
+                );
+            }
+        })
+        .detach();
+}
+
+fn is_gpl(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gpl"))
+}
+
+pub fn palette_presets(
+    ui: &mut egui::Ui,
+    palette: &mut Palette,
+    custom_palettes: &mut CustomPalettes,
+    events: &mut EventWriter<EmulatorEvent>,
+) {
+    let current = PRESETS
+        .iter()
+        .map(|preset| (preset.name, preset.palette()))
+        .chain(
+            custom_palettes
+                .presets
+                .iter()
+                .map(|(name, palette)| (name.as_str(), palette.clone())),
+        )
+        .find(|(_, preset)| preset.colors() == palette.colors())
+        .map(|(name, _)| name.to_owned());
+
+    let mut delete = None;
+    egui::ComboBox::from_label("Preset")
+        .selected_text(current.as_deref().unwrap_or("Custom"))
+        .show_ui(ui, |ui| {
+            for preset in PRESETS {
+                let selected = current.as_deref() == Some(preset.name);
+                if ui.selectable_label(selected, preset.name).clicked() {
+                    *palette = preset.palette();
+                }
+            }
+            if !custom_palettes.presets.is_empty() {
+                ui.separator();
+            }
+            for (i, (name, preset)) in custom_palettes.presets.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let selected = current.as_deref() == Some(name.as_str());
+                    if ui.selectable_label(selected, name).clicked() {
+                        *palette = preset.clone();
+                    }
+                    if ui.small_button("🗑").on_hover_text("Delete").clicked() {
+                        delete = Some(i);
+                    }
+                });
+            }
+        });
+    if let Some(i) = delete {
+        custom_palettes.presets.remove(i);
+    }
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut custom_palettes.new_name)
+                .hint_text("Preset name")
+                .desired_width(120.0),
+        );
+        // Names become keys in the settings file, so they can't contain `=`
+        let name = custom_palettes.new_name.replace('=', "").trim().to_owned();
+        if ui
+            .add_enabled(!name.is_empty(), egui::Button::new("Save Preset"))
+            .clicked()
+        {
+            match custom_palettes
+                .presets
+                .iter_mut()
+                .find(|(existing, _)| *existing == name)
+            {
+                Some((_, existing)) => *existing = palette.clone(),
+                None => custom_palettes.presets.push((name, palette.clone())),
+            }
+            custom_palettes.new_name.clear();
+        }
+    });
+    ui.horizontal(|ui| {
+        if ui.button("Import...").clicked() {
+            events.send(EmulatorEvent::ImportPalette);
+        }
+        if ui.button("Export...").clicked() {
+            events.send(EmulatorEvent::ExportPalette);
+        }
+    });
+}
@@ -0,0 +1,172 @@
+use std::{collections::BTreeMap, fmt::Display, path::Path, str::FromStr};
+
+use bevy::{prelude::*, tasks::IoTaskPool};
+
+/// Where settings are saved, relative to the working directory.
+pub const SETTINGS_PATH: &str = "murmur8tion.cfg";
+
+/// Settings that are saved between runs, as `key = value` lines. Unknown keys are kept, so that
+/// each part of the frontend only needs to know about its own settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingsFile(BTreeMap<String, String>);
+
+impl SettingsFile {
+    /// Load the settings file, or start with empty settings if it doesn't exist or can't be read.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        match std::fs::read_to_string(path.as_ref()) {
+            Ok(text) => Self::parse(&text),
+            Err(error) => {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Error reading {}: {}", path.as_ref().display(), error);
+                }
+                Self::default()
+            }
+        }
+    }
+
+    /// Parse `key = value` lines, ignoring blank lines, comments starting with `#`, and anything
+    /// else that doesn't look like a setting.
+    pub fn parse(text: &str) -> Self {
+        Self(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+                .collect(),
+        )
+    }
+
+    pub fn serialize(&self) -> String {
+        self.0
+            .iter()
+            .map(|(key, value)| format!("{key} = {value}\n"))
+            .collect()
+    }
+
+    /// Get a setting, if it's present and valid.
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        let value = self.0.get(key)?;
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            warn!("Ignoring invalid value for setting {key}: {value}");
+        }
+        parsed
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Display) {
+        self.0.insert(key.to_owned(), value.to_string());
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.0.remove(key);
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Store `value` with `store` if it's different from `last_saved`, returning whether it was.
+    /// The first call only remembers `value`, since it's what was just loaded.
+    pub fn store_if_changed<T: Clone + PartialEq>(
+        &mut self,
+        last_saved: &mut Option<T>,
+        value: &T,
+        store: impl FnOnce(&mut Self, &T),
+    ) -> bool {
+        match last_saved {
+            Some(saved) if saved == value => false,
+            Some(saved) => {
+                saved.clone_from(value);
+                store(self, value);
+                true
+            }
+            None => {
+                *last_saved = Some(value.clone());
+                false
+            }
+        }
+    }
+
+    /// Write the settings file in the background.
+    pub fn save_to(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_owned();
+        let text = self.serialize();
+        IoTaskPool::get()
+            .spawn(async move {
+                if let Err(error) = std::fs::write(&path, text) {
+                    error!("Error saving settings to {}: {}", path.display(), error);
+                }
+            })
+            .detach();
+    }
+}
+
+/// The frontend's saved settings, loaded from [`SETTINGS_PATH`] at startup.
+#[derive(Resource, Debug, Clone, Deref, DerefMut)]
+pub struct Settings(SettingsFile);
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self(SettingsFile::load(SETTINGS_PATH))
+    }
+}
+
+impl Settings {
+    pub fn save(&self) {
+        self.0.save_to(SETTINGS_PATH);
+    }
+
+    /// Store and save `value` if it's changed since `last_saved`. The UI mutably borrows the
+    /// resources it edits every frame, so Bevy's change detection can't tell when they really
+    /// changed, and systems that save them keep the last saved value in a `Local` instead.
+    pub fn save_if_changed<T: Clone + PartialEq>(
+        &mut self,
+        last_saved: &mut Option<T>,
+        value: &T,
+        store: impl FnOnce(&mut SettingsFile, &T),
+    ) {
+        if self.0.store_if_changed(last_saved, value, store) {
+            self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SettingsFile;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut settings = SettingsFile::parse(
+            "# comment\n\naudio.volume = 0.5\n  audio.muted=true  \nnot a setting\nother = x = y\n",
+        );
+        assert_eq!(settings.get::<f32>("audio.volume"), Some(0.5));
+        assert_eq!(settings.get::<bool>("audio.muted"), Some(true));
+        assert_eq!(settings.get::<bool>("other"), None);
+        assert_eq!(settings.get::<String>("other").as_deref(), Some("x = y"));
+        assert_eq!(settings.get::<u32>("missing"), None);
+
+        settings.set("audio.volume", 0.25);
+        let reparsed = SettingsFile::parse(&settings.serialize());
+        assert_eq!(reparsed, settings);
+        assert_eq!(reparsed.get::<f32>("audio.volume"), Some(0.25));
+    }
+
+    #[test]
+    fn test_store_if_changed() {
+        let mut settings = SettingsFile::default();
+        let mut last_saved = None;
+        let store = |settings: &mut SettingsFile, value: &u32| settings.set("value", value);
+
+        // The first value is what was loaded, so there's nothing to store
+        assert!(!settings.store_if_changed(&mut last_saved, &1, store));
+        assert_eq!(settings.get::<u32>("value"), None);
+        assert!(!settings.store_if_changed(&mut last_saved, &1, store));
+
+        assert!(settings.store_if_changed(&mut last_saved, &2, store));
+        assert_eq!(settings.get::<u32>("value"), Some(2));
+        assert_eq!(last_saved, Some(2));
+        assert!(!settings.store_if_changed(&mut last_saved, &2, store));
+    }
+}
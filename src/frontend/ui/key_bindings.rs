@@ -0,0 +1,223 @@
+use arbitrary_int::u4;
+use bevy::{
+    input::{
+        keyboard::{KeyCode, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
+use bevy_egui::egui::{self, Ui};
+
+use crate::frontend::{
+    machine::keymap::{GamepadMapping, KeyBinding, KeyMapping, KeyProfile, KEYPAD_LAYOUT},
+    EmulatorData,
+};
+
+use super::style;
+
+#[derive(Default)]
+pub struct KeyBindingsState {
+    capture_logical: bool,
+    /// The profile name being typed, which is only applied while it's not a duplicate.
+    name: String,
+}
+
+pub fn key_bindings_ui(
+    ui: InMut<Ui>,
+    emulator_data: Res<EmulatorData>,
+    mut key_mapping: ResMut<KeyMapping>,
+    mut gamepad_mapping: ResMut<GamepadMapping>,
+    mut key_events: EventReader<KeyboardInput>,
+    mut state: Local<KeyBindingsState>,
+) {
+    key_mapping.editor_drawn = true;
+    for event in key_events.read() {
+        let Some(key) = key_mapping.capturing else {
+            continue;
+        };
+        if event.state != ButtonState::Pressed || event.repeat {
+            continue;
+        }
+        if event.key_code != KeyCode::Escape {
+            let binding = if state.capture_logical {
+                KeyBinding::Logical(event.logical_key.clone())
+            } else {
+                KeyBinding::Physical(event.key_code)
+            };
+            key_mapping.active_profile_mut().bind(key, binding);
+        }
+        key_mapping.capturing = None;
+    }
+
+    profile_selector(ui.0, &mut key_mapping);
+
+    ui.0.horizontal(|ui| {
+        ui.label("Profile name:");
+        let name_id = egui::Id::new("key_profile_name");
+        if !ui.memory(|memory| memory.has_focus(name_id)) {
+            state.name.clone_from(&key_mapping.active_profile().name);
+        }
+        let response = ui.add(egui::TextEdit::singleline(&mut state.name).id(name_id));
+        let name_free = key_mapping.is_name_free(&state.name, Some(key_mapping.active));
+        if response.changed() && name_free && !state.name.trim().is_empty() {
+            key_mapping
+                .active_profile_mut()
+                .name
+                .clone_from(&state.name);
+        }
+        if !name_free {
+            ui.colored_label(style::ACCENT_LIGHT, "Another profile has this name");
+        }
+    });
+
+    ui.0.horizontal(|ui| {
+        if ui.button("Duplicate").clicked() {
+            let mut profile = key_mapping.active_profile().clone();
+            profile.name.push_str(" (copy)");
+            key_mapping.add_profile(profile);
+        }
+        if ui
+            .add_enabled(key_mapping.profiles.len() > 1, egui::Button::new("Delete"))
+            .clicked()
+        {
+            key_mapping.remove_active_profile();
+        }
+    });
+
+    if let Some(rom_name) = emulator_data.rom_name.as_ref() {
+        let profile_id = key_mapping.active_profile().id;
+        let mut use_for_rom = key_mapping.rom_profiles.get(rom_name) == Some(&profile_id);
+        if ui
+            .0
+            .checkbox(&mut use_for_rom, format!("Use this profile for {rom_name}"))
+            .changed()
+        {
+            if use_for_rom {
+                key_mapping
+                    .rom_profiles
+                    .insert(rom_name.clone(), profile_id);
+            } else {
+                key_mapping.rom_profiles.remove(rom_name);
+            }
+        }
+    }
+
+    ui.0.checkbox(
+        &mut state.capture_logical,
+        "Bind by typed character instead of key position",
+    );
+
+    ui.0.separator();
+
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui.0, |ui| {
+            egui::Grid::new("key_bindings")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    for key in KEYPAD_LAYOUT.as_flattened() {
+                        let key = u4::new(*key);
+                        ui.colored_label(style::ACCENT_LIGHT, format!("{key:X}"));
+                        key_binding_row(ui, &mut key_mapping, key);
+                        ui.end_row();
+                    }
+                });
+
+            ui.separator();
+            ui.collapsing("Gamepad", |ui| gamepad_bindings(ui, &mut gamepad_mapping));
+        });
+}
+
+fn gamepad_bindings(ui: &mut Ui, gamepad_mapping: &mut GamepadMapping) {
+    ui.add(egui::Slider::new(&mut gamepad_mapping.dead_zone, 0.05..=0.95).text("Stick dead zone"));
+
+    egui::Grid::new("gamepad_axes")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for binding in &mut gamepad_mapping.axes {
+                ui.label(format!("{:?}", binding.axis));
+                key_selector(ui, ("negative", binding.axis), &mut binding.negative);
+                key_selector(ui, ("positive", binding.axis), &mut binding.positive);
+                ui.end_row();
+            }
+        });
+
+    ui.separator();
+
+    egui::Grid::new("gamepad_buttons")
+        .num_columns(2)
+        .show(ui, |ui| {
+            for button in GamepadButton::all() {
+                let mut key = gamepad_mapping.buttons.get(&button).copied();
+                ui.label(format!("{button:?}"));
+                if key_selector(ui, button, &mut key) {
+                    match key {
+                        Some(key) => gamepad_mapping.buttons.insert(button, key),
+                        None => gamepad_mapping.buttons.remove(&button),
+                    };
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// A dropdown for picking a CHIP-8 key, or none. Returns whether the selection changed.
+fn key_selector(ui: &mut Ui, id: impl std::hash::Hash, key: &mut Option<u4>) -> bool {
+    let before = *key;
+    egui::ComboBox::from_id_salt(id)
+        .width(48.0)
+        .selected_text(key.map_or("-".to_string(), |key| format!("{key:X}")))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(key, None, "-");
+            for value in KEYPAD_LAYOUT.as_flattened() {
+                let value = u4::new(*value);
+                ui.selectable_value(key, Some(value), format!("{value:X}"));
+            }
+        });
+    *key != before
+}
+
+fn profile_selector(ui: &mut Ui, key_mapping: &mut KeyMapping) {
+    let KeyMapping {
+        profiles, active, ..
+    } = key_mapping;
+    egui::ComboBox::from_label("Key mapping profile")
+        .selected_text(profiles[*active].name.as_str())
+        .show_ui(ui, |ui| {
+            for (i, profile) in profiles.iter().enumerate() {
+                ui.selectable_value(active, i, profile.name.as_str());
+            }
+        });
+}
+
+fn key_binding_row(ui: &mut Ui, key_mapping: &mut KeyMapping, key: u4) {
+    ui.horizontal_wrapped(|ui| {
+        let KeyProfile { bindings, .. } = key_mapping.active_profile_mut();
+        let bindings = &mut bindings[key.value() as usize];
+        let mut remove = None;
+        for (i, binding) in bindings.iter().enumerate() {
+            if ui
+                .button(binding.to_string())
+                .on_hover_text("Click to remove")
+                .clicked()
+            {
+                remove = Some(i);
+            }
+        }
+        if let Some(i) = remove {
+            bindings.remove(i);
+        }
+
+        if key_mapping.capturing == Some(key) {
+            if ui
+                .button("Press a key...")
+                .on_hover_text("Press Escape or click to cancel")
+                .clicked()
+            {
+                key_mapping.capturing = None;
+            }
+        } else if ui.button("+").on_hover_text("Add a binding").clicked() {
+            key_mapping.capturing = Some(key);
+        }
+    });
+}
@@ -0,0 +1,94 @@
+use arbitrary_int::u4;
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Ui};
+
+use crate::{
+    frontend::machine::{keymap::KEYPAD_LAYOUT, Machine, ToMachine},
+    hardware::KeyEvent,
+};
+
+use super::style;
+
+/// The key held down on the keypad pane. It's a resource rather than local to the pane, so that
+/// it can be released if the pane is closed or hidden while it's held.
+#[derive(Resource, Default)]
+pub struct KeypadState {
+    held: Option<u4>,
+    drawn: bool,
+}
+
+pub fn keypad_ui(ui: InMut<Ui>, machine: Res<Machine>, mut state: ResMut<KeypadState>) {
+    state.drawn = true;
+    let pressed_keys = machine.pressed_keys();
+    let spacing = ui.0.spacing().item_spacing;
+    let key_size = ((ui.0.available_size() - spacing * 3.0) / 4.0)
+        .min_elem()
+        .max(16.0);
+    let font = egui::FontId::monospace(key_size / 2.0);
+
+    let mut pointer_key = None;
+    egui::Grid::new("keypad").spacing(spacing).show(ui.0, |ui| {
+        for row in KEYPAD_LAYOUT {
+            for key in row {
+                let (rect, response) =
+                    ui.allocate_exact_size(egui::Vec2::splat(key_size), egui::Sense::drag());
+                if response.is_pointer_button_down_on() {
+                    pointer_key = Some(u4::new(key));
+                }
+
+                let (fill, text_color) = if pressed_keys & 1 << key != 0 {
+                    (style::ACCENT_LIGHT, style::BACKGROUND_DARK)
+                } else if response.hovered() {
+                    (style::FOREGROUND_MID, style::FOREGROUND_LIGHT)
+                } else {
+                    (style::FOREGROUND_DARK, style::FOREGROUND_LIGHT)
+                };
+                let painter = ui.painter();
+                painter.rect(
+                    rect,
+                    egui::Rounding::ZERO,
+                    fill,
+                    ui.visuals().widgets.inactive.bg_stroke,
+                );
+                painter.text(
+                    rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    format!("{key:X}"),
+                    font.clone(),
+                    text_color,
+                );
+            }
+            ui.end_row();
+        }
+    });
+
+    if pointer_key != state.held {
+        if let Some(key) = state.held.take() {
+            machine
+                .tx
+                .try_send(ToMachine::Input(key, KeyEvent::Release))
+                .unwrap();
+        }
+        if let Some(key) = pointer_key {
+            machine
+                .tx
+                .try_send(ToMachine::Input(key, KeyEvent::Press))
+                .unwrap();
+        }
+        state.held = pointer_key;
+    }
+}
+
+/// Release the held key if the keypad pane wasn't drawn this frame.
+pub fn release_hidden_keypad(machine: Res<Machine>, mut state: ResMut<KeypadState>) {
+    let state = state.bypass_change_detection();
+    if std::mem::take(&mut state.drawn) {
+        return;
+    }
+    if let Some(key) = state.held.take() {
+        machine
+            .tx
+            .try_send(ToMachine::Input(key, KeyEvent::Release))
+            .unwrap();
+    }
+}
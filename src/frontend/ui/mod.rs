@@ -3,30 +3,48 @@ use bevy::{
     prelude::*,
 };
 use bevy_egui::egui::{self, Ui};
-use widgets::{edit_quirks, model_selector, palette_editor};
+use keypad::{release_hidden_keypad, KeypadState};
+use widgets::{edit_quirks, model_selector, palette_editor, speed_controls, upscale_selector};
 
 use crate::model::Model;
 
 use super::{
+    audio::{show_audio_options, AudioSettings},
+    capture::{show_capture_options, Capture, SCREENSHOT_KEY},
     debug::{show_debug_options, DebugOptions},
-    machine::{EMULATOR_FPS, FRAME_TICK_TIME},
+    machine::{Machine, MovieStatus, EMULATOR_FPS, FRAME_TICK_TIME},
+    palettes::CustomPalettes,
     EmulatorData, EmulatorEvent,
 };
 
+mod key_bindings;
+mod keypad;
+mod sprites;
 pub mod style;
 mod widgets;
 
+pub use key_bindings::key_bindings_ui;
+pub use keypad::keypad_ui;
+pub use sprites::sprite_viewer_ui;
+
 pub fn ui_plugin(app: &mut App) {
     app.add_plugins(FrameTimeDiagnosticsPlugin)
-        .add_systems(Startup, style::apply_style);
+        .init_resource::<KeypadState>()
+        .add_systems(Startup, style::apply_style)
+        .add_systems(PostUpdate, release_hidden_keypad);
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn draw_main_ui(
     ui: InMut<Ui>,
     diagnostics: Res<DiagnosticsStore>,
     mut emulator_data: ResMut<EmulatorData>,
     mut events: EventWriter<EmulatorEvent>,
     mut debug_options: ResMut<DebugOptions>,
+    machine: Res<Machine>,
+    mut capture: ResMut<Capture>,
+    mut audio_settings: ResMut<AudioSettings>,
+    mut custom_palettes: ResMut<CustomPalettes>,
 ) {
     ui.0.label(format!(
         "FPS: {:.1}",
@@ -60,16 +78,75 @@ pub fn draw_main_ui(
 
         model_selector(ui, &mut emulator_data.machine_model);
 
-        if ui.button("Reset Emulator").clicked() {
-            events.send(EmulatorEvent::ResetMachine);
-        }
+        ui.horizontal(|ui| {
+            if ui.button("Reset Emulator").clicked() {
+                events.send(EmulatorEvent::ResetMachine);
+            }
+            if ui
+                .button("Screenshot")
+                .on_hover_text(format!("{SCREENSHOT_KEY:?}"))
+                .clicked()
+            {
+                events.send(EmulatorEvent::Screenshot);
+            }
+            let clip_label = match capture.clip.as_ref() {
+                Some(clip) => format!("Stop Clip ({} frames)", clip.length()),
+                None => format!("Record {}", capture.clip_format),
+            };
+            if ui.button(clip_label).clicked() {
+                events.send(EmulatorEvent::ToggleClipRecording);
+            }
+            let wav_label = if capture.wav_path.is_some() {
+                "Stop WAV"
+            } else {
+                "Record WAV"
+            };
+            if ui.button(wav_label).clicked() {
+                events.send(EmulatorEvent::ToggleAudioCapture);
+            }
+        });
+    });
+
+    ui.0.group(|ui| {
+        ui.horizontal(|ui| match machine.movie_status {
+            MovieStatus::Idle => {
+                if ui
+                    .add_enabled(
+                        emulator_data.rom_name.is_some(),
+                        egui::Button::new("Record Movie"),
+                    )
+                    .on_hover_text("Resets the emulator and records all input from the start")
+                    .clicked()
+                {
+                    events.send(EmulatorEvent::StartRecording);
+                }
+                if ui.button("Play Movie").clicked() {
+                    events.send(EmulatorEvent::PlayMovie);
+                }
+            }
+            MovieStatus::Recording { frame } => {
+                ui.colored_label(style::ACCENT_LIGHT, format!("Recording: frame {frame}"));
+                if ui.button("Stop and Save").clicked() {
+                    events.send(EmulatorEvent::StopMovie);
+                }
+            }
+            MovieStatus::Playing { frame, length } => {
+                ui.colored_label(
+                    style::ACCENT_LIGHT,
+                    format!("Playing: frame {frame} / {length}"),
+                );
+                if ui.button("Stop").clicked() {
+                    events.send(EmulatorEvent::StopMovie);
+                }
+            }
+        });
     });
 
     ui.0.group(|ui| {
         egui::ScrollArea::vertical().show(ui, |ui| {
             ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Wrap);
 
-            ui.toggle_value(&mut emulator_data.paused, "Pause");
+            speed_controls(ui, &mut emulator_data, &mut events);
 
             let original_tick_rate = emulator_data.frame_rate;
             ui.add(
@@ -97,8 +174,18 @@ pub fn draw_main_ui(
                     .text("Cycles per frame"),
             );
 
-            palette_editor(ui, &mut emulator_data.palette);
-            show_debug_options(ui, &mut debug_options);
+            let emulator_data = &mut *emulator_data;
+            palette_editor(
+                ui,
+                &mut emulator_data.palette,
+                &mut emulator_data.post_process,
+                &mut custom_palettes,
+                &mut events,
+            );
+            upscale_selector(ui, &mut emulator_data.upscale);
+            show_audio_options(ui, &mut audio_settings);
+            show_capture_options(ui, &mut capture);
+            show_debug_options(ui, &mut debug_options, &mut emulator_data.error_policy);
             let default_quirks = emulator_data.machine_model.default_quirks();
             edit_quirks(ui, emulator_data.machine_model.quirks_mut(), default_quirks);
 
@@ -0,0 +1,262 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Ui};
+
+use crate::{
+    frontend::{debug::MemoryJump, machine::Machine, EmulatorData},
+    instruction::InstructionSet,
+};
+
+use super::style;
+
+/// The address, size and layout of the sprites shown by [`sprite_viewer_ui`].
+pub struct SpriteViewerState {
+    /// Where to start, or `None` to follow I.
+    address: Option<u16>,
+    wide: bool,
+    height: u8,
+    /// How many XO-CHIP display planes each sprite draws on, one after another in memory.
+    planes: u8,
+    scale: f32,
+    /// Scroll the chosen sprite into view, after the user picks an address or starts following
+    /// I. Following I doesn't scroll by itself, so the view can still be scrolled around.
+    recenter: bool,
+}
+
+impl Default for SpriteViewerState {
+    fn default() -> Self {
+        Self {
+            address: None,
+            wide: false,
+            height: 8,
+            planes: 1,
+            scale: 4.0,
+            recenter: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SpriteShape {
+    wide: bool,
+    height: u8,
+    planes: u8,
+}
+
+impl SpriteShape {
+    fn width(self) -> usize {
+        if self.wide {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn plane_size(self) -> usize {
+        self.width() / 8 * self.height as usize
+    }
+
+    fn size(self) -> usize {
+        self.plane_size() * self.planes as usize
+    }
+
+    /// The palette index of each pixel of the sprite at the start of `memory`, one row after
+    /// another, combining its planes the way XO-CHIP does. Pixels past the end of memory are
+    /// left blank.
+    fn pixels(self, memory: &[u8]) -> Vec<u8> {
+        let width = self.width();
+        let mut pixels = vec![0; width * self.height as usize];
+        for plane in 0..self.planes as usize {
+            let start = plane * self.plane_size();
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let (y, x) = (i / width, i % width);
+                let byte = memory.get(start + y * width / 8 + x / 8).unwrap_or(&0);
+                if byte & 0x80 >> (x % 8) != 0 {
+                    *pixel |= 1 << plane;
+                }
+            }
+        }
+        pixels
+    }
+}
+
+/// Shows memory as a grid of sprites, starting from I or a chosen address. Clicking a sprite
+/// shows it in the memory view.
+pub fn sprite_viewer_ui(
+    ui: InMut<Ui>,
+    machine: Res<Machine>,
+    emulator_data: Res<EmulatorData>,
+    mut memory_jump: ResMut<MemoryJump>,
+    mut state: Local<SpriteViewerState>,
+) {
+    let ui = ui.0;
+    let multi_plane = machine.instruction_set() >= InstructionSet::XoChip;
+    if !multi_plane {
+        state.planes = 1;
+    }
+
+    ui.horizontal(|ui| {
+        let mut follow_i = state.address.is_none();
+        let follow_changed = ui.checkbox(&mut follow_i, "Follow I").changed();
+        let mut address = state.address.unwrap_or(machine.cpu().i);
+        let address_changed = ui
+            .add_enabled(
+                !follow_i,
+                egui::DragValue::new(&mut address).hexadecimal(4, false, true),
+            )
+            .changed();
+        state.address = (!follow_i).then_some(address);
+        state.recenter |= (follow_changed && follow_i) || address_changed;
+    });
+    ui.horizontal(|ui| {
+        ui.label("Width:");
+        ui.selectable_value(&mut state.wide, false, "8");
+        ui.selectable_value(&mut state.wide, true, "16");
+        ui.label("Height:");
+        ui.add(egui::DragValue::new(&mut state.height).range(1..=16));
+        if ui.button("16x16").clicked() {
+            state.wide = true;
+            state.height = 16;
+        }
+    });
+    ui.horizontal(|ui| {
+        if multi_plane {
+            ui.label("Planes:");
+            for planes in 1..=4 {
+                ui.selectable_value(&mut state.planes, planes, planes.to_string());
+            }
+        }
+        ui.label("Zoom:");
+        ui.add(egui::Slider::new(&mut state.scale, 1.0..=16.0).integer());
+    });
+    ui.separator();
+
+    let shape = SpriteShape {
+        wide: state.wide,
+        height: state.height,
+        planes: state.planes,
+    };
+    let address = state.address.unwrap_or(machine.cpu().i) as usize;
+    let colors = machine
+        .index_colors(&emulator_data.palette)
+        .map(|color| egui::Color32::from_rgb(color[0], color[1], color[2]));
+    let memory = machine.memory();
+
+    // Sprites are lined up with the chosen address, so it's always the start of one
+    let size = shape.size();
+    let first = address % size;
+    let num_sprites = memory.len().saturating_sub(first).div_ceil(size);
+    let sprite_size = egui::vec2(shape.width() as f32, shape.height as f32) * state.scale;
+    let label_height = ui.text_style_height(&egui::TextStyle::Small);
+    let spacing = ui.spacing().item_spacing;
+    let cell_size = sprite_size + egui::vec2(0.0, label_height) + spacing;
+    let columns = ((ui.available_width() + spacing.x) / cell_size.x).max(1.0) as usize;
+    let num_rows = num_sprites.div_ceil(columns);
+
+    let mut scroll_area = egui::ScrollArea::vertical().auto_shrink(false);
+    if std::mem::take(&mut state.recenter) {
+        // Bring the chosen sprite's row into the middle of the view
+        let row = (address - first) / size / columns;
+        let offset = row as f32 * cell_size.y - (ui.available_height() - cell_size.y) / 2.0;
+        scroll_area = scroll_area.vertical_scroll_offset(offset.max(0.0));
+    }
+
+    scroll_area.show_rows(ui, cell_size.y - spacing.y, num_rows, |ui, rows| {
+        for row in rows {
+            ui.horizontal(|ui| {
+                for column in 0..columns {
+                    let index = row * columns + column;
+                    if index >= num_sprites {
+                        break;
+                    }
+                    let start = first + index * size;
+                    let (rect, response) = ui.allocate_exact_size(
+                        sprite_size + egui::vec2(0.0, label_height),
+                        egui::Sense::click(),
+                    );
+                    let sprite_rect = egui::Rect::from_min_size(rect.min, sprite_size);
+                    let painter = ui.painter();
+                    for (i, pixel) in shape.pixels(&memory[start..]).into_iter().enumerate() {
+                        let (y, x) = (i / shape.width(), i % shape.width());
+                        painter.rect_filled(
+                            egui::Rect::from_min_size(
+                                sprite_rect.min + egui::vec2(x as f32, y as f32) * state.scale,
+                                egui::Vec2::splat(state.scale),
+                            ),
+                            egui::Rounding::ZERO,
+                            colors[pixel as usize],
+                        );
+                    }
+                    let stroke_color = if start == address {
+                        style::ACCENT_LIGHT
+                    } else if response.hovered() {
+                        style::FOREGROUND_MID
+                    } else {
+                        style::FOREGROUND_DARK
+                    };
+                    painter.rect_stroke(
+                        sprite_rect,
+                        egui::Rounding::ZERO,
+                        egui::Stroke::new(1.0, stroke_color),
+                    );
+                    painter.text(
+                        rect.center_bottom(),
+                        egui::Align2::CENTER_BOTTOM,
+                        format!("{start:04X}"),
+                        egui::TextStyle::Small.resolve(ui.style()),
+                        style::FOREGROUND_MID,
+                    );
+
+                    if response
+                        .on_hover_text("Click to show in the memory view")
+                        .clicked()
+                    {
+                        memory_jump.0 = Some(start..(start + size).min(memory.len()));
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpriteShape;
+
+    #[test]
+    fn test_sprite_pixels() {
+        let narrow = SpriteShape {
+            wide: false,
+            height: 2,
+            planes: 1,
+        };
+        assert_eq!(
+            narrow.pixels(&[0b1000_0001, 0b0110_0000]),
+            [1, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0, 0, 0, 0, 0]
+        );
+        // Past the end of memory is blank
+        assert_eq!(narrow.pixels(&[0xFF]), [[1; 8], [0; 8]].concat());
+
+        // Each plane follows the last, and sets its own bit of the palette index
+        let planes = SpriteShape {
+            wide: false,
+            height: 1,
+            planes: 3,
+        };
+        assert_eq!(
+            planes.pixels(&[0b1010_0000, 0b1100_0000, 0b0000_0001]),
+            [3, 2, 1, 0, 0, 0, 0, 4]
+        );
+
+        // Wide sprites have two bytes per row
+        let wide = SpriteShape {
+            wide: true,
+            height: 1,
+            planes: 1,
+        };
+        assert_eq!(
+            wide.pixels(&[0x00, 0x01]),
+            [[0; 15].as_slice(), &[1]].concat()
+        );
+        assert_eq!(wide.size(), 2);
+    }
+}
@@ -1,12 +1,20 @@
 use std::fmt::Display;
 
+use bevy::prelude::EventWriter;
 use bevy_egui::egui::{self, reset_button_with, Align, Color32, Response, Ui};
 use image::Rgba;
 
 use crate::{
+    frontend::{
+        machine::{FAST_FORWARD_KEY, FRAME_ADVANCE_KEY, SLOW_MOTION_KEY},
+        palettes::{palette_presets, CustomPalettes},
+        EmulatorData, EmulatorEvent,
+    },
     hardware::KeyEvent,
     model::{DrawWaitSetting, DynamicModel, Quirks},
+    postprocess::{PostProcessMode, MAX_BLEND_FRAMES},
     screen::Palette,
+    upscale::UpscaleFilter,
 };
 
 pub fn model_selector(ui: &mut Ui, model: &mut DynamicModel) -> egui::Response {
@@ -37,8 +45,70 @@ pub fn model_selector(ui: &mut Ui, model: &mut DynamicModel) -> egui::Response {
         .response
 }
 
-pub fn palette_editor(ui: &mut Ui, palette: &mut Palette) -> egui::CollapsingResponse<()> {
+pub fn upscale_selector(ui: &mut Ui, filter: &mut UpscaleFilter) -> egui::Response {
+    egui::ComboBox::from_label("Upscaling filter")
+        .selected_text(filter.to_string())
+        .show_ui(ui, |ui| {
+            for option in UpscaleFilter::ALL {
+                ui.selectable_value(filter, option, option.to_string());
+            }
+        })
+        .response
+}
+
+pub fn speed_controls(
+    ui: &mut Ui,
+    emulator_data: &mut EmulatorData,
+    events: &mut EventWriter<EmulatorEvent>,
+) {
+    ui.horizontal(|ui| {
+        ui.toggle_value(&mut emulator_data.paused, "Pause");
+        if ui
+            .button("Advance Frame")
+            .on_hover_text(format!("{FRAME_ADVANCE_KEY:?}"))
+            .clicked()
+        {
+            emulator_data.paused = true;
+            events.send(EmulatorEvent::FrameAdvance);
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.toggle_value(&mut emulator_data.slow_motion, "Slow Motion")
+            .on_hover_text(format!("{SLOW_MOTION_KEY:?}"));
+        ui.add(
+            egui::Slider::new(&mut emulator_data.slow_motion_speed, 0.05..=0.95)
+                .step_by(0.05)
+                .custom_formatter(|speed, _| format!("{:.0}%", speed * 100.0)),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Fast-forward")
+            .on_hover_text(format!("Hold {FAST_FORWARD_KEY:?}"));
+        let mut uncapped = emulator_data.fast_forward_speed.is_infinite();
+        // The slider would clamp infinity, so only show it with a finite speed
+        if !uncapped {
+            ui.add(
+                egui::Slider::new(&mut emulator_data.fast_forward_speed, 1.5..=16.0)
+                    .logarithmic(true)
+                    .suffix("×"),
+            );
+        }
+        if ui.checkbox(&mut uncapped, "Uncapped").changed() {
+            emulator_data.fast_forward_speed = if uncapped { f64::INFINITY } else { 4.0 };
+        }
+    });
+}
+
+pub fn palette_editor(
+    ui: &mut Ui,
+    palette: &mut Palette,
+    post_process: &mut PostProcessMode,
+    custom_palettes: &mut CustomPalettes,
+    events: &mut EventWriter<EmulatorEvent>,
+) -> egui::CollapsingResponse<()> {
     egui::CollapsingHeader::new("Customize Palette").show(ui, |ui| {
+        palette_presets(ui, palette, custom_palettes, events);
+        ui.separator();
         ui.checkbox(
             &mut palette.use_custom_two_color,
             "Use custom colors for two-color mode",
@@ -62,9 +132,42 @@ pub fn palette_editor(ui: &mut Ui, palette: &mut Palette) -> egui::CollapsingRes
                 }
             }
         });
+        ui.separator();
+        post_process_editor(ui, post_process);
     })
 }
 
+fn post_process_editor(ui: &mut Ui, mode: &mut PostProcessMode) {
+    egui::ComboBox::from_label("Flicker reduction")
+        .selected_text(mode.to_string())
+        .show_ui(ui, |ui| {
+            for preset in [
+                PostProcessMode::None,
+                PostProcessMode::BLEND,
+                PostProcessMode::PHOSPHOR,
+                PostProcessMode::OrLastTwo,
+            ] {
+                let selected = std::mem::discriminant(mode) == std::mem::discriminant(&preset);
+                if ui.selectable_label(selected, preset.to_string()).clicked() && !selected {
+                    *mode = preset;
+                }
+            }
+        });
+    match mode {
+        PostProcessMode::Blend { frames } => {
+            ui.add(egui::Slider::new(frames, 2..=MAX_BLEND_FRAMES).text("Frames"));
+        }
+        PostProcessMode::Phosphor { half_life } => {
+            ui.add(
+                egui::Slider::new(half_life, 0.25..=16.0)
+                    .logarithmic(true)
+                    .text("Half-life (frames)"),
+            );
+        }
+        PostProcessMode::None | PostProcessMode::OrLastTwo => {}
+    }
+}
+
 pub fn color_edit_button(ui: &mut Ui, color: &mut Rgba<u8>) -> Response {
     let mut egui_color =
         Color32::from_rgba_premultiplied(color.0[0], color.0[1], color.0[2], color.0[3]);
@@ -7,12 +7,12 @@ use rand_xoshiro::Xoshiro256PlusPlus;
 use thiserror::Error;
 
 use crate::{
-    frontend::audio::DEFAULT_PATTERN,
-    instruction::{ExecuteInstruction, InstructionSet},
+    instruction::{ExecuteInstruction, InstructionSet, OctoSyntax, Op},
     match_execute,
     model::{self, CosmacVip, DynamicModel, LegacySuperChip, ModernSuperChip, Quirks, XoChip},
     screen::{
-        self, CosmacVipScreen, LegacySuperChipScreen, ModernSuperChipScreen, Palette, XoChipScreen,
+        self, CosmacVipScreen, IndexedFrame, LegacySuperChipScreen, ModernSuperChipScreen, Palette,
+        XoChipScreen,
     },
 };
 
@@ -35,6 +35,67 @@ pub enum Error {
     },
     #[error("an unsupported screen operation was run")]
     UnsupportedScreenOperation(#[from] screen::UnsupportedScreenOperation),
+    /// An error from running an instruction, with the state of the machine when it happened.
+    #[error("{0}")]
+    Runtime(Box<ErrorContext>),
+}
+
+impl Error {
+    /// What went wrong, without the context it happened in.
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Runtime(context) => &context.error,
+            error => error,
+        }
+    }
+
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Runtime(context) => Some(context),
+            _ => None,
+        }
+    }
+}
+
+/// The state of the machine when an instruction failed, for working out why without having to
+/// reproduce it.
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    pub error: Error,
+    pub pc: u16,
+    /// The instruction that failed, unless the PC was outside memory.
+    pub opcode: Option<u16>,
+    /// The instruction in Octo syntax, if it's valid.
+    pub disassembly: Option<String>,
+    pub instruction_set: InstructionSet,
+    pub i: u16,
+    pub sp: u4,
+    /// Return addresses on the stack, innermost first.
+    pub backtrace: Vec<u16>,
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {:#06X}", self.error, self.pc)?;
+        if let Some(opcode) = self.opcode {
+            let disassembly = self.disassembly.as_deref().unwrap_or("????");
+            write!(f, " ({opcode:04X} {disassembly})")?;
+        }
+        write!(
+            f,
+            " [{}, I = {:#06X}, SP = {}",
+            self.instruction_set, self.i, self.sp
+        )?;
+        if !self.backtrace.is_empty() {
+            let backtrace = self
+                .backtrace
+                .iter()
+                .map(|address| format!("{address:#06X}"))
+                .collect::<Vec<_>>();
+            write!(f, ", called from {}", backtrace.join(" < "))?;
+        }
+        write!(f, "]")
+    }
 }
 
 fn format_range(start: u16, offset: usize, inclusive: bool) -> String {
@@ -48,42 +109,36 @@ fn format_range(start: u16, offset: usize, inclusive: bool) -> String {
 
 pub trait Machine: Send + Sync {
     fn event(&mut self, key: u4, event: KeyEvent);
+    fn pressed_keys(&self) -> u16;
+    fn reseed(&mut self, seed: u64);
     fn render_frame(&self, palette: &Palette) -> image::RgbaImage;
-    fn tick_timers(&mut self);
+    fn render_indexed(&self, frame: &mut IndexedFrame, palette: &Palette) -> bool;
+    fn screen_version(&self) -> u64;
+    fn clone_screen(&self) -> Box<dyn screen::Screen>;
+    fn start_frame(&mut self);
+    fn set_frame_rate(&mut self, frame_rate: f64);
+    fn set_timer_rate(&mut self, timer_rate: f64);
     fn disable_vblank(&mut self);
     fn sound_active(&self) -> bool;
     fn pitch(&self) -> u8;
     fn audio_pattern(&self) -> &[u8; 16];
+    fn audio_frame(&self) -> AudioFrame;
     fn memory(&self) -> &[u8];
     fn cpu(&self) -> &Cpu;
     fn quirks(&self) -> &Quirks;
     fn instruction_set(&self) -> InstructionSet;
+    fn set_decode_cache(&mut self, enabled: bool);
+    fn skip_instruction(&mut self);
+    fn set_register(&mut self, register: Register, value: u16);
+    fn push_stack(&mut self, address: u16) -> Result<()>;
+    fn pop_stack(&mut self) -> Result<u16>;
+    fn poke(&mut self, address: u16, bytes: &[u8]) -> Result<()>;
     fn tick(&mut self) -> Result<()>;
-    fn tick_many(&mut self, count: u32, breakpoints: &BTreeSet<u16>) -> Result<bool> {
-        if breakpoints.is_empty() {
-            if count > 0 {
-                self.tick()?;
-                self.disable_vblank();
-            }
-            for _ in 1..count {
-                self.tick()?;
-            }
-        } else {
-            if count > 0 {
-                if breakpoints.contains(&self.cpu().pc) {
-                    return Ok(true);
-                }
-                self.tick()?;
-                self.disable_vblank();
-            }
-            for _ in 1..count {
-                if breakpoints.contains(&self.cpu().pc) {
-                    return Ok(true);
-                }
-                self.tick()?;
-            }
-        }
-        Ok(false)
+    fn tick_many(&mut self, count: u32, breakpoints: &BTreeSet<u16>) -> Result<bool>;
+    fn idle(&self) -> Option<Idle>;
+    fn run_frame(&mut self, ipf: u32) -> Result<bool> {
+        self.start_frame();
+        self.tick_many(ipf, &BTreeSet::new())
     }
 }
 
@@ -101,17 +156,33 @@ where
     Screen: screen::Screen,
 {
     blanket_machine_method!(event(self: &mut Self, key: u4, event: KeyEvent));
+    blanket_machine_method!(pressed_keys(self: &Self) -> u16);
+    blanket_machine_method!(reseed(self: &mut Self, seed: u64));
     blanket_machine_method!(render_frame(self: &Self, palette: &Palette) -> image::RgbaImage);
-    blanket_machine_method!(tick_timers(self: &mut Self));
+    blanket_machine_method!(render_indexed(self: &Self, frame: &mut IndexedFrame, palette: &Palette) -> bool);
+    blanket_machine_method!(screen_version(self: &Self) -> u64);
+    blanket_machine_method!(clone_screen(self: &Self) -> Box<dyn screen::Screen>);
+    blanket_machine_method!(start_frame(self: &mut Self));
+    blanket_machine_method!(set_frame_rate(self: &mut Self, frame_rate: f64));
+    blanket_machine_method!(set_timer_rate(self: &mut Self, timer_rate: f64));
     blanket_machine_method!(disable_vblank(self: &mut Self));
     blanket_machine_method!(sound_active(self: &Self) -> bool);
     blanket_machine_method!(pitch(self: &Self) -> u8);
     blanket_machine_method!(audio_pattern(self: &Self) -> &[u8; 16]);
+    blanket_machine_method!(audio_frame(self: &Self) -> AudioFrame);
     blanket_machine_method!(memory(self: &Self) -> &[u8]);
     blanket_machine_method!(cpu(self: &Self) -> &Cpu);
     blanket_machine_method!(quirks(self: &Self) -> &Quirks);
     blanket_machine_method!(instruction_set(self: &Self) -> InstructionSet);
+    blanket_machine_method!(set_decode_cache(self: &mut Self, enabled: bool));
+    blanket_machine_method!(skip_instruction(self: &mut Self));
+    blanket_machine_method!(set_register(self: &mut Self, register: Register, value: u16));
+    blanket_machine_method!(push_stack(self: &mut Self, address: u16) -> Result<()>);
+    blanket_machine_method!(pop_stack(self: &mut Self) -> Result<u16>);
+    blanket_machine_method!(poke(self: &mut Self, address: u16, bytes: &[u8]) -> Result<()>);
     blanket_machine_method!(tick(self: &mut Self) -> Result<()>);
+    blanket_machine_method!(tick_many(self: &mut Self, count: u32, breakpoints: &BTreeSet<u16>) -> Result<bool>);
+    blanket_machine_method!(idle(self: &Self) -> Option<Idle>);
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -174,21 +245,37 @@ impl DynamicMachine {
 
 impl Machine for DynamicMachine {
     dynamic_machine_method!(event(self: &mut Self, key: u4, event: KeyEvent));
+    dynamic_machine_method!(pressed_keys(self: &Self) -> u16);
+    dynamic_machine_method!(reseed(self: &mut Self, seed: u64));
     dynamic_machine_method!(render_frame(self: &Self, palette: &Palette) -> image::RgbaImage);
-    dynamic_machine_method!(tick_timers(self: &mut Self));
+    dynamic_machine_method!(render_indexed(self: &Self, frame: &mut IndexedFrame, palette: &Palette) -> bool);
+    dynamic_machine_method!(screen_version(self: &Self) -> u64);
+    dynamic_machine_method!(clone_screen(self: &Self) -> Box<dyn screen::Screen>);
+    dynamic_machine_method!(start_frame(self: &mut Self));
+    dynamic_machine_method!(set_frame_rate(self: &mut Self, frame_rate: f64));
+    dynamic_machine_method!(set_timer_rate(self: &mut Self, timer_rate: f64));
     dynamic_machine_method!(disable_vblank(self: &mut Self));
     dynamic_machine_method!(sound_active(self: &Self) -> bool);
     dynamic_machine_method!(pitch(self: &Self) -> u8);
     dynamic_machine_method!(audio_pattern(self: &Self) -> &[u8; 16]);
+    dynamic_machine_method!(audio_frame(self: &Self) -> AudioFrame);
     dynamic_machine_method!(memory(self: &Self) -> &[u8]);
     dynamic_machine_method!(cpu(self: &Self) -> &Cpu);
     dynamic_machine_method!(quirks(self: &Self) -> &Quirks);
     dynamic_machine_method!(instruction_set(self: &Self) -> InstructionSet);
+    dynamic_machine_method!(set_decode_cache(self: &mut Self, enabled: bool));
+    dynamic_machine_method!(skip_instruction(self: &mut Self));
+    dynamic_machine_method!(set_register(self: &mut Self, register: Register, value: u16));
+    dynamic_machine_method!(push_stack(self: &mut Self, address: u16) -> Result<()>);
+    dynamic_machine_method!(pop_stack(self: &mut Self) -> Result<u16>);
+    dynamic_machine_method!(poke(self: &mut Self, address: u16, bytes: &[u8]) -> Result<()>);
     dynamic_machine_method!(tick(self: &mut Self) -> Result<()>);
     dynamic_machine_method!(tick_many(self: &mut Self, count: u32, breakpoints: &BTreeSet<u16>) -> Result<bool>);
+    dynamic_machine_method!(idle(self: &Self) -> Option<Idle>);
+    dynamic_machine_method!(run_frame(self: &mut Self, ipf: u32) -> Result<bool>);
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cpu {
     pub v: [u8; 16],
     pub i: u16,
@@ -199,6 +286,31 @@ pub struct Cpu {
     pub stack: [u16; 16],
 }
 
+/// A register that can be changed from outside the running program, with
+/// [`Machine::set_register`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u4),
+    I,
+    Pc,
+    Dt,
+    St,
+    Sp,
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Register::V(reg) => write!(f, "v{reg:X}"),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::Dt => write!(f, "DT"),
+            Register::St => write!(f, "ST"),
+            Register::Sp => write!(f, "SP"),
+        }
+    }
+}
+
 impl Default for Cpu {
     fn default() -> Self {
         Self {
@@ -250,7 +362,7 @@ impl Cpu {
     }
 
     fn dec_pc(&mut self) {
-        self.pc -= 2;
+        self.pc = self.pc.wrapping_sub(2)
     }
 
     fn arithmetic_op(&mut self, x: u4, y: u4, f: impl FnOnce(u8, u8) -> u8, reset_vf: bool) {
@@ -327,6 +439,117 @@ impl Keypad {
     }
 }
 
+/// The state of the sound hardware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioState {
+    pub pattern: [u8; 16],
+    pub pitch: u8,
+    pub playing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioChange {
+    Pattern([u8; 16]),
+    Pitch(u8),
+    Playing(bool),
+}
+
+/// A change to the sound hardware, timestamped with the number of instructions that had run in
+/// the frame before it happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioEvent {
+    pub cycle: u32,
+    pub change: AudioChange,
+}
+
+/// Everything needed to render the audio for the last frame: the state the sound hardware started
+/// in, what changed and when, how many instructions the frame ran, and the state it ended up in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioFrame {
+    pub start: AudioState,
+    pub events: Vec<AudioEvent>,
+    pub cycles: u32,
+    pub end: AudioState,
+}
+
+/// The rate the delay and sound timers count down at, whatever the frame rate is.
+pub const TIMER_RATE: f64 = 60.0;
+
+/// Works out how many times the timers tick in each frame, so that they count at their own rate
+/// while vblank follows the frame rate. Rates are kept in millihertz, so the ticks come out exact
+/// over any whole number of seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerClock {
+    timer_rate: u64,
+    frame_rate: u64,
+    /// Progress towards the next tick, where `frame_rate` is a whole tick.
+    elapsed: u64,
+}
+
+impl TimerClock {
+    pub fn new(timer_rate: f64, frame_rate: f64) -> Self {
+        let frame_rate = millihertz(frame_rate);
+        Self {
+            timer_rate: millihertz(timer_rate),
+            frame_rate,
+            // The first tick is due straight away
+            elapsed: frame_rate,
+        }
+    }
+
+    pub fn set_timer_rate(&mut self, timer_rate: f64) {
+        self.timer_rate = millihertz(timer_rate);
+    }
+
+    /// Change the frame rate, keeping the progress towards the next tick.
+    pub fn set_frame_rate(&mut self, frame_rate: f64) {
+        let frame_rate = millihertz(frame_rate);
+        self.elapsed = self.elapsed * frame_rate / self.frame_rate;
+        self.frame_rate = frame_rate;
+    }
+
+    /// Start the next frame, returning how many timer ticks have come due since the last one
+    /// started.
+    pub fn next_frame(&mut self) -> u32 {
+        let ticks = self.elapsed / self.frame_rate;
+        self.elapsed = self.elapsed % self.frame_rate + self.timer_rate;
+        ticks as u32
+    }
+}
+
+fn millihertz(rate: f64) -> u64 {
+    (rate * 1000.0).round().max(1.0) as u64
+}
+
+/// Why the machine has nothing to do until the next frame, so the rest of the frame can be
+/// skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idle {
+    /// Jumping to the same instruction forever, which usually means the program has finished.
+    JumpToSelf,
+    /// Going around a loop that hasn't changed anything since the last time, like polling the
+    /// delay timer or the keypad, which only change between frames.
+    Polling,
+    /// Waiting for a key with `Fx0A`.
+    WaitingForKey,
+    /// Waiting for vblank to draw a sprite.
+    WaitingForVblank,
+}
+
+/// The state of the machine the last time it jumped backwards, to tell if a loop is repeating
+/// itself exactly.
+#[derive(Clone, PartialEq, Eq)]
+struct LoopState {
+    cpu: Cpu,
+    vblank: bool,
+    screen_version: u64,
+    effects: u64,
+}
+
+// Audio events are only cleared when a frame starts, so don't let them grow without bound if
+// something is running instructions without ever ending a frame.
+const MAX_AUDIO_EVENTS: usize = 4096;
+
 #[derive(Clone)]
 pub struct Chip8<Model: model::Model, Screen: screen::Screen + ?Sized> {
     model: Model,
@@ -339,6 +562,17 @@ pub struct Chip8<Model: model::Model, Screen: screen::Screen + ?Sized> {
     rpl: [u8; 16],
     pitch: u8,
     audio_pattern: [u8; 16],
+    cycle: u32,
+    audio_start: AudioState,
+    audio_events: Vec<AudioEvent>,
+    timer_clock: TimerClock,
+    idle: Option<Idle>,
+    loop_state: Option<LoopState>,
+    /// Counts changes that the CPU and screen don't show, like memory writes and random numbers,
+    /// so that loops making them are never mistaken for idle ones.
+    effects: u64,
+    /// The decoded instruction at each address, if the cache is enabled.
+    decode_cache: Option<Box<[Op]>>,
 }
 
 impl<Model: model::Model, Screen: screen::Screen + ?Sized> Chip8<Model, Screen> {
@@ -358,6 +592,8 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> Chip8<Model, Screen>
             warn!("ROM is too big to completely load into memory");
             memory[0x200..].copy_from_slice(&rom[..memory_size - 0x200]);
         }
+        let audio_pattern = model.default_audio_pattern();
+        let timer_clock = TimerClock::new(TIMER_RATE, model.default_framerate());
         Self {
             keypad: Default::default(),
             model,
@@ -368,7 +604,19 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> Chip8<Model, Screen>
             vblank: false,
             rpl: [0; 16],
             pitch: 64,
-            audio_pattern: DEFAULT_PATTERN,
+            audio_pattern,
+            cycle: 0,
+            audio_start: AudioState {
+                pattern: audio_pattern,
+                pitch: 64,
+                playing: false,
+            },
+            audio_events: Vec::new(),
+            timer_clock,
+            idle: None,
+            loop_state: None,
+            effects: 0,
+            decode_cache: None,
         }
     }
 
@@ -377,18 +625,65 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> Chip8<Model, Screen>
             .event(key, event, self.model.quirks().key_wait_trigger)
     }
 
+    /// A bitmask of the currently held keys, with bit N set if key N is held.
+    pub fn pressed_keys(&self) -> u16 {
+        self.keypad.keys
+    }
+
+    /// Replace the random number generator used by `Cxnn` with one seeded from `seed`, so that
+    /// runs can be reproduced exactly.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    }
+
     pub fn render_frame(&self, palette: &Palette) -> image::RgbaImage {
         self.screen.to_image(palette)
     }
 
-    pub fn tick_timers(&mut self) {
+    /// Render the screen into `frame`, returning whether it changed. See [`IndexedFrame::update`].
+    pub fn render_indexed(&self, frame: &mut IndexedFrame, palette: &Palette) -> bool {
+        frame.update(self.screen.as_ref(), palette)
+    }
+
+    /// See [`Screen::version`](screen::Screen::version).
+    pub fn screen_version(&self) -> u64 {
+        self.screen.version()
+    }
+
+    pub fn clone_screen(&self) -> Box<dyn screen::Screen> {
+        screen::clone_boxed(self.screen.as_ref())
+    }
+
+    /// Start a new frame, which begins vblank and ticks the timers as many times as they're due.
+    pub fn start_frame(&mut self) {
+        self.cycle = 0;
+        self.audio_start = self.audio_state();
+        self.audio_events.clear();
+        for _ in 0..self.timer_clock.next_frame() {
+            self.tick_timers();
+        }
+        self.vblank = true;
+    }
+
+    /// Set how many frames run per second, which the timers need to keep their own rate.
+    pub fn set_frame_rate(&mut self, frame_rate: f64) {
+        self.timer_clock.set_frame_rate(frame_rate);
+    }
+
+    pub fn set_timer_rate(&mut self, timer_rate: f64) {
+        self.timer_clock.set_timer_rate(timer_rate);
+    }
+
+    fn tick_timers(&mut self) {
         if self.cpu.dt > 0 {
             self.cpu.dt -= 1;
         }
         if self.cpu.st > 0 {
             self.cpu.st -= 1;
+            if self.cpu.st == 0 {
+                self.audio_event(AudioChange::Playing(false));
+            }
         }
-        self.vblank = true;
     }
 
     pub fn disable_vblank(&mut self) {
@@ -407,6 +702,33 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> Chip8<Model, Screen>
         &self.audio_pattern
     }
 
+    pub fn audio_frame(&self) -> AudioFrame {
+        AudioFrame {
+            start: self.audio_start,
+            events: self.audio_events.clone(),
+            cycles: self.cycle,
+            end: self.audio_state(),
+        }
+    }
+
+    fn audio_state(&self) -> AudioState {
+        AudioState {
+            pattern: self.audio_pattern,
+            pitch: self.pitch,
+            playing: self.sound_active(),
+        }
+    }
+
+    fn audio_event(&mut self, change: AudioChange) {
+        self.effects = self.effects.wrapping_add(1);
+        if self.audio_events.len() < MAX_AUDIO_EVENTS {
+            self.audio_events.push(AudioEvent {
+                cycle: self.cycle,
+                change,
+            });
+        }
+    }
+
     pub fn memory(&self) -> &[u8] {
         &self.memory
     }
@@ -423,6 +745,45 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> Chip8<Model, Screen>
         self.model.instruction_set()
     }
 
+    /// Enable or disable caching decoded instructions, so that each word of memory only has to be
+    /// matched against the instruction table once until it's written to.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache =
+            enabled.then(|| vec![Op::Undecoded; self.memory.len()].into_boxed_slice());
+    }
+
+    /// Record that `len` bytes of memory starting at `start` were written, forgetting the decoded
+    /// instructions overlapping them, including the one that starts just before them.
+    fn memory_written(&mut self, start: u16, len: usize) {
+        self.effects = self.effects.wrapping_add(1);
+        if let Some(cache) = &mut self.decode_cache {
+            let end = (start as usize + len).min(cache.len());
+            if let Some(entries) = cache.get_mut(start.saturating_sub(1) as usize..end) {
+                entries.fill(Op::Undecoded);
+            }
+        }
+    }
+
+    /// Why the last call to [`Chip8::tick_many`] stopped early, if it did.
+    pub fn idle(&self) -> Option<Idle> {
+        self.idle
+    }
+
+    /// Called on backward jumps, to notice when a loop has gone around without changing anything.
+    fn check_loop(&mut self) {
+        let state = LoopState {
+            cpu: self.cpu.clone(),
+            vblank: self.vblank,
+            screen_version: self.screen.version(),
+            effects: self.effects,
+        };
+        if self.loop_state.as_ref() == Some(&state) {
+            self.idle = Some(Idle::Polling);
+        } else {
+            self.loop_state = Some(state);
+        }
+    }
+
     fn draw_wait_for_vblank(&self) -> bool {
         self.model
             .quirks()
@@ -454,11 +815,153 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> Chip8<Model, Screen>
         }
     }
 
-    // Returns a boolean specifying whether to exit
-    pub fn tick(&mut self) -> Result<()> {
-        let instruction = self.read_word()?;
+    /// Run up to `count` instructions, returning whether a breakpoint was hit. Stops early if the
+    /// machine goes idle, counting the instructions it skipped as having run.
+    pub fn tick_many(&mut self, count: u32, breakpoints: &BTreeSet<u16>) -> Result<bool> {
+        // Keys can change between calls, which could wake up a loop that looked idle
+        self.idle = None;
+        self.loop_state = None;
+        for i in 0..count {
+            if !breakpoints.is_empty() && breakpoints.contains(&self.cpu.pc) {
+                return Ok(true);
+            }
+            self.tick()?;
+            if i == 0 {
+                self.disable_vblank();
+            }
+            if self.idle.is_some() {
+                self.cycle = self.cycle.wrapping_add(count - i - 1);
+                break;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Move the PC past the next instruction without running it, including the address after a
+    /// long `F000` load.
+    pub fn skip_instruction(&mut self) {
+        let long = self.model.instruction_set() >= InstructionSet::XoChip
+            && self.read_word().is_ok_and(|word| word == 0xF000);
         self.cpu.inc_pc();
-        self.execute(instruction, self.model.instruction_set())
+        if long {
+            self.cpu.inc_pc();
+        }
+    }
+
+    /// Change a register, for debugging. Only as many of the low bits of `value` as fit in the
+    /// register are kept.
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        match register {
+            Register::V(reg) => self.cpu.set_v(reg, value as u8),
+            Register::I => self.cpu.i = value,
+            Register::Pc => self.cpu.pc = value,
+            Register::Dt => self.cpu.dt = value as u8,
+            Register::St => {
+                let was_active = self.sound_active();
+                self.cpu.st = value as u8;
+                if self.sound_active() != was_active {
+                    self.audio_event(AudioChange::Playing(self.sound_active()));
+                }
+            }
+            Register::Sp => self.cpu.sp = u4::new(value as u8 & 0xF),
+        }
+    }
+
+    /// Push a return address onto the stack, as if a subroutine had been called from just before
+    /// it.
+    pub fn push_stack(&mut self, address: u16) -> Result<()> {
+        self.cpu.sp = self
+            .cpu
+            .sp
+            .checked_add(u4::new(1))
+            .ok_or(Error::StackFull)?;
+        self.cpu.stack[self.cpu.sp.value() as usize] = address;
+        Ok(())
+    }
+
+    /// Pop the innermost return address off the stack, without returning to it.
+    pub fn pop_stack(&mut self) -> Result<u16> {
+        let sp = self
+            .cpu
+            .sp
+            .checked_sub(u4::new(1))
+            .ok_or(Error::StackEmpty)?;
+        let address = self.cpu.stack[self.cpu.sp.value() as usize];
+        self.cpu.sp = sp;
+        Ok(address)
+    }
+
+    /// Write `bytes` to memory starting at `address`, for debugging. Nothing is written if they
+    /// don't all fit.
+    pub fn poke(&mut self, address: u16, bytes: &[u8]) -> Result<()> {
+        let memory_size = self.memory.len();
+        let memory = self
+            .memory
+            .get_mut(address as usize..address as usize + bytes.len())
+            .ok_or(Error::InvalidMemoryRange {
+                start: address,
+                offset: bytes.len(),
+                inclusive: false,
+                memory_size,
+            })?;
+        memory.copy_from_slice(bytes);
+        self.memory_written(address, bytes.len());
+        Ok(())
+    }
+
+    /// Run one instruction. If it fails, the PC is left on it, and the error has the machine's
+    /// state in its [`ErrorContext`].
+    pub fn tick(&mut self) -> Result<()> {
+        let pc = self.cpu.pc;
+        let result = self.read_word().and_then(|instruction| {
+            self.cpu.inc_pc();
+            let result = match &mut self.decode_cache {
+                Some(cache) => {
+                    let mut op = cache[pc as usize];
+                    if op == Op::Undecoded {
+                        op = Op::decode(instruction, self.model.instruction_set());
+                        cache[pc as usize] = op;
+                    }
+                    self.execute_op(op, instruction)
+                }
+                None => self.execute(instruction, self.model.instruction_set()),
+            };
+            self.cycle = self.cycle.wrapping_add(1);
+            result
+        });
+        result.map_err(|error| {
+            self.cpu.pc = pc;
+            self.error_context(error)
+        })
+    }
+
+    fn error_context(&self, error: Error) -> Error {
+        if matches!(error, Error::Exit) {
+            return error;
+        }
+        let word = |address: u16| {
+            let bytes = self.memory.get(address as usize..address as usize + 2)?;
+            Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+        let opcode = word(self.cpu.pc);
+        let instruction_set = self.model.instruction_set();
+        Error::Runtime(Box::new(ErrorContext {
+            error,
+            pc: self.cpu.pc,
+            opcode,
+            disassembly: opcode.and_then(|opcode| {
+                OctoSyntax(self.model.quirks(), word(self.cpu.pc.wrapping_add(2)))
+                    .execute(opcode, instruction_set)
+            }),
+            instruction_set,
+            i: self.cpu.i,
+            sp: self.cpu.sp,
+            backtrace: self.cpu.stack[1..=self.cpu.sp.value() as usize]
+                .iter()
+                .rev()
+                .copied()
+                .collect(),
+        }))
     }
 }
 
@@ -507,6 +1010,11 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
             }
         }
         _1nnn => {
+            if nnn == self.cpu.pc.wrapping_sub(2) {
+                self.idle = Some(Idle::JumpToSelf);
+            } else if nnn < self.cpu.pc {
+                self.check_loop();
+            }
             self.cpu.pc = nnn;
         }
         _2nnn => {
@@ -536,6 +1044,7 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
                 mem_slice.copy_from_slice(&self.cpu.v[y_usize..=x_usize]);
                 mem_slice.reverse();
             }
+            self.memory_written(self.cpu.i, x_usize.abs_diff(y_usize) + 1);
         }
         _5xy3 => {
             let x_usize = u8::from(x) as usize;
@@ -641,10 +1150,12 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
         }
         _Cxnn => {
             self.cpu.set_v(x, self.rng.random::<u8>() & nn);
+            self.effects = self.effects.wrapping_add(1);
         }
         _Dxy0 => {
             if self.draw_wait_for_vblank() && !self.vblank {
                 self.cpu.dec_pc();
+                self.idle = Some(Idle::WaitingForVblank);
             } else {
                 let x_val = self.cpu.get_v(x);
                 let y_val = self.cpu.get_v(y);
@@ -657,7 +1168,7 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
                             self.cpu.i,
                             16 * self.screen.num_active_planes(),
                         )?,
-                    ) as u8;
+                    );
                 } else {
                     self.cpu.v[0xF] = self.screen.draw_large_sprite(
                         x_val,
@@ -674,6 +1185,7 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
         _Dxyn => {
             if self.draw_wait_for_vblank() && !self.vblank {
                 self.cpu.dec_pc();
+                self.idle = Some(Idle::WaitingForVblank);
             } else {
                 let x_val = self.cpu.get_v(x);
                 let y_val = self.cpu.get_v(y);
@@ -685,7 +1197,7 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
                         self.cpu.i,
                         n_u8 as usize * self.screen.num_active_planes(),
                     )?,
-                ) as u8;
+                );
             }
         }
         _Ex9E => {
@@ -703,8 +1215,11 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
             self.screen.set_planes(x)?;
         }
         _F002 => {
-            self.audio_pattern
-                .copy_from_slice(mem_slice(&self.memory, self.cpu.i, 16)?);
+            let pattern = mem_slice(&self.memory, self.cpu.i, 16)?;
+            if self.audio_pattern != pattern {
+                self.audio_pattern.copy_from_slice(pattern);
+                self.audio_event(AudioChange::Pattern(self.audio_pattern));
+            }
         }
         _Fx07 => {
             self.cpu.set_v(x, self.cpu.dt);
@@ -714,13 +1229,18 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
                 self.cpu.set_v(x, u8::from(key));
             } else {
                 self.cpu.dec_pc();
+                self.idle = Some(Idle::WaitingForKey);
             }
         }
         _Fx15 => {
             self.cpu.dt = self.cpu.get_v(x);
         }
         _Fx18 => {
+            let was_active = self.sound_active();
             self.cpu.st = self.cpu.get_v(x);
+            if self.sound_active() != was_active {
+                self.audio_event(AudioChange::Playing(self.sound_active()));
+            }
         }
         _Fx1E => {
             self.cpu.i = self.cpu.i.wrapping_add(self.cpu.get_v(x) as u16);
@@ -737,13 +1257,18 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
         _Fx33 => {
             mem_slice_mut(&mut self.memory, self.cpu.i, 3)?
                 .copy_from_slice(&bcd(self.cpu.get_v(x)));
+            self.memory_written(self.cpu.i, 3);
         }
         _Fx3A => {
-            self.pitch = self.cpu.get_v(x);
+            if self.pitch != self.cpu.get_v(x) {
+                self.pitch = self.cpu.get_v(x);
+                self.audio_event(AudioChange::Pitch(self.pitch));
+            }
         }
         _Fx55 => {
             mem_slice_inclusive_mut(&mut self.memory, self.cpu.i, x_u8 as usize)?
                 .copy_from_slice(&self.cpu.v[..=x_u8 as usize]);
+            self.memory_written(self.cpu.i, x_u8 as usize + 1);
             if self.model.quirks().inc_i_on_slice {
                 self.cpu.i = self.cpu.i.wrapping_add(x_u8 as u16).wrapping_add(1);
             }
@@ -761,6 +1286,7 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
         _Fx75 => {
             self.rpl[..=u8::from(x) as usize]
                 .copy_from_slice(&self.cpu.v[..=u8::from(x) as usize]);
+            self.effects = self.effects.wrapping_add(1);
         }
         _Fx85 => {
             self.cpu.v[..=u8::from(x) as usize]
@@ -780,7 +1306,6 @@ impl<Model: model::Model, Screen: screen::Screen + ?Sized> ExecuteInstruction<Re
         _nn: u8,
         _nnn: u16,
     ) -> Result<()> {
-        self.cpu.dec_pc();
         Err(Error::InvalidInstruction(instruction))
     }
 }
@@ -838,3 +1363,192 @@ fn mem_slice_inclusive_mut(memory: &mut [u8], start: u16, offset: usize) -> Resu
         }),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use arbitrary_int::u4;
+
+    use super::{DynamicMachine, Error, Idle, Machine, Register, TimerClock};
+    use crate::model::DynamicModel;
+
+    // Sets the delay timer to 60, then loops forever
+    const DELAY_ROM: &[u8] = &[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04];
+
+    // Sets the delay timer to 2 and polls it until it reaches 0, then loops forever
+    const POLL_ROM: &[u8] = &[
+        0x60, 0x02, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x12, 0x0A,
+    ];
+
+    // Sets vA to 5, then overwrites that instruction with 7A04 (add 4 to vA) and jumps back to it
+    const SELF_MODIFYING_ROM: &[u8] = &[
+        0x6A, 0x05, 0x3A, 0x09, 0x12, 0x08, 0x12, 0x06, 0x60, 0x7A, 0x61, 0x04, 0xA2, 0x00, 0xF1,
+        0x55, 0x12, 0x00,
+    ];
+
+    #[test]
+    fn test_decode_cache_invalidation() {
+        for enabled in [true, false] {
+            let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, SELF_MODIFYING_ROM);
+            machine.set_decode_cache(enabled);
+            for _ in 0..12 {
+                machine.tick().unwrap();
+            }
+            assert_eq!(machine.cpu().v[0xA], 9);
+            assert_eq!(machine.cpu().pc, 0x206);
+        }
+    }
+
+    #[test]
+    fn test_timer_clock() {
+        let mut clock = TimerClock::new(60.0, 64.0);
+        let ticks = (0..64 * 3).map(|_| clock.next_frame()).collect::<Vec<_>>();
+        assert_eq!(ticks.iter().sum::<u32>(), 180);
+        assert!(ticks.iter().all(|ticks| *ticks <= 1));
+
+        let mut clock = TimerClock::new(60.0, 30.0);
+        assert_eq!(clock.next_frame(), 1);
+        assert!((0..30).all(|_| clock.next_frame() == 2));
+
+        clock.set_frame_rate(0.5);
+        assert_eq!(clock.next_frame(), 2);
+        assert_eq!(clock.next_frame(), 120);
+
+        let mut clock = TimerClock::new(60.0, 60.0);
+        assert!((0..1000).all(|_| clock.next_frame() == 1));
+    }
+
+    #[test]
+    fn test_timers_independent_of_frame_rate() {
+        for frame_rate in [60.0, 64.0, 30.0, 120.0] {
+            let mut machine = DynamicMachine::new(DynamicModel::LEGACY_SCHIP, DELAY_ROM);
+            machine.set_frame_rate(frame_rate);
+            machine.run_frame(2).unwrap();
+            assert_eq!(machine.cpu().dt, 60);
+            // One second of frames, less one
+            let frames = frame_rate as u32;
+            for _ in 1..frames {
+                machine.run_frame(1).unwrap();
+            }
+            assert!(machine.cpu().dt > 0, "{frame_rate} FPS");
+            machine.run_frame(1).unwrap();
+            assert_eq!(machine.cpu().dt, 0, "{frame_rate} FPS");
+        }
+    }
+
+    #[test]
+    fn test_idle_detection() {
+        let mut machine = DynamicMachine::new(DynamicModel::COSMAC_VIP, POLL_ROM);
+        for idle in [Idle::Polling, Idle::Polling, Idle::JumpToSelf] {
+            machine.run_frame(1000).unwrap();
+            assert_eq!(machine.idle(), Some(idle));
+            // Skipped instructions still count towards the frame, to keep audio timing right
+            assert_eq!(machine.audio_frame().cycles, 1000);
+        }
+        assert_eq!(machine.cpu().pc, 0x20A);
+
+        let mut machine = DynamicMachine::new(DynamicModel::COSMAC_VIP, &[0xF0, 0x0A, 0x12, 0x00]);
+        machine.run_frame(1000).unwrap();
+        assert_eq!(machine.idle(), Some(Idle::WaitingForKey));
+
+        // Counting changes a register each time around, so it's never idle
+        let mut machine = DynamicMachine::new(DynamicModel::COSMAC_VIP, &[0x70, 0x01, 0x12, 0x00]);
+        machine.run_frame(100).unwrap();
+        assert_eq!(machine.idle(), None);
+        assert_eq!(machine.cpu().v[0], 50);
+    }
+
+    #[test]
+    fn test_edge_cases_return_errors() {
+        // Drawing with every plane disabled
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, &[0xF0, 0x01, 0xD1, 0x25]);
+        machine.run_frame(2).unwrap();
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, &[0xF0, 0x01, 0xD1, 0x20]);
+        machine.run_frame(2).unwrap();
+
+        // An invalid instruction in the last word of memory, where the PC wraps around to 0
+        let mut rom = [0x60, 0x00].repeat(0x7F00);
+        rom[0xFDFE..].copy_from_slice(&[0xF0, 0xFF]);
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, &rom);
+        for _ in 0..0x7EFF {
+            machine.tick().unwrap();
+        }
+        let error = machine.tick().unwrap_err();
+        assert!(matches!(error.kind(), Error::InvalidInstruction(0xF0FF)));
+        assert_eq!(machine.cpu().pc, 0xFFFE);
+
+        // Returning from the top level fails, leaving the PC on the return so it can be skipped
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, &[0x00, 0xEE, 0xF0, 0x00]);
+        let error = machine.tick().unwrap_err();
+        assert!(matches!(error.kind(), Error::StackEmpty));
+        assert_eq!(machine.cpu().pc, 0x200);
+        machine.skip_instruction();
+        assert_eq!(machine.cpu().pc, 0x202);
+        machine.skip_instruction();
+        assert_eq!(machine.cpu().pc, 0x206);
+
+        // A ROM too big to fit in memory is cut short
+        let machine = DynamicMachine::new(DynamicModel::COSMAC_VIP, &[0x12; 0x1000]);
+        assert_eq!(machine.memory().len(), 0x1000);
+    }
+
+    #[test]
+    fn test_error_context() {
+        // Call 202, call 206, then an invalid instruction
+        let rom = [0x22, 0x02, 0x22, 0x06, 0x00, 0x00, 0xA1, 0x23, 0xF0, 0xFF];
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, &rom);
+        for _ in 0..3 {
+            machine.tick().unwrap();
+        }
+        let error = machine.tick().unwrap_err();
+        let context = error.context().unwrap();
+        assert!(matches!(context.error, Error::InvalidInstruction(0xF0FF)));
+        assert_eq!(context.pc, 0x208);
+        assert_eq!(context.opcode, Some(0xF0FF));
+        assert_eq!(context.backtrace, [0x204, 0x202]);
+        assert_eq!(
+            error.to_string(),
+            "invalid instruction '0xF0FF' at 0x0208 (F0FF ????) \
+             [XO-CHIP, I = 0x0123, SP = 2, called from 0x0204 < 0x0202]"
+        );
+
+        // Exiting isn't a failure, so it's left as it is
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, &[0x00, 0xFD]);
+        assert!(matches!(machine.tick(), Err(Error::Exit)));
+    }
+
+    #[test]
+    fn test_debug_edits() {
+        // v0 := 1
+        let mut machine = DynamicMachine::new(DynamicModel::XO_CHIP, &[0x60, 0x01]);
+        machine.set_decode_cache(true);
+        machine.tick().unwrap();
+        assert_eq!(machine.cpu().v[0], 1);
+
+        // Poking memory forgets the cached instruction, so the new one runs
+        machine.poke(0x200, &[0x61, 0x02]).unwrap();
+        assert_eq!(&machine.memory()[0x200..0x202], [0x61, 0x02]);
+        machine.set_register(Register::Pc, 0x200);
+        machine.tick().unwrap();
+        assert_eq!(machine.cpu().v[1], 2);
+        assert!(matches!(
+            machine.poke(0xFFFF, &[0, 0]),
+            Err(Error::InvalidMemoryRange { .. })
+        ));
+        assert_eq!(machine.memory()[0xFFFF], 0);
+
+        machine.set_register(Register::V(u4::new(0xF)), 0x1FF);
+        assert_eq!(machine.cpu().v[0xF], 0xFF);
+        machine.set_register(Register::St, 10);
+        assert!(machine.sound_active());
+
+        for address in 0..15 {
+            machine.push_stack(address).unwrap();
+        }
+        assert!(matches!(machine.push_stack(15), Err(Error::StackFull)));
+        assert_eq!(machine.cpu().sp.value(), 15);
+        assert_eq!(machine.pop_stack().unwrap(), 14);
+        assert_eq!(machine.cpu().sp.value(), 14);
+        machine.set_register(Register::Sp, 0);
+        assert!(matches!(machine.pop_stack(), Err(Error::StackEmpty)));
+    }
+}
@@ -0,0 +1,107 @@
+use thiserror::Error;
+
+use crate::{
+    hardware::{self, DynamicMachine, Idle, Machine},
+    model::{DynamicModel, Model},
+    movie::{Movie, MovieError, MoviePlayer},
+};
+
+#[derive(Error, Debug)]
+pub enum HeadlessError {
+    #[error(transparent)]
+    Movie(#[from] MovieError),
+    #[error("emulator error on frame {frame}: {error}")]
+    Machine { frame: u64, error: hardware::Error },
+}
+
+/// Settings for running a ROM without the frontend.
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub model: DynamicModel,
+    pub ipf: u32,
+    /// How many frames to run. If this isn't set, a movie is run until its last input, and
+    /// anything else for 10 seconds.
+    pub frames: Option<u64>,
+    /// Replay this movie's inputs. Its model and instructions per frame take priority over the
+    /// ones above.
+    pub movie: Option<Movie>,
+    /// Stop once the ROM jumps to itself forever and its sound has finished, since it can't do
+    /// anything else.
+    pub stop_when_finished: bool,
+    /// Cache decoded instructions, as [`Machine::set_decode_cache`] does.
+    pub decode_cache: bool,
+}
+
+impl HeadlessOptions {
+    pub fn new(model: DynamicModel) -> Self {
+        Self {
+            model,
+            ipf: 1000,
+            frames: None,
+            movie: None,
+            stop_when_finished: false,
+            decode_cache: false,
+        }
+    }
+
+    /// The model that's run, which is the movie's if there is one.
+    pub fn model(&self) -> &DynamicModel {
+        self.movie
+            .as_ref()
+            .map_or(&self.model, |movie| &movie.model)
+    }
+
+    /// The frame rate that's emulated, which is the movie's if there is one.
+    pub fn frame_rate(&self) -> f64 {
+        self.movie
+            .as_ref()
+            .map_or_else(|| self.model.default_framerate(), |movie| movie.frame_rate)
+    }
+}
+
+/// Run `rom`, calling `on_frame` after every emulated frame. Stops early if the ROM exits, or
+/// finishes if [`HeadlessOptions::stop_when_finished`] is set.
+pub fn run(
+    rom: &[u8],
+    options: &HeadlessOptions,
+    mut on_frame: impl FnMut(&DynamicMachine),
+) -> Result<(), HeadlessError> {
+    let (mut machine, mut player, mut ipf) = match options.movie.as_ref() {
+        Some(movie) => (
+            movie.new_machine(rom)?,
+            Some(MoviePlayer::new(movie.clone())),
+            movie.ipf,
+        ),
+        None => (
+            DynamicMachine::new(options.model.clone(), rom),
+            None,
+            options.ipf,
+        ),
+    };
+    machine.set_decode_cache(options.decode_cache);
+    let frames = options.frames.unwrap_or_else(|| {
+        options.movie.as_ref().map_or_else(
+            || (options.frame_rate() * 10.0) as u64,
+            |movie| movie.length(),
+        )
+    });
+
+    for frame in 0..frames {
+        if let Some(player) = player.as_mut() {
+            player.apply_frame(&mut machine, &mut ipf);
+        }
+        match machine.run_frame(ipf) {
+            Ok(_) => {}
+            Err(hardware::Error::Exit) => return Ok(()),
+            Err(error) => return Err(HeadlessError::Machine { frame, error }),
+        }
+        on_frame(&machine);
+        if options.stop_when_finished
+            && machine.idle() == Some(Idle::JumpToSelf)
+            && !machine.sound_active()
+        {
+            break;
+        }
+    }
+    Ok(())
+}
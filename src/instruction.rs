@@ -10,6 +10,16 @@ pub enum InstructionSet {
     XoChip,
 }
 
+impl std::fmt::Display for InstructionSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CosmacVip => write!(f, "CHIP-8"),
+            Self::SuperChip => write!(f, "SUPER-CHIP"),
+            Self::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
 #[bitfield(u16)]
 struct RawInstruction {
     #[bits(0..=3, r)]
@@ -40,6 +50,18 @@ struct RawInstruction {
     kk: u8,
 }
 
+/// Split an opcode into the operands that are passed to every `execute_` method.
+#[inline(always)]
+#[allow(clippy::type_complexity)]
+fn operands(opcode: u16) -> (u4, u4, u4, u8, u8, u8, u8, u16) {
+    let [_, y_u8] = ((opcode & 0xF0F0) >> 4).to_be_bytes();
+    let [x_u8, n_u8] = (opcode & 0x0F0F).to_be_bytes();
+    let [x, y, n] = [x_u8, y_u8, n_u8].map(|nibble| unsafe { u4::new_unchecked(nibble) });
+    let nn = (opcode & 0xFF) as u8;
+    let nnn = opcode & 0xFFF;
+    (x, y, n, x_u8, y_u8, n_u8, nn, nnn)
+}
+
 #[macro_export]
 macro_rules! match_execute {
     ($type:ty, $self:ident, $x:ident, $y:ident, $n:ident, $x_u8:ident, $y_u8:ident, $n_u8:ident, $nn:ident, $nnn:ident; $return:expr; $($opcode:ident => $impl:expr)*) => {
@@ -98,11 +120,8 @@ pub trait ExecuteInstruction<T> {
         use InstructionSet::SuperChip as IsSc;
         use InstructionSet::XoChip as IsXc;
 
-        let [disc1, y_u8] = ((opcode & 0xF0F0) >> 4).to_be_bytes();
-        let [x_u8, n_u8] = (opcode & 0x0F0F).to_be_bytes();
-        let [x, y, n] = [x_u8, y_u8, n_u8].map(|nibble| unsafe { u4::new_unchecked(nibble) });
-        let nn = (opcode & 0xFF) as u8;
-        let nnn = opcode & 0xFFF;
+        let disc1 = (opcode >> 12) as u8;
+        let (x, y, n, x_u8, y_u8, n_u8, nn, nnn) = operands(opcode);
 
         match (disc1, x_u8, y_u8, n_u8, instruction_set) {
             (0x10.., _, _, _, _)
@@ -165,6 +184,12 @@ pub trait ExecuteInstruction<T> {
         }
     }
 
+    /// Execute an instruction that was decoded with [`Op::decode`].
+    #[inline(always)]
+    fn execute_op(&mut self, op: Op, opcode: u16) -> T {
+        dispatch_op(self, op, opcode)
+    }
+
     fn execute_0000(&mut self, x: u4, y: u4, n: u4, x_u8: u8, y_u8: u8, n_u8: u8, nn: u8, nnn: u16) -> T;
     fn execute_00Cn(&mut self, x: u4, y: u4, n: u4, x_u8: u8, y_u8: u8, n_u8: u8, nn: u8, nnn: u16) -> T;
     fn execute_00Dn(&mut self, x: u4, y: u4, n: u4, x_u8: u8, y_u8: u8, n_u8: u8, nn: u8, nnn: u16) -> T;
@@ -220,6 +245,64 @@ pub trait ExecuteInstruction<T> {
     fn no_match(&mut self, instruction: u16, x: u4, y: u4, n: u4, x_u8: u8, y_u8: u8, n_u8: u8, nn: u8, nnn: u16) -> T;
 }
 
+macro_rules! decoded_instructions {
+    ($($opcode:ident)*) => {
+        /// An instruction that has already been matched against the table in
+        /// [`ExecuteInstruction::execute`], so it can be run again with
+        /// [`ExecuteInstruction::execute_op`] without matching it again.
+        /// Only the kind of instruction is stored, so the operands still come from the opcode.
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        pub enum Op {
+            /// Not decoded yet, for use in caches.
+            #[default]
+            Undecoded,
+            $($opcode,)*
+            /// An opcode that isn't valid for the instruction set it was decoded for.
+            Invalid,
+        }
+
+        struct Decoder;
+
+        impl ExecuteInstruction<Op> for Decoder {
+            $(
+                ::paste::paste! {
+                    fn [<execute $opcode>](&mut self, _x: u4, _y: u4, _n: u4, _x_u8: u8, _y_u8: u8, _n_u8: u8, _nn: u8, _nnn: u16) -> Op {
+                        Op::$opcode
+                    }
+                }
+            )*
+
+            fn no_match(&mut self, _instruction: u16, _x: u4, _y: u4, _n: u4, _x_u8: u8, _y_u8: u8, _n_u8: u8, _nn: u8, _nnn: u16) -> Op {
+                Op::Invalid
+            }
+        }
+
+        #[inline(always)]
+        fn dispatch_op<T, E: ExecuteInstruction<T> + ?Sized>(executor: &mut E, op: Op, opcode: u16) -> T {
+            let (x, y, n, x_u8, y_u8, n_u8, nn, nnn) = operands(opcode);
+            match op {
+                $(Op::$opcode => ::paste::paste! { executor.[<execute $opcode>](x, y, n, x_u8, y_u8, n_u8, nn, nnn) },)*
+                Op::Undecoded | Op::Invalid => executor.no_match(opcode, x, y, n, x_u8, y_u8, n_u8, nn, nnn),
+            }
+        }
+    };
+}
+
+#[rustfmt::skip]
+decoded_instructions! {
+    _0000 _00Cn _00Dn _00E0 _00EE _00FB _00FC _00FD _00FE _00FF _1nnn _2nnn _3xnn _4xnn _5xy0
+    _5xy2 _5xy3 _6xnn _7xnn _8xy0 _8xy1 _8xy2 _8xy3 _8xy4 _8xy5 _8xy6 _8xy7 _8xyE _9xy0 _Annn
+    _Bnnn _Cxnn _Dxy0 _Dxyn _Ex9E _ExA1 _F000 _Fx01 _F002 _Fx07 _Fx0A _Fx15 _Fx18 _Fx1E _Fx29
+    _Fx30 _Fx33 _Fx3A _Fx55 _Fx65 _Fx75 _Fx85
+}
+
+impl Op {
+    pub fn decode(opcode: u16, instruction_set: InstructionSet) -> Self {
+        Decoder.execute(opcode, instruction_set)
+    }
+}
+
 pub struct OctoSyntax<'a>(pub &'a Quirks, pub Option<u16>);
 
 impl ExecuteInstruction<Option<String>> for OctoSyntax<'_> {
@@ -1,5 +1,10 @@
 pub mod frontend;
 pub mod hardware;
+pub mod headless;
 pub mod instruction;
 pub mod model;
+pub mod movie;
+pub mod postprocess;
+pub mod recording;
 pub mod screen;
+pub mod upscale;
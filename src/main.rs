@@ -1,5 +1,36 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
 use bevy::{prelude::*, winit::WinitSettings};
-use murmur8tion::{model::Model, *};
+use murmur8tion::{
+    frontend::audio::{Chip8Synth, WAV_SPEC},
+    hardware::Machine,
+    headless::HeadlessOptions,
+    model::{DynamicModel, Model},
+    movie::Movie,
+    recording::{AnimationFormat, FrameRecorder},
+    screen::Palette,
+    upscale::UpscaleFilter,
+    *,
+};
+
+const USAGE: &str = "\
+Usage: murmur8tion [COMMAND]
+
+With no command, opens the emulator.
+
+Commands:
+  record-clip <ROM> <OUTPUT> [OPTIONS]  Record an animated GIF (.gif) or APNG (.png)
+  render-audio <ROM> <OUTPUT> [OPTIONS] Render the sound output to a WAV file
+
+Options:
+  --model <MODEL>  cosmac-vip, legacy-schip, modern-schip or xo-chip [default: cosmac-vip]
+  --ipf <N>        Instructions per frame [default: 1000]
+  --frames <N>     Number of frames to run [default: the movie's length, or 10 seconds]
+  --movie <FILE>   Replay the inputs from a movie file
+  --filter <NAME>  Upscale recorded clips with none, scale2x, scale3x, hq2x, hq4x
+                   or xbr2x [default: none]
+  --until-finished Stop early once the ROM jumps to itself forever
+  --decode-cache   Cache decoded instructions instead of decoding each one as it runs";
 
 // fn setup_global_subscriber() -> impl Drop {
 //     use std::{fs::File, io::BufWriter};
@@ -17,6 +48,15 @@ use murmur8tion::{model::Model, *};
 // }
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        if let Err(error) = run_command(&args) {
+            eprintln!("error: {error}\n\n{USAGE}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // let tracing_flame_guard = setup_global_subscriber();
     // puffin::set_scopes_on(true);
 
@@ -41,3 +81,119 @@ fn main() {
         .add_plugins(frontend::emulator_plugin)
         .run();
 }
+
+fn run_command(args: &[String]) -> Result<(), String> {
+    let (command, args) = args.split_first().ok_or("missing command")?;
+    let (paths, options, filter) = parse_options(args)?;
+    match (command.as_str(), paths.as_slice()) {
+        ("help" | "--help" | "-h", _) => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        ("record-clip", [rom, output]) => record_clip(rom, output, &options, filter),
+        ("record-clip", _) => Err("record-clip needs a ROM and an output file".to_owned()),
+        ("render-audio", [rom, output]) => render_audio(rom, output, &options),
+        ("render-audio", _) => Err("render-audio needs a ROM and an output file".to_owned()),
+        _ => Err(format!("unknown command '{command}'")),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<(Vec<&str>, HeadlessOptions, UpscaleFilter), String> {
+    let mut paths = Vec::new();
+    let mut options = HeadlessOptions::new(DynamicModel::default());
+    let mut filter = UpscaleFilter::None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            paths.push(arg.as_str());
+            continue;
+        }
+        match arg.as_str() {
+            "--until-finished" => {
+                options.stop_when_finished = true;
+                continue;
+            }
+            "--decode-cache" => {
+                options.decode_cache = true;
+                continue;
+            }
+            _ => {}
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {arg}"))?;
+        let invalid = || format!("invalid value for {arg}: '{value}'");
+        match arg.as_str() {
+            "--model" => options.model = DynamicModel::from_id(value).ok_or_else(invalid)?,
+            "--ipf" => options.ipf = value.parse().map_err(|_| invalid())?,
+            "--frames" => options.frames = Some(value.parse().map_err(|_| invalid())?),
+            "--movie" => {
+                let text = std::fs::read_to_string(value)
+                    .map_err(|error| format!("error reading {value}: {error}"))?;
+                options.movie =
+                    Some(Movie::parse(&text).map_err(|error| format!("{value}: {error}"))?);
+            }
+            "--filter" => filter = UpscaleFilter::from_id(value).ok_or_else(invalid)?,
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+    Ok((paths, options, filter))
+}
+
+fn read_rom(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|error| format!("error reading {path}: {error}"))
+}
+
+fn record_clip(
+    rom: &str,
+    output: &str,
+    options: &HeadlessOptions,
+    filter: UpscaleFilter,
+) -> Result<(), String> {
+    let format = match Path::new(output).extension().and_then(|ext| ext.to_str()) {
+        Some("gif") => AnimationFormat::Gif,
+        Some("png" | "apng") => AnimationFormat::Apng,
+        _ => return Err("the output file must end in .gif or .png".to_owned()),
+    };
+    let rom = read_rom(rom)?;
+    let palette = Palette::default();
+    let mut recorder = FrameRecorder::new(&palette, options.frame_rate())
+        .with_screen_size(options.model().instruction_set(), filter);
+    headless::run(&rom, options, |machine| {
+        recorder.push_frame(&filter.apply(&machine.render_frame(&palette)))
+    })
+    .map_err(|error| error.to_string())?;
+
+    let file = File::create(output).map_err(|error| format!("error creating {output}: {error}"))?;
+    recorder
+        .encode(format, BufWriter::new(file))
+        .map_err(|error| error.to_string())?;
+    println!("Recorded {} frames to {output}", recorder.length());
+    Ok(())
+}
+
+fn render_audio(rom: &str, output: &str, options: &HeadlessOptions) -> Result<(), String> {
+    let rom = read_rom(rom)?;
+    let timestep = 1.0 / options.frame_rate();
+    let mut synth = Chip8Synth::new();
+    let mut writer = hound::WavWriter::create(output, WAV_SPEC)
+        .map_err(|error| format!("error creating {output}: {error}"))?;
+
+    let mut write_result = Ok(());
+    headless::run(&rom, options, |machine| {
+        if write_result.is_ok() {
+            write_result = synth
+                .generate_frame(machine, timestep)
+                .into_iter()
+                .try_for_each(|sample| writer.write_sample(sample));
+        }
+    })
+    .map_err(|error| error.to_string())?;
+
+    let seconds = writer.duration() as f64 / WAV_SPEC.sample_rate as f64;
+    write_result
+        .and_then(|_| writer.finalize())
+        .map_err(|error| format!("error writing {output}: {error}"))?;
+    println!("Rendered {seconds:.2} seconds of audio to {output}");
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use crate::{
+    frontend::audio::{DEFAULT_PATTERN, VIP_PATTERN},
     hardware::{Chip8, KeyEvent, Machine},
     instruction::InstructionSet,
     screen::{CosmacVipScreen, LegacySuperChipScreen, ModernSuperChipScreen, Screen, XoChipScreen},
@@ -17,6 +18,10 @@ pub trait Model: Send + Sync {
     fn default_framerate(&self) -> f64 {
         60.0
     }
+    /// The beeper waveform before a ROM loads its own, which only XO-CHIP ROMs can do.
+    fn default_audio_pattern(&self) -> [u8; 16] {
+        DEFAULT_PATTERN
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -85,6 +90,11 @@ impl Model for Box<dyn Model> {
     fn default_framerate(&self) -> f64 {
         self.as_ref().default_framerate()
     }
+
+    #[inline(always)]
+    fn default_audio_pattern(&self) -> [u8; 16] {
+        self.as_ref().default_audio_pattern()
+    }
 }
 
 macro_rules! dynamic_model_method {
@@ -131,6 +141,7 @@ impl Model for DynamicModel {
     dynamic_model_method!(instruction_set(self: &Self) -> InstructionSet);
     dynamic_model_method!(quirks(self: &Self) -> &Quirks);
     dynamic_model_method!(default_framerate(self: &Self) -> f64);
+    dynamic_model_method!(default_audio_pattern(self: &Self) -> [u8; 16]);
 }
 
 impl DynamicModel {
@@ -139,6 +150,28 @@ impl DynamicModel {
     pub const MODERN_SCHIP: Self = Self::ModernSuperChip(ModernSuperChip(ModernSuperChip::QUIRKS));
     pub const XO_CHIP: Self = Self::XoChip(XoChip(XoChip::QUIRKS));
 
+    /// A short name for the model, used in movie files and on the command line.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::CosmacVip(_) => "cosmac-vip",
+            Self::LegacySuperChip(_) => "legacy-schip",
+            Self::ModernSuperChip(_) => "modern-schip",
+            Self::XoChip(_) => "xo-chip",
+        }
+    }
+
+    /// Look up a model with its default quirks by its [`id`](Self::id).
+    pub fn from_id(id: &str) -> Option<Self> {
+        [
+            Self::COSMAC_VIP,
+            Self::LEGACY_SCHIP,
+            Self::MODERN_SCHIP,
+            Self::XO_CHIP,
+        ]
+        .into_iter()
+        .find(|model| model.id() == id)
+    }
+
     pub fn quirks_mut(&mut self) -> &mut Quirks {
         match self {
             Self::CosmacVip(CosmacVip(quirks)) => quirks,
@@ -229,6 +262,11 @@ impl Model for CosmacVip {
     fn quirks(&self) -> &Quirks {
         &self.0
     }
+
+    #[inline(always)]
+    fn default_audio_pattern(&self) -> [u8; 16] {
+        VIP_PATTERN
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
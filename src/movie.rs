@@ -0,0 +1,524 @@
+use std::{fmt::Write, str::FromStr};
+
+use arbitrary_int::u4;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use thiserror::Error;
+
+use crate::{
+    hardware::{DynamicMachine, KeyEvent, Machine},
+    model::{DrawWaitSetting, DynamicModel, Model, Quirks},
+};
+
+const MAGIC: &str = "murmur8tion-movie 1";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    #[error("not a movie file")]
+    MissingHeader,
+    #[error("line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("the movie is missing the '{0}' field")]
+    MissingField(&'static str),
+    #[error("the movie's events are not in frame order")]
+    EventsOutOfOrder,
+    #[error("the movie has events after its last frame")]
+    EventsAfterEnd,
+    #[error("the movie was recorded with a different ROM")]
+    RomMismatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieEvent {
+    Input(u4, KeyEvent),
+    SetIpf(u32),
+}
+
+/// A recording of everything needed to replay a run bit-exactly: the ROM it was made with, the
+/// machine configuration, the random seed, every input along with the frame it was applied on,
+/// and how many frames were recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub model: DynamicModel,
+    pub seed: u64,
+    pub ipf: u32,
+    /// The timers count at their own rate, so how many times they tick in each frame depends on
+    /// this. Movies from before it was recorded ran at their model's default frame rate.
+    pub frame_rate: f64,
+    pub events: Vec<(u64, MovieEvent)>,
+    /// The number of frames recorded, which can run past the last event.
+    pub frames: u64,
+}
+
+impl Movie {
+    pub fn new(rom: &[u8], model: DynamicModel, ipf: u32, frame_rate: f64) -> Self {
+        Self {
+            rom_hash: rom_hash(rom),
+            model,
+            seed: Xoshiro256PlusPlus::from_os_rng().random(),
+            ipf,
+            frame_rate,
+            events: Vec::new(),
+            frames: 0,
+        }
+    }
+
+    /// Create a machine in the state the movie starts from.
+    pub fn new_machine(&self, rom: &[u8]) -> Result<DynamicMachine, MovieError> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(MovieError::RomMismatch);
+        }
+        let mut machine = DynamicMachine::new(self.model.clone(), rom);
+        machine.reseed(self.seed);
+        machine.set_frame_rate(self.frame_rate);
+        Ok(machine)
+    }
+
+    /// The number of frames recorded.
+    pub fn length(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn parse(text: &str) -> Result<Self, MovieError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        if lines.next().map(|(_, line)| line) != Some(MAGIC) {
+            return Err(MovieError::MissingHeader);
+        }
+
+        let mut rom_hash = None;
+        let mut model = None;
+        let mut quirks = Vec::new();
+        let mut seed = None;
+        let mut ipf = None;
+        let mut frame_rate = None;
+        let mut frames = None;
+        let mut events = Vec::new();
+        for (line, text) in lines {
+            let error = |message: &str| MovieError::Parse {
+                line,
+                message: message.to_owned(),
+            };
+            let fields = text.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                ["rom", hash] => {
+                    rom_hash =
+                        Some(u64::from_str_radix(hash, 16).map_err(|_| error("invalid ROM hash"))?)
+                }
+                ["model", name] => {
+                    model = Some(DynamicModel::from_id(name).ok_or_else(|| error("unknown model"))?)
+                }
+                ["quirk", name, value] => quirks.push((line, *name, *value)),
+                ["seed", value] => seed = Some(parse_field(value, line)?),
+                ["ipf", value] => ipf = Some(parse_field(value, line)?),
+                ["frame-rate", value] => {
+                    frame_rate = Some(
+                        parse_field(value, line)
+                            .ok()
+                            .filter(|rate: &f64| rate.is_finite() && *rate > 0.0)
+                            .ok_or_else(|| error("invalid frame rate"))?,
+                    )
+                }
+                ["frames", value] => frames = Some(parse_field(value, line)?),
+                ["input", frame, key, event] => {
+                    let key = u8::from_str_radix(key, 16)
+                        .ok()
+                        .filter(|key| *key < 16)
+                        .ok_or_else(|| error("invalid key"))?;
+                    let event = match *event {
+                        "press" => KeyEvent::Press,
+                        "release" => KeyEvent::Release,
+                        _ => return Err(error("invalid key event")),
+                    };
+                    events.push((
+                        parse_field(frame, line)?,
+                        MovieEvent::Input(u4::new(key), event),
+                    ));
+                }
+                ["set-ipf", frame, value] => events.push((
+                    parse_field(frame, line)?,
+                    MovieEvent::SetIpf(parse_field(value, line)?),
+                )),
+                _ => return Err(error("unrecognized line")),
+            }
+        }
+
+        let mut model = model.ok_or(MovieError::MissingField("model"))?;
+        for (line, name, value) in quirks {
+            set_quirk(model.quirks_mut(), name, value).map_err(|message| MovieError::Parse {
+                line,
+                message: message.to_owned(),
+            })?;
+        }
+        if events.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+            return Err(MovieError::EventsOutOfOrder);
+        }
+        let frames = frames.ok_or(MovieError::MissingField("frames"))?;
+        // Events on the frame after the last one were recorded just before stopping, and never
+        // ran, but don't hurt either
+        if events.last().is_some_and(|(frame, _)| *frame > frames) {
+            return Err(MovieError::EventsAfterEnd);
+        }
+
+        Ok(Self {
+            rom_hash: rom_hash.ok_or(MovieError::MissingField("rom"))?,
+            frame_rate: frame_rate.unwrap_or_else(|| model.default_framerate()),
+            model,
+            seed: seed.ok_or(MovieError::MissingField("seed"))?,
+            ipf: ipf.ok_or(MovieError::MissingField("ipf"))?,
+            events,
+            frames,
+        })
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "{MAGIC}").unwrap();
+        writeln!(text, "rom {:016x}", self.rom_hash).unwrap();
+        writeln!(text, "model {}", self.model.id()).unwrap();
+        for (name, value) in quirk_fields(self.model.quirks()) {
+            writeln!(text, "quirk {name} {value}").unwrap();
+        }
+        writeln!(text, "seed {}", self.seed).unwrap();
+        writeln!(text, "ipf {}", self.ipf).unwrap();
+        writeln!(text, "frame-rate {}", self.frame_rate).unwrap();
+        writeln!(text, "frames {}", self.frames).unwrap();
+        for (frame, event) in &self.events {
+            match event {
+                MovieEvent::Input(key, KeyEvent::Press) => {
+                    writeln!(text, "input {frame} {key:X} press")
+                }
+                MovieEvent::Input(key, KeyEvent::Release) => {
+                    writeln!(text, "input {frame} {key:X} release")
+                }
+                MovieEvent::SetIpf(ipf) => writeln!(text, "set-ipf {frame} {ipf}"),
+            }
+            .unwrap();
+        }
+        text
+    }
+}
+
+fn parse_field<T: FromStr>(value: &str, line: usize) -> Result<T, MovieError> {
+    value.parse().map_err(|_| MovieError::Parse {
+        line,
+        message: format!("invalid number '{value}'"),
+    })
+}
+
+fn quirk_fields(quirks: &Quirks) -> [(&'static str, &'static str); 9] {
+    let bool_str = |value: bool| if value { "true" } else { "false" };
+    [
+        (
+            "graceful_exit_on_0000",
+            bool_str(quirks.graceful_exit_on_0000),
+        ),
+        ("bitshift_use_y", bool_str(quirks.bitshift_use_y)),
+        (
+            "key_wait_trigger",
+            match quirks.key_wait_trigger {
+                KeyEvent::Press => "press",
+                KeyEvent::Release => "release",
+            },
+        ),
+        ("inc_i_on_slice", bool_str(quirks.inc_i_on_slice)),
+        ("bitwise_reset_flag", bool_str(quirks.bitwise_reset_flag)),
+        (
+            "draw_wait_for_vblank",
+            match quirks.draw_wait_for_vblank {
+                DrawWaitSetting::Always => "always",
+                DrawWaitSetting::LoresOnly => "lores-only",
+                DrawWaitSetting::Never => "never",
+            },
+        ),
+        (
+            "clear_screen_on_mode_switch",
+            bool_str(quirks.clear_screen_on_mode_switch),
+        ),
+        ("jump_v0_use_vx", bool_str(quirks.jump_v0_use_vx)),
+        (
+            "lores_draw_large_as_small",
+            bool_str(quirks.lores_draw_large_as_small),
+        ),
+    ]
+}
+
+fn set_quirk(quirks: &mut Quirks, name: &str, value: &str) -> Result<(), &'static str> {
+    let flag = match name {
+        "graceful_exit_on_0000" => &mut quirks.graceful_exit_on_0000,
+        "bitshift_use_y" => &mut quirks.bitshift_use_y,
+        "inc_i_on_slice" => &mut quirks.inc_i_on_slice,
+        "bitwise_reset_flag" => &mut quirks.bitwise_reset_flag,
+        "clear_screen_on_mode_switch" => &mut quirks.clear_screen_on_mode_switch,
+        "jump_v0_use_vx" => &mut quirks.jump_v0_use_vx,
+        "lores_draw_large_as_small" => &mut quirks.lores_draw_large_as_small,
+        "key_wait_trigger" => {
+            quirks.key_wait_trigger = match value {
+                "press" => KeyEvent::Press,
+                "release" => KeyEvent::Release,
+                _ => return Err("invalid key event"),
+            };
+            return Ok(());
+        }
+        "draw_wait_for_vblank" => {
+            quirks.draw_wait_for_vblank = match value {
+                "always" => DrawWaitSetting::Always,
+                "lores-only" => DrawWaitSetting::LoresOnly,
+                "never" => DrawWaitSetting::Never,
+                _ => return Err("invalid draw wait setting"),
+            };
+            return Ok(());
+        }
+        _ => return Err("unknown quirk"),
+    };
+    *flag = match value {
+        "true" => true,
+        "false" => false,
+        _ => return Err("expected true or false"),
+    };
+    Ok(())
+}
+
+/// A 64-bit FNV-1a hash, used to check that a movie is played back with the ROM it was recorded
+/// with.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Records events into a movie. Call [`MovieRecorder::next_frame`] once for every frame that is
+/// actually emulated, so that frame numbers line up with playback.
+#[derive(Debug, Clone)]
+pub struct MovieRecorder {
+    movie: Movie,
+    frame: u64,
+}
+
+impl MovieRecorder {
+    pub fn new(movie: Movie) -> Self {
+        Self { movie, frame: 0 }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn record(&mut self, event: MovieEvent) {
+        self.movie.events.push((self.frame, event));
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub fn finish(mut self) -> Movie {
+        self.movie.frames = self.frame;
+        self.movie
+    }
+}
+
+/// Plays a movie back, one frame at a time.
+#[derive(Debug, Clone)]
+pub struct MoviePlayer {
+    movie: Movie,
+    position: usize,
+    frame: u64,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        Self {
+            movie,
+            position: 0,
+            frame: 0,
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    /// Apply the events recorded for the current frame to `machine`, updating `ipf` if it was
+    /// changed, then move on to the next frame.
+    pub fn apply_frame(&mut self, machine: &mut impl Machine, ipf: &mut u32) {
+        while let Some((_, event)) = self
+            .movie
+            .events
+            .get(self.position)
+            .filter(|(frame, _)| *frame == self.frame)
+        {
+            match *event {
+                MovieEvent::Input(key, event) => machine.event(key, event),
+                MovieEvent::SetIpf(new_ipf) => *ipf = new_ipf,
+            }
+            self.position += 1;
+        }
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arbitrary_int::u4;
+
+    use super::{rom_hash, Movie, MovieError, MovieEvent, MoviePlayer, MovieRecorder};
+    use crate::{
+        hardware::{KeyEvent, Machine},
+        model::{DrawWaitSetting, DynamicModel},
+    };
+
+    // Waits for a key, then stores a random byte in v1 and loops forever
+    const RANDOM_ROM: &[u8] = &[0xF0, 0x0A, 0xC1, 0xFF, 0x12, 0x04];
+
+    #[test]
+    fn test_movie_round_trip() {
+        let mut model = DynamicModel::XO_CHIP;
+        model.quirks_mut().draw_wait_for_vblank = DrawWaitSetting::LoresOnly;
+        model.quirks_mut().bitshift_use_y = true;
+        let mut recorder = MovieRecorder::new(Movie::new(RANDOM_ROM, model, 500, 59.94));
+        recorder.next_frame();
+        recorder.record(MovieEvent::Input(u4::new(0xA), KeyEvent::Press));
+        recorder.record(MovieEvent::SetIpf(20));
+        recorder.next_frame();
+        recorder.record(MovieEvent::Input(u4::new(0xA), KeyEvent::Release));
+        let movie = recorder.finish();
+
+        assert_eq!(Movie::parse(&movie.serialize()), Ok(movie));
+    }
+
+    #[test]
+    fn test_movie_replay() {
+        let movie = Movie {
+            rom_hash: rom_hash(RANDOM_ROM),
+            model: DynamicModel::COSMAC_VIP,
+            seed: 1234,
+            ipf: 100,
+            frame_rate: 60.0,
+            events: vec![
+                (3, MovieEvent::Input(u4::new(0x5), KeyEvent::Press)),
+                (4, MovieEvent::Input(u4::new(0x5), KeyEvent::Release)),
+            ],
+            frames: 10,
+        };
+
+        let mut machine = movie.new_machine(RANDOM_ROM).unwrap();
+        for frame in 0..10 {
+            match frame {
+                3 => machine.event(u4::new(0x5), KeyEvent::Press),
+                4 => machine.event(u4::new(0x5), KeyEvent::Release),
+                _ => {}
+            }
+            machine.run_frame(movie.ipf).unwrap();
+        }
+
+        let mut replay = movie.new_machine(RANDOM_ROM).unwrap();
+        let mut player = MoviePlayer::new(movie.clone());
+        let mut ipf = movie.ipf;
+        while !player.finished() {
+            player.apply_frame(&mut replay, &mut ipf);
+            replay.run_frame(ipf).unwrap();
+        }
+
+        assert_eq!(player.frame(), 10);
+        assert_eq!(replay.cpu().v, machine.cpu().v);
+        assert_eq!(replay.cpu().pc, machine.cpu().pc);
+        assert!(movie.new_machine(&[0x12, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_recorded_length() {
+        // Record the way the machine thread does: inputs arrive between frames, and the recorder
+        // moves on to the next frame just before it's run
+        let movie = Movie::new(RANDOM_ROM, DynamicModel::COSMAC_VIP, 100, 60.0);
+        let mut machine = movie.new_machine(RANDOM_ROM).unwrap();
+        let mut recorder = MovieRecorder::new(movie);
+        for frame in 0..20 {
+            let event = match frame {
+                2 => Some(KeyEvent::Press),
+                3 => Some(KeyEvent::Release),
+                _ => None,
+            };
+            if let Some(event) = event {
+                recorder.record(MovieEvent::Input(u4::new(0x5), event));
+                machine.event(u4::new(0x5), event);
+            }
+            recorder.next_frame();
+            machine.run_frame(100).unwrap();
+        }
+        let movie = recorder.finish();
+
+        // The idle frames after the last input are part of the movie too
+        assert_eq!(movie.length(), 20);
+        let movie = Movie::parse(&movie.serialize()).unwrap();
+        assert_eq!(movie.length(), 20);
+
+        let mut replay = movie.new_machine(RANDOM_ROM).unwrap();
+        let mut player = MoviePlayer::new(movie.clone());
+        let mut ipf = movie.ipf;
+        let mut frames = 0;
+        while !player.finished() {
+            player.apply_frame(&mut replay, &mut ipf);
+            replay.run_frame(ipf).unwrap();
+            frames += 1;
+        }
+        assert_eq!(frames, 20);
+        assert_eq!(replay.cpu().v, machine.cpu().v);
+        assert_eq!(replay.cpu().pc, machine.cpu().pc);
+    }
+
+    #[test]
+    fn test_movie_end_frame() {
+        let mut recorder =
+            MovieRecorder::new(Movie::new(RANDOM_ROM, DynamicModel::XO_CHIP, 500, 60.0));
+        recorder.next_frame();
+        let text = recorder.finish().serialize();
+        assert_eq!(
+            Movie::parse(&text.replace("frames 1\n", "")),
+            Err(MovieError::MissingField("frames"))
+        );
+        assert_eq!(
+            Movie::parse(&format!("{text}input 2 A press\n")),
+            Err(MovieError::EventsAfterEnd)
+        );
+    }
+
+    #[test]
+    fn test_movie_frame_rate() {
+        // Sets the delay timer to 60, then loops forever
+        let rom = &[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04];
+        let movie = Movie::new(rom, DynamicModel::LEGACY_SCHIP, 100, 30.0);
+        let text = movie.serialize();
+        assert_eq!(Movie::parse(&text), Ok(movie.clone()));
+
+        // The timers tick twice a frame at 30 FPS
+        let mut machine = movie.new_machine(rom).unwrap();
+        for _ in 0..11 {
+            machine.run_frame(movie.ipf).unwrap();
+        }
+        assert_eq!(machine.cpu().dt, 40);
+
+        // Older movies ran at the model's frame rate
+        let movie = Movie::parse(&text.replace("frame-rate 30\n", "")).unwrap();
+        assert_eq!(movie.frame_rate, 64.0);
+        assert_eq!(
+            Movie::parse(&text.replace("frame-rate 30", "frame-rate 0")),
+            Err(MovieError::Parse {
+                line: 15,
+                message: "invalid frame rate".to_owned()
+            })
+        );
+    }
+}
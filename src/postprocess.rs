@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+
+use image::{Rgba, RgbaImage};
+
+pub const MAX_BLEND_FRAMES: usize = 8;
+
+/// Ways of smoothing out the flicker caused by sprites being erased and redrawn every frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PostProcessMode {
+    #[default]
+    None,
+    /// Average each pixel over the last `frames` frames.
+    Blend { frames: usize },
+    /// Pixels light up immediately, then fade back to the background color, losing half their
+    /// brightness every `half_life` frames.
+    Phosphor { half_life: f32 },
+    /// Show every pixel that was lit in either of the last two frames.
+    OrLastTwo,
+}
+
+impl PostProcessMode {
+    pub const BLEND: Self = Self::Blend { frames: 3 };
+    pub const PHOSPHOR: Self = Self::Phosphor { half_life: 2.0 };
+}
+
+impl std::fmt::Display for PostProcessMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostProcessMode::None => write!(f, "None"),
+            PostProcessMode::Blend { .. } => write!(f, "Frame blending"),
+            PostProcessMode::Phosphor { .. } => write!(f, "Phosphor decay"),
+            PostProcessMode::OrLastTwo => write!(f, "OR last two frames"),
+        }
+    }
+}
+
+/// Applies a [`PostProcessMode`] to a sequence of frames. Each frame should be passed to
+/// [`PostProcessor::process`] exactly once, in order, since most modes depend on earlier frames.
+#[derive(Debug, Clone, Default)]
+pub struct PostProcessor {
+    mode: PostProcessMode,
+    history: VecDeque<RgbaImage>,
+    /// The current brightness of each pixel's color channels, in phosphor mode.
+    phosphor: Vec<[f32; 4]>,
+}
+
+impl PostProcessor {
+    pub fn new(mode: PostProcessMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn mode(&self) -> PostProcessMode {
+        self.mode
+    }
+
+    /// Change the mode, forgetting all previous frames if it's different.
+    pub fn set_mode(&mut self, mode: PostProcessMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.reset();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.phosphor.clear();
+    }
+
+    /// Process the next frame. `background` is the color of unlit pixels.
+    pub fn process(&mut self, frame: RgbaImage, background: Rgba<u8>) -> RgbaImage {
+        // Switching between lores and hires makes the previous frames meaningless
+        if self
+            .history
+            .back()
+            .is_some_and(|last| last.dimensions() != frame.dimensions())
+        {
+            self.reset();
+        }
+
+        match self.mode {
+            PostProcessMode::None => frame,
+            PostProcessMode::Blend { frames } => {
+                self.blend(frame, frames.clamp(1, MAX_BLEND_FRAMES))
+            }
+            PostProcessMode::Phosphor { half_life } => self.phosphor(frame, background, half_life),
+            PostProcessMode::OrLastTwo => self.or_last_two(frame, background),
+        }
+    }
+
+    fn remember(&mut self, frame: RgbaImage, frames: usize) {
+        self.history.push_back(frame);
+        while self.history.len() > frames {
+            self.history.pop_front();
+        }
+    }
+
+    fn blend(&mut self, frame: RgbaImage, frames: usize) -> RgbaImage {
+        let mut output = RgbaImage::new(frame.width(), frame.height());
+        self.remember(frame, frames);
+        let count = self.history.len() as u32;
+        for (i, pixel) in output.pixels_mut().enumerate() {
+            let mut sum = [0u32; 4];
+            for frame in &self.history {
+                for (total, channel) in sum.iter_mut().zip(frame.as_raw()[i * 4..].iter()) {
+                    *total += *channel as u32;
+                }
+            }
+            *pixel = Rgba(sum.map(|total| ((total + count / 2) / count) as u8));
+        }
+        output
+    }
+
+    fn phosphor(&mut self, frame: RgbaImage, background: Rgba<u8>, half_life: f32) -> RgbaImage {
+        let decay = 0.5f32.powf(1.0 / half_life.max(0.01));
+        let background_brightness = background.0.map(|channel| channel as f32);
+        if self.phosphor.len() != frame.pixels().len() || self.history.is_empty() {
+            self.phosphor = frame
+                .pixels()
+                .map(|pixel| pixel.0.map(|channel| channel as f32))
+                .collect();
+        }
+
+        let mut output = RgbaImage::new(frame.width(), frame.height());
+        for ((pixel, brightness), output) in frame
+            .pixels()
+            .zip(&mut self.phosphor)
+            .zip(output.pixels_mut())
+        {
+            if *pixel != background {
+                *brightness = pixel.0.map(|channel| channel as f32);
+            } else {
+                for (channel, background) in brightness.iter_mut().zip(background_brightness) {
+                    *channel = background + (*channel - background) * decay;
+                }
+            }
+            *output = Rgba(brightness.map(|channel| channel.round() as u8));
+        }
+        // Only kept to notice when the frame size changes
+        self.remember(frame, 1);
+        output
+    }
+
+    fn or_last_two(&mut self, frame: RgbaImage, background: Rgba<u8>) -> RgbaImage {
+        let mut output = frame.clone();
+        if let Some(previous) = self.history.back() {
+            for (pixel, previous) in output.pixels_mut().zip(previous.pixels()) {
+                if *pixel == background {
+                    *pixel = *previous;
+                }
+            }
+        }
+        self.remember(frame, 1);
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::{PostProcessMode, PostProcessor};
+
+    const OFF: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const ON: Rgba<u8> = Rgba([200, 100, 40, 255]);
+
+    fn frame(lit: &[(u32, u32)]) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(4, 2, OFF);
+        for &(x, y) in lit {
+            image.put_pixel(x, y, ON);
+        }
+        image
+    }
+
+    #[test]
+    fn test_no_post_processing() {
+        let mut processor = PostProcessor::default();
+        assert_eq!(processor.process(frame(&[(1, 1)]), OFF), frame(&[(1, 1)]));
+        assert_eq!(processor.process(frame(&[]), OFF), frame(&[]));
+    }
+
+    #[test]
+    fn test_blend() {
+        let mut processor = PostProcessor::new(PostProcessMode::Blend { frames: 2 });
+        assert_eq!(processor.process(frame(&[(0, 0)]), OFF), frame(&[(0, 0)]));
+        let output = processor.process(frame(&[(1, 0)]), OFF);
+        assert_eq!(*output.get_pixel(0, 0), Rgba([100, 50, 20, 255]));
+        assert_eq!(*output.get_pixel(1, 0), Rgba([100, 50, 20, 255]));
+        assert_eq!(*output.get_pixel(2, 0), OFF);
+        // The first frame has dropped out of the blend by now
+        let output = processor.process(frame(&[(1, 0)]), OFF);
+        assert_eq!(*output.get_pixel(0, 0), OFF);
+        assert_eq!(*output.get_pixel(1, 0), ON);
+    }
+
+    #[test]
+    fn test_phosphor() {
+        let mut processor = PostProcessor::new(PostProcessMode::Phosphor { half_life: 2.0 });
+        processor.process(frame(&[(0, 0)]), OFF);
+        let output = processor.process(frame(&[(1, 0)]), OFF);
+        assert_eq!(*output.get_pixel(1, 0), ON);
+        assert_eq!(*output.get_pixel(0, 0), Rgba([141, 71, 28, 255]));
+        let output = processor.process(frame(&[(1, 0)]), OFF);
+        assert_eq!(*output.get_pixel(0, 0), Rgba([100, 50, 20, 255]));
+        for _ in 0..30 {
+            processor.process(frame(&[]), OFF);
+        }
+        assert_eq!(processor.process(frame(&[]), OFF), frame(&[]));
+    }
+
+    #[test]
+    fn test_or_last_two() {
+        let mut processor = PostProcessor::new(PostProcessMode::OrLastTwo);
+        processor.process(frame(&[(0, 0)]), OFF);
+        let output = processor.process(frame(&[(1, 0)]), OFF);
+        assert_eq!(output, frame(&[(0, 0), (1, 0)]));
+        let output = processor.process(frame(&[]), OFF);
+        assert_eq!(output, frame(&[(1, 0)]));
+
+        // A different resolution starts over
+        let output = processor.process(RgbaImage::from_pixel(8, 4, OFF), OFF);
+        assert_eq!(output, RgbaImage::from_pixel(8, 4, OFF));
+    }
+}
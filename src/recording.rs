@@ -0,0 +1,367 @@
+use std::{borrow::Cow, io::Write};
+
+use image::{Rgba, RgbaImage};
+use thiserror::Error;
+
+use crate::{instruction::InstructionSet, screen::Palette, upscale::UpscaleFilter};
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("no frames were recorded")]
+    Empty,
+    #[error("error encoding GIF: {0}")]
+    Gif(#[from] gif::EncodingError),
+    #[error("error encoding APNG: {0}")]
+    Png(#[from] png::EncodingError),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+        }
+    }
+}
+
+impl std::fmt::Display for AnimationFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnimationFormat::Gif => write!(f, "GIF"),
+            AnimationFormat::Apng => write!(f, "APNG"),
+        }
+    }
+}
+
+/// Browsers and most other viewers show GIF frames with delays of 0 or 1 centiseconds for about
+/// 10 centiseconds instead, which would play fast games several times too slowly.
+const MIN_GIF_DELAY: u64 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordedFrame {
+    width: u32,
+    height: u32,
+    indices: Vec<u8>,
+    /// How many emulated frames this frame was shown for.
+    length: u32,
+}
+
+/// Collects emulated frames as palette indices, merging runs of identical frames, so that they can
+/// be encoded as an animation afterwards. The animation is as large as the largest frame, and
+/// smaller frames are scaled up to fill it.
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    palette: Vec<Rgba<u8>>,
+    frames: Vec<RecordedFrame>,
+    width: u32,
+    height: u32,
+    frame_rate: f64,
+}
+
+impl FrameRecorder {
+    pub fn new(palette: &Palette, frame_rate: f64) -> Self {
+        let mut colors = Vec::with_capacity(18);
+        for color in palette.two_color.iter().chain(&palette.sixteen_color) {
+            if !colors.contains(color) {
+                colors.push(*color);
+            }
+        }
+        Self {
+            palette: colors,
+            frames: Vec::new(),
+            width: 0,
+            height: 0,
+            frame_rate,
+        }
+    }
+
+    /// Make the animation at least as large as `instruction_set`'s hires screen after `filter`,
+    /// so that a clip that starts in lores mode doesn't shrink to fit.
+    pub fn with_screen_size(
+        mut self,
+        instruction_set: InstructionSet,
+        filter: UpscaleFilter,
+    ) -> Self {
+        let (width, height) = if instruction_set >= InstructionSet::SuperChip {
+            (128, 64)
+        } else {
+            (64, 32)
+        };
+        self.width = self.width.max(width * filter.factor());
+        self.height = self.height.max(height * filter.factor());
+        self
+    }
+
+    /// The number of emulated frames recorded so far.
+    pub fn length(&self) -> u32 {
+        self.frames.iter().map(|frame| frame.length).sum()
+    }
+
+    pub fn push_frame(&mut self, frame: &RgbaImage) {
+        self.width = self.width.max(frame.width());
+        self.height = self.height.max(frame.height());
+        let frame = RecordedFrame {
+            width: frame.width(),
+            height: frame.height(),
+            indices: frame
+                .pixels()
+                .map(|pixel| self.color_index(*pixel))
+                .collect(),
+            length: 1,
+        };
+        match self.frames.last_mut() {
+            Some(last) if last.width == frame.width && last.indices == frame.indices => {
+                last.length += 1
+            }
+            _ => self.frames.push(frame),
+        }
+    }
+
+    /// A frame's indices at the size of the animation.
+    fn scaled_indices<'a>(&self, frame: &'a RecordedFrame) -> Cow<'a, [u8]> {
+        if (frame.width, frame.height) == (self.width, self.height) {
+            return Cow::Borrowed(&frame.indices);
+        }
+        let mut indices = Vec::with_capacity((self.width * self.height) as usize);
+        for y in 0..self.height {
+            let row = (y * frame.height / self.height * frame.width) as usize;
+            indices.extend(
+                (0..self.width)
+                    .map(|x| frame.indices[row + (x * frame.width / self.width) as usize]),
+            );
+        }
+        Cow::Owned(indices)
+    }
+
+    /// The frames to encode and how long to show each one for, in `1 / units_per_second` of a
+    /// second. Each delay is rounded to fit the animation's time units, so keep track of the total
+    /// time to avoid drifting. Frames that would be shown for less than `min_delay` are stretched
+    /// over the ones after them, which are dropped.
+    fn timed_frames(&self, units_per_second: f64, min_delay: u64) -> Vec<(&RecordedFrame, u16)> {
+        let units =
+            |frames: u32| (frames as f64 * units_per_second / self.frame_rate).round() as u64;
+        let mut timed = Vec::new();
+        let mut elapsed_frames = 0;
+        let mut elapsed_units = 0;
+        let mut shown = None;
+        for frame in &self.frames {
+            let shown_frame = *shown.get_or_insert(frame);
+            elapsed_frames += frame.length;
+            let delay = units(elapsed_frames) - elapsed_units;
+            if delay >= min_delay {
+                timed.push((shown_frame, delay.min(u16::MAX as u64) as u16));
+                elapsed_units += delay;
+                shown = None;
+            }
+        }
+        // The end is too short to be a frame of its own, so show the last frame for longer
+        if let Some(frame) = shown {
+            let delay = (units(elapsed_frames) - elapsed_units).min(u16::MAX as u64) as u16;
+            match timed.last_mut() {
+                Some((_, last)) => *last = last.saturating_add(delay),
+                None => timed.push((frame, delay)),
+            }
+        }
+        timed
+    }
+
+    fn color_index(&mut self, color: Rgba<u8>) -> u8 {
+        match self.palette.iter().position(|c| *c == color) {
+            Some(index) => index as u8,
+            // Upscaling filters can blend the palette's colors, so add any new ones, and only
+            // approximate them once there's no more room
+            None if self.palette.len() < 256 => {
+                self.palette.push(color);
+                (self.palette.len() - 1) as u8
+            }
+            None => self.nearest_color(color),
+        }
+    }
+
+    fn nearest_color(&self, color: Rgba<u8>) -> u8 {
+        let distance = |other: &Rgba<u8>| -> u32 {
+            (0..3)
+                .map(|i| (color[i] as i32 - other[i] as i32).unsigned_abs().pow(2))
+                .sum()
+        };
+        self.palette
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, other)| distance(other))
+            .map_or(0, |(index, _)| index as u8)
+    }
+
+    pub fn encode(
+        &self,
+        format: AnimationFormat,
+        writer: impl Write,
+    ) -> Result<(), RecordingError> {
+        if self.frames.is_empty() {
+            return Err(RecordingError::Empty);
+        }
+        match format {
+            AnimationFormat::Gif => self.encode_gif(writer),
+            AnimationFormat::Apng => self.encode_apng(writer),
+        }
+    }
+
+    fn encode_gif(&self, writer: impl Write) -> Result<(), RecordingError> {
+        let palette = self
+            .palette
+            .iter()
+            .flat_map(|color| [color[0], color[1], color[2]])
+            .collect::<Vec<_>>();
+        let mut encoder =
+            gif::Encoder::new(writer, self.width as u16, self.height as u16, &palette)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        // GIF delays are in centiseconds, and most viewers slow down anything shorter than two
+        for (frame, delay) in self.timed_frames(100.0, MIN_GIF_DELAY) {
+            encoder.write_frame(&gif::Frame {
+                width: self.width as u16,
+                height: self.height as u16,
+                delay,
+                buffer: self.scaled_indices(frame),
+                ..Default::default()
+            })?;
+        }
+        Ok(())
+    }
+
+    fn encode_apng(&self, writer: impl Write) -> Result<(), RecordingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(
+            self.palette
+                .iter()
+                .flat_map(|color| [color[0], color[1], color[2]])
+                .collect::<Vec<_>>(),
+        );
+        let frames = self.timed_frames(1000.0, 1);
+        encoder.set_animated(frames.len() as u32, 0)?;
+        let mut writer = encoder.write_header()?;
+        for (frame, delay) in frames {
+            writer.set_frame_delay(delay, 1000)?;
+            writer.write_image_data(&self.scaled_indices(frame))?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::RgbaImage;
+
+    use super::{AnimationFormat, FrameRecorder, MIN_GIF_DELAY};
+    use crate::{instruction::InstructionSet, screen::Palette, upscale::UpscaleFilter};
+
+    #[test]
+    fn test_frame_deduplication() {
+        let palette = Palette::default();
+        let mut recorder = FrameRecorder::new(&palette, 60.0);
+        let off = RgbaImage::from_pixel(4, 2, palette.two_color[0]);
+        let mut on = off.clone();
+        on.put_pixel(1, 1, palette.two_color[1]);
+
+        for frame in [&off, &off, &on, &on, &on, &off] {
+            recorder.push_frame(frame);
+        }
+
+        assert_eq!(recorder.frames.len(), 3);
+        assert_eq!(recorder.length(), 6);
+        assert_eq!(recorder.frames[1].indices[5], 1);
+
+        let mut gif = Vec::new();
+        recorder.encode(AnimationFormat::Gif, &mut gif).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+        let mut apng = Vec::new();
+        recorder.encode(AnimationFormat::Apng, &mut apng).unwrap();
+        assert!(apng.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn test_frame_sizes() {
+        let palette = Palette::default();
+        let mut recorder = FrameRecorder::new(&palette, 60.0);
+        let mut lores = RgbaImage::from_pixel(2, 1, palette.two_color[0]);
+        lores.put_pixel(1, 0, palette.two_color[1]);
+        let hires = RgbaImage::from_pixel(4, 2, palette.two_color[0]);
+        recorder.push_frame(&lores);
+        recorder.push_frame(&hires);
+        recorder.push_frame(&lores);
+
+        // Hires frames aren't shrunk, and lores ones are scaled up to match them
+        assert_eq!((recorder.width, recorder.height), (4, 2));
+        assert_eq!(recorder.frames.len(), 3);
+        assert_eq!(
+            recorder.scaled_indices(&recorder.frames[0]).as_ref(),
+            [0, 0, 1, 1, 0, 0, 1, 1]
+        );
+        assert_eq!(
+            recorder.scaled_indices(&recorder.frames[1]).as_ref(),
+            [0; 8]
+        );
+
+        let recorder = FrameRecorder::new(&palette, 60.0)
+            .with_screen_size(InstructionSet::SuperChip, UpscaleFilter::None);
+        assert_eq!((recorder.width, recorder.height), (128, 64));
+    }
+
+    #[test]
+    fn test_frame_delays() {
+        let palette = Palette::default();
+        let mut recorder = FrameRecorder::new(&palette, 60.0);
+        for i in 0..60 {
+            recorder.push_frame(&RgbaImage::from_pixel(1, 1, palette.sixteen_color[i % 2]));
+        }
+
+        // 60 frames at 60fps is exactly one second, even though no frame is a whole number of
+        // milliseconds or centiseconds
+        let millis = recorder.timed_frames(1000.0, 1);
+        assert_eq!(millis.len(), 60);
+        assert_eq!(
+            millis.iter().map(|(_, delay)| *delay as u32).sum::<u32>(),
+            1000
+        );
+        assert!(millis.iter().all(|(_, delay)| (16..=17).contains(delay)));
+        let centis = recorder.timed_frames(100.0, 1);
+        assert_eq!(
+            centis.iter().map(|(_, delay)| *delay as u32).sum::<u32>(),
+            100
+        );
+    }
+
+    #[test]
+    fn test_min_gif_delay() {
+        let palette = Palette::default();
+        for frame_rate in [30.0, 60.0, 120.0, 1000.0] {
+            let mut recorder = FrameRecorder::new(&palette, frame_rate);
+            for i in 0..frame_rate as usize {
+                recorder.push_frame(&RgbaImage::from_pixel(1, 1, palette.sixteen_color[i % 2]));
+            }
+            // Frames too short for a GIF are merged, without changing the total length
+            let centis = recorder.timed_frames(100.0, MIN_GIF_DELAY);
+            assert!(
+                centis.iter().all(|(_, delay)| *delay >= 2),
+                "{frame_rate}fps: {centis:?}"
+            );
+            assert_eq!(
+                centis.iter().map(|(_, delay)| *delay as u32).sum::<u32>(),
+                100,
+                "{frame_rate}fps"
+            );
+            if frame_rate <= 50.0 {
+                assert_eq!(centis.len(), frame_rate as usize);
+            }
+        }
+    }
+}
@@ -3,10 +3,15 @@ use std::ops::BitOr;
 use bytemuck::Zeroable;
 use image::RgbaImage;
 
-use super::{draw_line_clipping, screen_to_image, Palette, Screen};
+use super::{
+    draw_line_clipping, next_version, screen_to_image, screen_to_indices, Palette, Screen,
+};
 
 #[derive(Clone, Zeroable)]
-pub struct CosmacVipScreen([u64; 32]);
+pub struct CosmacVipScreen {
+    data: [u64; 32],
+    version: u64,
+}
 
 impl Default for Box<CosmacVipScreen> {
     fn default() -> Self {
@@ -29,21 +34,31 @@ impl Screen for CosmacVipScreen {
     }
 
     fn clear(&mut self) {
-        bytemuck::fill_zeroes(&mut self.0);
+        bytemuck::fill_zeroes(&mut self.data);
+        self.version = next_version();
     }
 
     #[inline(always)]
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8 {
         // let span = info_span!("CosmacVipScreen::draw_sprite", name = "CosmacVipScreen::draw_sprite").entered();
+        self.version = next_version();
         sprite
             .iter()
-            .zip(self.0[(y % Self::HEIGHT) as usize..].iter_mut())
+            .zip(self.data[(y % Self::HEIGHT) as usize..].iter_mut())
             .map(|(line, dest)| draw_line_clipping(dest, x % Self::WIDTH, *line))
-            .fold(false, BitOr::bitor)
+            .fold(false, BitOr::bitor) as u8
     }
 
     fn to_image(&self, palette: &Palette) -> RgbaImage {
-        // println!("{:?}", self.0);
-        screen_to_image(self.0.as_slice(), palette)
+        // println!("{:?}", self.data);
+        screen_to_image(self.data.as_slice(), palette)
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn render_indices(&self, buffer: &mut [u8]) {
+        screen_to_indices(self.data.as_slice(), buffer)
     }
 }
@@ -1,4 +1,5 @@
 mod cosmac_vip;
+mod palette;
 mod schip;
 mod xochip;
 
@@ -7,6 +8,7 @@ use std::{
     fmt::Binary,
     mem,
     ops::{BitAnd, BitXorAssign, Shl, ShlAssign, Shr},
+    sync::atomic::{self, AtomicU64},
 };
 
 use arbitrary_int::u4;
@@ -16,51 +18,10 @@ use num_traits::PrimInt;
 use thiserror::Error;
 
 pub use cosmac_vip::CosmacVipScreen;
+pub use palette::{Palette, PaletteError, PalettePreset, PRESETS};
 pub use schip::{LegacySuperChipScreen, ModernSuperChipScreen};
 pub use xochip::XoChipScreen;
 
-// from https://github.com/gulrak/cadmium/blob/1e1f524c4d1c5ceff3b3da8818f0ed815e9160db/src/cadmium.cpp#L1893-L1898
-const CADMIUM_PALETTE: [u32; 16] = [
-    0x1a1c2cff, 0xf4f4f4ff, 0x94b0c2ff, 0x333c57ff, 0xb13e53ff, 0xa7f070ff, 0x3b5dc9ff, 0xffcd75ff,
-    0x5d275dff, 0x38b764ff, 0x29366fff, 0x566c86ff, 0xef7d57ff, 0x73eff7ff, 0x41a6f6ff, 0x257179ff,
-];
-
-#[derive(Debug, Clone)]
-pub struct Palette {
-    pub two_color: [Rgba<u8>; 2],
-    pub sixteen_color: [Rgba<u8>; 16],
-    pub use_custom_two_color: bool,
-}
-
-impl Default for Palette {
-    fn default() -> Self {
-        let sixteen_color = CADMIUM_PALETTE.map(|color| Rgba::from(color.to_be_bytes()));
-        Self {
-            two_color: [sixteen_color[0], sixteen_color[1]],
-            sixteen_color,
-            use_custom_two_color: true,
-        }
-    }
-}
-
-impl Palette {
-    fn two_color_off(&self) -> Rgba<u8> {
-        if self.use_custom_two_color {
-            self.two_color[0]
-        } else {
-            self.sixteen_color[0]
-        }
-    }
-
-    fn two_color_on(&self) -> Rgba<u8> {
-        if self.use_custom_two_color {
-            self.two_color[1]
-        } else {
-            self.sixteen_color[1]
-        }
-    }
-}
-
 #[derive(Error, Debug, Clone)]
 pub enum UnsupportedScreenOperation {
     #[error("this screen type does not support hires mode")]
@@ -99,7 +60,8 @@ pub trait Screen: BoxDynClone + Send + Sync {
     fn num_active_planes(&self) -> usize {
         1
     }
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool;
+    /// Draw an 8-pixel-wide sprite, returning the value for vF.
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8;
     fn draw_large_sprite(&mut self, _x: u8, _y: u8, _sprite: &[[u8; 32]]) -> Result<u8> {
         Err(UnsupportedScreenOperation::LargeSprite)
     }
@@ -116,12 +78,37 @@ pub trait Screen: BoxDynClone + Send + Sync {
         Err(UnsupportedScreenOperation::ScrollLeft)
     }
     fn to_image(&self, palette: &Palette) -> RgbaImage;
+    /// Identifies the current contents of the screen. Every change gives the screen a new
+    /// version that is unique across all screens, so an unchanged frame doesn't need to be
+    /// rendered again.
+    fn version(&self) -> u64;
+    /// Write the palette index of each pixel into `buffer`, which holds `width() * height()`
+    /// bytes, one row after another.
+    fn render_indices(&self, buffer: &mut [u8]);
+    /// The color of each palette index written by [`Screen::render_indices`].
+    fn index_colors(&self, palette: &Palette) -> [Rgba<u8>; 16] {
+        let mut colors = [palette.two_color_off(); 16];
+        colors[1] = palette.two_color_on();
+        colors
+    }
+}
+
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+/// A new screen version. Version 0 is left for screens that have never been drawn to.
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, atomic::Ordering::Relaxed)
 }
 
 trait BoxDynClone {
     fn box_dyn_clone(&self) -> Box<dyn Screen>;
 }
 
+/// Copy any screen into a new box, including one that's already a trait object.
+pub fn clone_boxed<S: Screen + ?Sized>(screen: &S) -> Box<dyn Screen> {
+    screen.box_dyn_clone()
+}
+
 impl<T> BoxDynClone for T
 where
     T: Clone + Screen,
@@ -235,13 +222,16 @@ impl Screen for DynamicScreen {
     screen_method!(set_hires(self: &mut Self, hires: bool) -> Result<()>);
     screen_method!(set_planes(self: &mut Self, planes: u4) -> Result<()>);
     screen_method!(num_active_planes(self: &Self) -> usize);
-    screen_method!(draw_sprite(self: &mut Self, x: u8, y: u8, sprite: &[u8]) -> bool);
+    screen_method!(draw_sprite(self: &mut Self, x: u8, y: u8, sprite: &[u8]) -> u8);
     screen_method!(draw_large_sprite(self: &mut Self, x: u8, y: u8, sprite: &[[u8; 32]]) -> Result<u8>);
     screen_method!(scroll_down(self: &mut Self, amount: u4) -> Result<()>);
     screen_method!(scroll_up(self: &mut Self, amount: u4) -> Result<()>);
     screen_method!(scroll_right(self: &mut Self) -> Result<()>);
     screen_method!(scroll_left(self: &mut Self) -> Result<()>);
     screen_method!(to_image(self: &Self, palette: &Palette) -> RgbaImage);
+    screen_method!(version(self: &Self) -> u64);
+    screen_method!(render_indices(self: &Self, buffer: &mut [u8]));
+    screen_method!(index_colors(self: &Self, palette: &Palette) -> [Rgba<u8>; 16]);
 }
 
 macro_rules! dyn_screen_method {
@@ -265,13 +255,16 @@ impl Screen for Box<dyn Screen> {
     dyn_screen_method!(set_hires(self: &mut Self, hires: bool) -> Result<()>);
     dyn_screen_method!(set_planes(self: &mut Self, planes: u4) -> Result<()>);
     dyn_screen_method!(num_active_planes(self: &Self) -> usize);
-    dyn_screen_method!(draw_sprite(self: &mut Self, x: u8, y: u8, sprite: &[u8]) -> bool);
+    dyn_screen_method!(draw_sprite(self: &mut Self, x: u8, y: u8, sprite: &[u8]) -> u8);
     dyn_screen_method!(draw_large_sprite(self: &mut Self, x: u8, y: u8, sprite: &[[u8; 32]]) -> Result<u8>);
     dyn_screen_method!(scroll_down(self: &mut Self, amount: u4) -> Result<()>);
     dyn_screen_method!(scroll_up(self: &mut Self, amount: u4) -> Result<()>);
     dyn_screen_method!(scroll_right(self: &mut Self) -> Result<()>);
     dyn_screen_method!(scroll_left(self: &mut Self) -> Result<()>);
     dyn_screen_method!(to_image(self: &Self, palette: &Palette) -> RgbaImage);
+    dyn_screen_method!(version(self: &Self) -> u64);
+    dyn_screen_method!(render_indices(self: &Self, buffer: &mut [u8]));
+    dyn_screen_method!(index_colors(self: &Self, palette: &Palette) -> [Rgba<u8>; 16]);
 }
 
 impl BoxDynClone for Box<dyn Screen> {
@@ -407,6 +400,151 @@ fn screen_to_image<N: PrimInt + ShlAssign<u32> + Binary>(
     image
 }
 
+/// The palette indices of the eight pixels in each possible byte of a single-plane screen.
+const BYTE_INDICES: [[u8; 8]; 256] = {
+    let mut table = [[0; 8]; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut bit = 0;
+        while bit < 8 {
+            table[byte][bit] = (byte >> (7 - bit)) as u8 & 1;
+            bit += 1;
+        }
+        byte += 1;
+    }
+    table
+};
+
+fn screen_to_indices<N: PrimInt>(data: &[N], buffer: &mut [u8]) {
+    let width = mem::size_of::<N>() * 8;
+    for (line, row) in data.iter().zip(buffer.chunks_exact_mut(width)) {
+        for (i, pixels) in row.chunks_exact_mut(8).enumerate() {
+            let byte = (*line >> (width - 8 - i * 8)) & N::from(0xFF).unwrap();
+            pixels.copy_from_slice(&BYTE_INDICES[byte.to_usize().unwrap()]);
+        }
+    }
+}
+
+/// Convert the palette indices from [`Screen::render_indices`] into RGBA pixels.
+pub fn indices_to_rgba(indices: &[u8], colors: &[Rgba<u8>; 16], rgba: &mut [u8]) {
+    for (index, pixel) in indices.iter().zip(rgba.chunks_exact_mut(4)) {
+        pixel.copy_from_slice(&colors[*index as usize & 0xF].0);
+    }
+}
+
+/// A screen rendered into buffers that are reused from frame to frame. Updating it only redraws
+/// what has changed: nothing if neither the screen nor the palette has, or only the colors if just
+/// the palette has.
+#[derive(Debug, Clone)]
+pub struct IndexedFrame {
+    width: u32,
+    height: u32,
+    version: Option<u64>,
+    colors: [Rgba<u8>; 16],
+    indices: Vec<u8>,
+    rgba: Vec<u8>,
+    generation: u64,
+}
+
+impl Default for IndexedFrame {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            version: None,
+            colors: [Rgba([0; 4]); 16],
+            indices: Vec::new(),
+            rgba: Vec::new(),
+            generation: 0,
+        }
+    }
+}
+
+impl IndexedFrame {
+    /// Bring the frame up to date with `screen`, returning whether any pixels changed.
+    pub fn update<S: Screen + ?Sized>(&mut self, screen: &S, palette: &Palette) -> bool {
+        let (width, height) = (screen.width() as u32, screen.height() as u32);
+        let resized = (width, height) != (self.width, self.height);
+        if resized {
+            self.width = width;
+            self.height = height;
+            self.indices.resize((width * height) as usize, 0);
+            self.rgba.resize((width * height * 4) as usize, 0);
+        }
+
+        let version = screen.version();
+        let screen_changed = resized || self.version != Some(version);
+        if screen_changed {
+            screen.render_indices(&mut self.indices);
+            self.version = Some(version);
+        }
+        let colors = screen.index_colors(palette);
+        if !screen_changed && colors == self.colors {
+            return false;
+        }
+        self.colors = colors;
+        indices_to_rgba(&self.indices, &self.colors, &mut self.rgba);
+        self.generation += 1;
+        true
+    }
+
+    /// Counts the updates that changed the frame.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    /// The frame's pixels, in the same layout as an [`RgbaImage`].
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    pub fn to_image(&self) -> RgbaImage {
+        RgbaImage::from_raw(self.width, self.height, self.rgba.clone())
+            .expect("frame buffer matches its dimensions")
+    }
+}
+
+#[test]
+fn test_indexed_frame() {
+    let palette = Palette::default();
+    let sprite = [0b1100_0011, 0b0011_1100, 0b1010_0101];
+    let mut screens = [
+        DynamicScreen::new_cosmac_vip(),
+        DynamicScreen::new_legacy_super_chip(),
+        DynamicScreen::new_modern_super_chip(),
+        DynamicScreen::new_xochip(),
+    ];
+    for screen in &mut screens {
+        let mut frame = IndexedFrame::default();
+        assert!(frame.update(screen.as_ref(), &palette));
+        assert!(!frame.update(screen.as_ref(), &palette));
+
+        let _ = screen.set_hires(true);
+        screen.draw_sprite(3, 5, &sprite);
+        screen.draw_sprite(60, 30, &sprite);
+        assert!(frame.update(screen.as_ref(), &palette));
+        assert_eq!(frame.to_image(), screen.to_image(&palette));
+
+        // A copy of the screen has the same contents, so it doesn't need redrawing
+        let copy = screen.clone();
+        assert!(!frame.update(copy.as_ref(), &palette));
+
+        let mut inverted = palette.clone();
+        inverted.two_color.reverse();
+        inverted.sixteen_color.swap(0, 1);
+        assert!(frame.update(screen.as_ref(), &inverted));
+        assert_eq!(frame.to_image(), screen.to_image(&inverted));
+    }
+}
+
 /// Double each bit in x.
 /// Credit to https://stackoverflow.com/a/2929404
 /// Based on https://graphics.stanford.edu/~seander/bithacks.html#Interleave64bitOps
@@ -0,0 +1,291 @@
+use image::Rgba;
+use thiserror::Error;
+
+// from https://github.com/gulrak/cadmium/blob/1e1f524c4d1c5ceff3b3da8818f0ed815e9160db/src/cadmium.cpp#L1893-L1898
+const CADMIUM_PALETTE: [u32; 16] = [
+    0x1a1c2cff, 0xf4f4f4ff, 0x94b0c2ff, 0x333c57ff, 0xb13e53ff, 0xa7f070ff, 0x3b5dc9ff, 0xffcd75ff,
+    0x5d275dff, 0x38b764ff, 0x29366fff, 0x566c86ff, 0xef7d57ff, 0x73eff7ff, 0x41a6f6ff, 0x257179ff,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub two_color: [Rgba<u8>; 2],
+    pub sixteen_color: [Rgba<u8>; 16],
+    pub use_custom_two_color: bool,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        let sixteen_color = CADMIUM_PALETTE.map(|color| Rgba::from(color.to_be_bytes()));
+        Self {
+            two_color: [sixteen_color[0], sixteen_color[1]],
+            sixteen_color,
+            use_custom_two_color: true,
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PaletteError {
+    #[error("invalid color '{0}'")]
+    InvalidColor(String),
+    #[error("line {0}: expected red, green and blue values from 0 to 255")]
+    InvalidGplLine(usize),
+    #[error("expected 2, 4, 6, 16 or 18 colors, found {0}")]
+    WrongColorCount(usize),
+}
+
+/// A built-in palette, as a list of colors in the same layout that [`Palette::from_colors`]
+/// takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PalettePreset {
+    pub name: &'static str,
+    pub colors: &'static [u32],
+}
+
+impl PalettePreset {
+    pub fn palette(&self) -> Palette {
+        let colors = self
+            .colors
+            .iter()
+            .map(|color| rgb_to_rgba(*color))
+            .collect::<Vec<_>>();
+        Palette::from_colors(&colors).expect("presets have a valid number of colors")
+    }
+}
+
+pub const PRESETS: &[PalettePreset] = &[
+    PalettePreset {
+        name: "Cadmium",
+        colors: &[
+            0x1a1c2c, 0xf4f4f4, 0x94b0c2, 0x333c57, 0xb13e53, 0xa7f070, 0x3b5dc9, 0xffcd75,
+            0x5d275d, 0x38b764, 0x29366f, 0x566c86, 0xef7d57, 0x73eff7, 0x41a6f6, 0x257179,
+        ],
+    },
+    // Background, fill, fill 2 and blend, buzzer and silence, like Octo's own palette settings
+    PalettePreset {
+        name: "Octo classic",
+        colors: &[0x996600, 0xffcc00, 0xff6600, 0x662200, 0xffaa00, 0x000000],
+    },
+    PalettePreset {
+        name: "VIP green phosphor",
+        colors: &[0x0a140c, 0x4dff7a, 0x1f8f3f, 0xb3ffc6],
+    },
+    PalettePreset {
+        name: "HP 48 LCD",
+        colors: &[0xb4c0a4, 0x1e261e, 0x6d7963, 0x47513f],
+    },
+    PalettePreset {
+        name: "Game Boy",
+        colors: &[0x9bbc0f, 0x0f380f, 0x8bac0f, 0x306230],
+    },
+    PalettePreset {
+        name: "High contrast",
+        colors: &[0x000000, 0xffffff, 0xffff00, 0x00ffff],
+    },
+    // Okabe and Ito's eight colors, followed by Paul Tol's muted scheme
+    PalettePreset {
+        name: "Colour-blind safe (Okabe-Ito)",
+        colors: &[
+            0x000000, 0xffffff, 0xe69f00, 0x56b4e9, 0x009e73, 0xf0e442, 0x0072b2, 0xd55e00,
+            0xcc79a7, 0x332288, 0x88ccee, 0x44aa99, 0x117733, 0x999933, 0xddcc77, 0x882255,
+        ],
+    },
+    // Paul Tol's bright and muted schemes, on a light background
+    PalettePreset {
+        name: "Colour-blind safe (Tol)",
+        colors: &[
+            0xdddddd, 0x222222, 0x4477aa, 0xee6677, 0x228833, 0xccbb44, 0x66ccee, 0xaa3377,
+            0x332288, 0x88ccee, 0x44aa99, 0x117733, 0x999933, 0xddcc77, 0xcc6677, 0x882255,
+        ],
+    },
+];
+
+fn rgb_to_rgba(color: u32) -> Rgba<u8> {
+    let [_, r, g, b] = color.to_be_bytes();
+    Rgba([r, g, b, 0xFF])
+}
+
+impl Palette {
+    /// The color of unlit pixels, on screens with several display planes or just one.
+    pub fn background(&self, multi_plane: bool) -> Rgba<u8> {
+        if multi_plane {
+            self.sixteen_color[0]
+        } else {
+            self.two_color_off()
+        }
+    }
+
+    pub(super) fn two_color_off(&self) -> Rgba<u8> {
+        if self.use_custom_two_color {
+            self.two_color[0]
+        } else {
+            self.sixteen_color[0]
+        }
+    }
+
+    pub(super) fn two_color_on(&self) -> Rgba<u8> {
+        if self.use_custom_two_color {
+            self.two_color[1]
+        } else {
+            self.sixteen_color[1]
+        }
+    }
+
+    /// Build a palette from a list of colors. The layout depends on how many there are:
+    ///
+    /// - 2: the two-color palette.
+    /// - 4: the first four XO-CHIP colors, which are also used for two colors.
+    /// - 6: Octo's palette, which is the same as 4 plus its buzzer and silence colors (ignored).
+    /// - 16: all of the XO-CHIP colors, which are also used for two colors.
+    /// - 18: the two-color palette, then the XO-CHIP colors. This is how palettes are exported.
+    ///
+    /// Anything that isn't given comes from the default palette.
+    pub fn from_colors(colors: &[Rgba<u8>]) -> Result<Self, PaletteError> {
+        let mut palette = Self::default();
+        match colors.len() {
+            2 => palette.two_color.copy_from_slice(colors),
+            4 | 6 => {
+                palette.sixteen_color[..4].copy_from_slice(&colors[..4]);
+                palette.use_custom_two_color = false;
+            }
+            16 => {
+                palette.sixteen_color.copy_from_slice(colors);
+                palette.use_custom_two_color = false;
+            }
+            18 => {
+                palette.two_color.copy_from_slice(&colors[..2]);
+                palette.sixteen_color.copy_from_slice(&colors[2..]);
+            }
+            count => return Err(PaletteError::WrongColorCount(count)),
+        }
+        Ok(palette)
+    }
+
+    /// All 18 colors, in the order [`Palette::from_colors`] expects.
+    pub fn colors(&self) -> Vec<Rgba<u8>> {
+        let two_color = [self.two_color_off(), self.two_color_on()];
+        two_color.into_iter().chain(self.sixteen_color).collect()
+    }
+
+    /// Parse a list of hex colors, like Octo's palette settings or a `.hex` palette file. Colors
+    /// can be separated by whitespace, commas or new lines, and can start with `#` or `0x`.
+    pub fn parse_hex(text: &str) -> Result<Self, PaletteError> {
+        let colors = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|color| !color.is_empty())
+            .map(|color| {
+                let hex = color
+                    .strip_prefix('#')
+                    .or_else(|| color.strip_prefix("0x"))
+                    .unwrap_or(color);
+                match u32::from_str_radix(hex, 16) {
+                    Ok(rgb) if hex.len() == 6 => Ok(rgb_to_rgba(rgb)),
+                    _ => Err(PaletteError::InvalidColor(color.to_owned())),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_colors(&colors)
+    }
+
+    /// One `RRGGBB` color per line, the format of `.hex` palette files.
+    pub fn to_hex(&self) -> String {
+        self.colors()
+            .iter()
+            .map(|color| format!("{:02X}{:02X}{:02X}\n", color[0], color[1], color[2]))
+            .collect()
+    }
+
+    /// Parse a GIMP palette (`.gpl`) file.
+    pub fn parse_gpl(text: &str) -> Result<Self, PaletteError> {
+        let mut colors = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("GIMP Palette")
+                || line.starts_with("Name:")
+                || line.starts_with("Columns:")
+            {
+                continue;
+            }
+            let channels = line
+                .split_whitespace()
+                .take(3)
+                .map(|channel| channel.parse::<u8>())
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .filter(|channels| channels.len() == 3)
+                .ok_or(PaletteError::InvalidGplLine(number + 1))?;
+            colors.push(Rgba([channels[0], channels[1], channels[2], 0xFF]));
+        }
+        Self::from_colors(&colors)
+    }
+
+    pub fn to_gpl(&self, name: &str) -> String {
+        let mut gpl = format!("GIMP Palette\nName: {name}\nColumns: 2\n#\n");
+        let names = ["Off", "On"]
+            .map(str::to_owned)
+            .into_iter()
+            .chain((0..16).map(|i| format!("Color {i}")));
+        for (color, name) in self.colors().iter().zip(names) {
+            gpl += &format!("{:3} {:3} {:3}\t{name}\n", color[0], color[1], color[2]);
+        }
+        gpl
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::Rgba;
+
+    use super::{Palette, PaletteError, PRESETS};
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(PRESETS[0].palette().colors(), Palette::default().colors());
+        for preset in PRESETS {
+            preset.palette();
+        }
+    }
+
+    #[test]
+    fn test_hex_palettes() {
+        let octo = Palette::parse_hex("#996600 #FFCC00 #FF6600 #662200 #FFAA00 #000000").unwrap();
+        assert_eq!(octo.sixteen_color[2], Rgba([0xFF, 0x66, 0x00, 0xFF]));
+        assert_eq!(octo.colors()[..2], octo.sixteen_color[..2]);
+        assert_eq!(
+            Palette::parse_hex(&octo.to_hex()).unwrap(),
+            Palette {
+                use_custom_two_color: true,
+                two_color: [octo.sixteen_color[0], octo.sixteen_color[1]],
+                ..octo
+            }
+        );
+
+        let two_color = Palette::parse_hex("0x000000,\n0xffffff").unwrap();
+        assert_eq!(two_color.two_color[1], Rgba([0xFF; 4]));
+        assert_eq!(
+            Palette::parse_hex("#12345"),
+            Err(PaletteError::InvalidColor("#12345".to_owned()))
+        );
+        assert_eq!(
+            Palette::parse_hex("#123456 #123456 #123456"),
+            Err(PaletteError::WrongColorCount(3))
+        );
+    }
+
+    #[test]
+    fn test_gpl_palettes() {
+        let palette = PRESETS[6].palette();
+        let gpl = palette.to_gpl("Test");
+        assert!(gpl.starts_with("GIMP Palette\nName: Test\n"));
+        let parsed = Palette::parse_gpl(&gpl).unwrap();
+        assert_eq!(parsed.colors(), palette.colors());
+
+        assert_eq!(
+            Palette::parse_gpl("GIMP Palette\n0 0 0\n255 256 0\n"),
+            Err(PaletteError::InvalidGplLine(3))
+        );
+    }
+}
@@ -5,19 +5,27 @@ use bytemuck::Zeroable;
 use image::RgbaImage;
 
 use super::{
-    double_bits_holger, double_bits_magic, draw_line_clipping, screen_to_image, Palette, Result,
-    Screen,
+    double_bits_holger, double_bits_magic, draw_line_clipping, next_version, screen_to_image,
+    screen_to_indices, Palette, Result, Screen,
 };
 
 #[derive(Clone, Zeroable)]
 pub struct LegacySuperChipScreen {
     data: [u128; 64],
     hires: bool,
+    version: u64,
 }
 
 impl LegacySuperChipScreen {
     const WIDTH: u8 = 128;
     const HEIGHT: u8 = 64;
+
+    /// How many rows of a sprite drawn at `y` fall off the bottom of the screen. When SUPER-CHIP
+    /// 1.1 draws in hires mode, it sets vF to the number of rows that collided plus these,
+    /// rather than just 0 or 1 (see the DXYN notes in <https://chip8.gulrak.net/>).
+    fn clipped_rows(y: u8, rows: usize) -> u8 {
+        (y as usize + rows).saturating_sub(Self::HEIGHT as usize) as u8
+    }
 }
 
 impl Default for Box<LegacySuperChipScreen> {
@@ -37,6 +45,7 @@ impl Screen for LegacySuperChipScreen {
 
     fn clear(&mut self) {
         bytemuck::fill_zeroes(&mut self.data);
+        self.version = next_version();
     }
 
     fn get_hires(&self) -> bool {
@@ -48,13 +57,16 @@ impl Screen for LegacySuperChipScreen {
         Ok(())
     }
 
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8 {
+        self.version = next_version();
         if self.hires {
+            let y = y % Self::HEIGHT;
             sprite
                 .iter()
-                .zip(self.data[(y % Self::HEIGHT) as usize..].iter_mut())
-                .map(|(line, dest)| draw_line_clipping(dest, x % Self::WIDTH, *line))
-                .fold(false, BitOr::bitor)
+                .zip(self.data[y as usize..].iter_mut())
+                .map(|(line, dest)| draw_line_clipping(dest, x % Self::WIDTH, *line) as u8)
+                .sum::<u8>()
+                + Self::clipped_rows(y, sprite.len())
         } else {
             let x = (x << 1) % Self::WIDTH;
             let zone_offset = x & 0xF0;
@@ -69,18 +81,21 @@ impl Screen for LegacySuperChipScreen {
                     dest[1] = (dest[1] & !mask) | (dest[0] & mask);
                     collided
                 })
-                .fold(false, BitOr::bitor)
+                .fold(false, BitOr::bitor) as u8
         }
     }
 
     fn draw_large_sprite(&mut self, x: u8, y: u8, sprite: &[[u8; 32]]) -> Result<u8> {
+        self.version = next_version();
         let collided = if self.hires {
+            let y = y % Self::HEIGHT;
             sprite[0]
                 .chunks_exact(2)
                 .map(|line| u16::from_be_bytes([line[0], line[1]]))
-                .zip(self.data[(y % Self::HEIGHT) as usize..].iter_mut())
+                .zip(self.data[y as usize..].iter_mut())
                 .map(|(line, dest)| draw_line_clipping(dest, x % Self::WIDTH, line) as u8)
-                .sum()
+                .sum::<u8>()
+                + Self::clipped_rows(y, 16)
         } else {
             return Err(super::UnsupportedScreenOperation::LargeSpriteInLores);
         };
@@ -90,6 +105,7 @@ impl Screen for LegacySuperChipScreen {
     fn scroll_down(&mut self, amount: u4) -> Result<()> {
         let amount = u8::from(amount) as usize;
         if amount > 0 {
+            self.version = next_version();
             self.data
                 .copy_within(..Self::HEIGHT as usize - amount, amount);
             for line in self.data[..amount].iter_mut() {
@@ -100,6 +116,7 @@ impl Screen for LegacySuperChipScreen {
     }
 
     fn scroll_right(&mut self) -> Result<()> {
+        self.version = next_version();
         for line in self.data.iter_mut() {
             *line >>= 4;
         }
@@ -107,6 +124,7 @@ impl Screen for LegacySuperChipScreen {
     }
 
     fn scroll_left(&mut self) -> Result<()> {
+        self.version = next_version();
         for line in self.data.iter_mut() {
             *line <<= 4;
         }
@@ -116,12 +134,21 @@ impl Screen for LegacySuperChipScreen {
     fn to_image(&self, palette: &Palette) -> RgbaImage {
         screen_to_image(self.data.as_slice(), palette)
     }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn render_indices(&self, buffer: &mut [u8]) {
+        screen_to_indices(self.data.as_slice(), buffer)
+    }
 }
 
 #[derive(Clone, Zeroable)]
 pub struct ModernSuperChipScreen {
     data: [u128; 64],
     hires: bool,
+    version: u64,
 }
 
 impl ModernSuperChipScreen {
@@ -146,6 +173,7 @@ impl Screen for ModernSuperChipScreen {
 
     fn clear(&mut self) {
         bytemuck::fill_zeroes(&mut self.data);
+        self.version = next_version();
     }
 
     fn get_hires(&self) -> bool {
@@ -157,8 +185,9 @@ impl Screen for ModernSuperChipScreen {
         Ok(())
     }
 
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
-        if self.hires {
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8 {
+        self.version = next_version();
+        let collided = if self.hires {
             sprite
                 .iter()
                 .zip(self.data[(y % Self::HEIGHT) as usize..].iter_mut())
@@ -176,10 +205,12 @@ impl Screen for ModernSuperChipScreen {
                         | draw_line_clipping(&mut dest[1], x, line)
                 })
                 .fold(false, BitOr::bitor)
-        }
+        };
+        collided as u8
     }
 
     fn draw_large_sprite(&mut self, x: u8, y: u8, sprite: &[[u8; 32]]) -> Result<u8> {
+        self.version = next_version();
         let collided = if self.hires {
             sprite[0]
                 .chunks_exact(2)
@@ -209,6 +240,7 @@ impl Screen for ModernSuperChipScreen {
             amount *= 2;
         }
         if amount > 0 {
+            self.version = next_version();
             self.data
                 .copy_within(..Self::HEIGHT as usize - amount, amount);
             for line in self.data[..amount].iter_mut() {
@@ -219,6 +251,7 @@ impl Screen for ModernSuperChipScreen {
     }
 
     fn scroll_right(&mut self) -> Result<()> {
+        self.version = next_version();
         let amount = if self.hires { 4 } else { 8 };
         for line in self.data.iter_mut() {
             *line >>= amount;
@@ -227,6 +260,7 @@ impl Screen for ModernSuperChipScreen {
     }
 
     fn scroll_left(&mut self) -> Result<()> {
+        self.version = next_version();
         let amount = if self.hires { 4 } else { 8 };
         for line in self.data.iter_mut() {
             *line <<= amount;
@@ -237,4 +271,12 @@ impl Screen for ModernSuperChipScreen {
     fn to_image(&self, palette: &Palette) -> RgbaImage {
         screen_to_image(self.data.as_slice(), palette)
     }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn render_indices(&self, buffer: &mut [u8]) {
+        screen_to_indices(self.data.as_slice(), buffer)
+    }
 }
@@ -2,10 +2,11 @@ use std::ops::BitOr;
 
 use arbitrary_int::u4;
 use bytemuck::Zeroable;
-use image::RgbaImage;
+use image::{Rgba, RgbaImage};
 
 use super::{
-    combine_planes, double_bits_holger, double_bits_magic, draw_line, Palette, Result, Screen,
+    combine_planes, double_bits_holger, double_bits_magic, draw_line, next_version, Palette,
+    Result, Screen,
 };
 
 #[derive(Clone, Zeroable)]
@@ -13,6 +14,7 @@ pub struct XoChipScreen {
     data: [[u128; 64]; 4],
     enabled_planes: [bool; 4],
     hires: bool,
+    version: u64,
 }
 
 impl XoChipScreen {
@@ -46,6 +48,7 @@ impl Screen for XoChipScreen {
     }
 
     fn clear(&mut self) {
+        self.version = next_version();
         for plane in self.iter_enabled_planes() {
             bytemuck::fill_zeroes(plane);
         }
@@ -78,7 +81,7 @@ impl Screen for XoChipScreen {
             .sum()
     }
 
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+    fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8 {
         // println!(
         //     "draw small sprite {x} {y} {}",
         //     sprite
@@ -87,9 +90,13 @@ impl Screen for XoChipScreen {
         //         .collect::<Vec<_>>()
         //         .join(" ")
         // );
+        self.version = next_version();
         let hires = self.hires;
-        let sprite_size = sprite.len() / self.num_active_planes();
-        if hires {
+        let Some(sprite_size) = sprite.len().checked_div(self.num_active_planes()) else {
+            // With no planes selected there's nothing to draw on
+            return 0;
+        };
+        let collided = if hires {
             self.iter_enabled_planes()
                 .zip(sprite.chunks(sprite_size))
                 .flat_map(|(plane, sprite)| {
@@ -115,10 +122,12 @@ impl Screen for XoChipScreen {
                         })
                 })
                 .fold(false, BitOr::bitor)
-        }
+        };
+        collided as u8
     }
 
     fn draw_large_sprite(&mut self, x: u8, y: u8, sprite: &[[u8; 32]]) -> Result<u8> {
+        self.version = next_version();
         let collided = if self.hires {
             self.iter_enabled_planes()
                 .zip(sprite.iter())
@@ -156,6 +165,7 @@ impl Screen for XoChipScreen {
             amount *= 2;
         }
         if amount > 0 {
+            self.version = next_version();
             for plane in self.iter_enabled_planes() {
                 plane.copy_within(..Self::HEIGHT as usize - amount, amount);
                 for line in plane[..amount].iter_mut() {
@@ -172,6 +182,7 @@ impl Screen for XoChipScreen {
             amount *= 2;
         }
         if amount > 0 {
+            self.version = next_version();
             for plane in self.iter_enabled_planes() {
                 plane.copy_within(amount.., 0);
                 for line in plane[Self::HEIGHT as usize - amount..].iter_mut() {
@@ -184,6 +195,7 @@ impl Screen for XoChipScreen {
 
     fn scroll_right(&mut self) -> Result<()> {
         let amount = if self.hires { 4 } else { 8 };
+        self.version = next_version();
         for plane in self.iter_enabled_planes() {
             for line in plane.iter_mut() {
                 *line >>= amount;
@@ -194,6 +206,7 @@ impl Screen for XoChipScreen {
 
     fn scroll_left(&mut self) -> Result<()> {
         let amount = if self.hires { 4 } else { 8 };
+        self.version = next_version();
         for plane in self.iter_enabled_planes() {
             for line in plane.iter_mut() {
                 *line <<= amount;
@@ -219,6 +232,29 @@ impl Screen for XoChipScreen {
         }
         image
     }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn render_indices(&self, buffer: &mut [u8]) {
+        for (y, row) in buffer
+            .chunks_exact_mut(Self::WIDTH as usize)
+            .enumerate()
+            .take(Self::HEIGHT as usize)
+        {
+            row.copy_from_slice(&combine_planes(
+                self.data[0][y],
+                self.data[1][y],
+                self.data[2][y],
+                self.data[3][y],
+            ));
+        }
+    }
+
+    fn index_colors(&self, palette: &Palette) -> [Rgba<u8>; 16] {
+        palette.sixteen_color
+    }
 }
 
 fn iter_plane_wrapping(plane: &mut [u128; 64], y: u8) -> impl Iterator<Item = &mut u128> {
@@ -0,0 +1,731 @@
+use image::{Rgba, RgbaImage};
+
+/// Pixel-art scaling filters, for smoothing out the blocky CHIP-8 display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    #[default]
+    None,
+    Scale2x,
+    Scale3x,
+    Hq2x,
+    Hq4x,
+    /// Only level 1 of xBR, and only at 2x. The higher levels, which follow shallower edges,
+    /// and the 3x and 4x versions aren't implemented.
+    Xbr2x,
+}
+
+impl UpscaleFilter {
+    pub const ALL: [Self; 6] = [
+        Self::None,
+        Self::Scale2x,
+        Self::Scale3x,
+        Self::Hq2x,
+        Self::Hq4x,
+        Self::Xbr2x,
+    ];
+
+    /// How many times larger the filter makes each side of the image.
+    pub fn factor(self) -> u32 {
+        match self {
+            Self::None => 1,
+            Self::Scale2x | Self::Hq2x | Self::Xbr2x => 2,
+            Self::Scale3x => 3,
+            Self::Hq4x => 4,
+        }
+    }
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Scale2x => "scale2x",
+            Self::Scale3x => "scale3x",
+            Self::Hq2x => "hq2x",
+            Self::Hq4x => "hq4x",
+            Self::Xbr2x => "xbr2x",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|filter| filter.id() == id)
+    }
+
+    pub fn apply(self, image: &RgbaImage) -> RgbaImage {
+        match self {
+            Self::None => image.clone(),
+            Self::Scale2x => scale2x(image),
+            Self::Scale3x => scale3x(image),
+            Self::Hq2x => hq2x(image),
+            Self::Hq4x => hq4x(image),
+            Self::Xbr2x => xbr2x(image),
+        }
+    }
+}
+
+impl std::fmt::Display for UpscaleFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Scale2x => write!(f, "Scale2x (EPX)"),
+            Self::Scale3x => write!(f, "Scale3x"),
+            Self::Hq2x => write!(f, "HQ2x"),
+            Self::Hq4x => write!(f, "HQ4x"),
+            Self::Xbr2x => write!(f, "xBR 2x"),
+        }
+    }
+}
+
+/// Reads pixels around a center pixel, with the edges of the image extending forever. Offsets
+/// can be mirrored, so that a rule written for one corner works for all four.
+#[derive(Clone, Copy)]
+struct Neighborhood<'a> {
+    image: &'a RgbaImage,
+    x: i64,
+    y: i64,
+    flip_x: i64,
+    flip_y: i64,
+}
+
+impl<'a> Neighborhood<'a> {
+    fn new(image: &'a RgbaImage, x: u32, y: u32) -> Self {
+        Self {
+            image,
+            x: x as i64,
+            y: y as i64,
+            flip_x: 1,
+            flip_y: 1,
+        }
+    }
+
+    /// Mirror the neighborhood so that offsets of `(1, 1)` point towards the given corner.
+    fn towards(self, right: bool, down: bool) -> Self {
+        Self {
+            flip_x: if right { 1 } else { -1 },
+            flip_y: if down { 1 } else { -1 },
+            ..self
+        }
+    }
+
+    fn get(&self, dx: i64, dy: i64) -> Rgba<u8> {
+        let x = (self.x + dx * self.flip_x).clamp(0, self.image.width() as i64 - 1);
+        let y = (self.y + dy * self.flip_y).clamp(0, self.image.height() as i64 - 1);
+        *self.image.get_pixel(x as u32, y as u32)
+    }
+}
+
+const CORNERS: [(bool, bool); 4] = [(false, false), (true, false), (false, true), (true, true)];
+
+/// Scale2x, also known as EPX: each pixel becomes 2x2, and a corner takes a neighbor's color when
+/// the two neighbors touching that corner match each other but not the rest.
+pub fn scale2x(image: &RgbaImage) -> RgbaImage {
+    let mut output = RgbaImage::new(image.width() * 2, image.height() * 2);
+    for (x, y, e) in image.enumerate_pixels() {
+        for (right, down) in CORNERS {
+            let pixels = Neighborhood::new(image, x, y).towards(right, down);
+            let (b, d, f, h) = (
+                pixels.get(0, 1),
+                pixels.get(1, 0),
+                pixels.get(-1, 0),
+                pixels.get(0, -1),
+            );
+            let color = if b != h && d != f && b == d { d } else { *e };
+            output.put_pixel(x * 2 + right as u32, y * 2 + down as u32, color);
+        }
+    }
+    output
+}
+
+/// Scale3x (AdvMAME3x), the 3x version of [`scale2x`].
+pub fn scale3x(image: &RgbaImage) -> RgbaImage {
+    let mut output = RgbaImage::new(image.width() * 3, image.height() * 3);
+    for (x, y, &e) in image.enumerate_pixels() {
+        let pixels = Neighborhood::new(image, x, y);
+        let [a, b, c, d, f, g, h, i] = [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ]
+        .map(|(dx, dy)| pixels.get(dx, dy));
+
+        let block = if b != h && d != f {
+            [
+                if d == b { d } else { e },
+                if (d == b && e != c) || (b == f && e != a) {
+                    b
+                } else {
+                    e
+                },
+                if b == f { f } else { e },
+                if (d == b && e != g) || (d == h && e != a) {
+                    d
+                } else {
+                    e
+                },
+                e,
+                if (b == f && e != i) || (h == f && e != c) {
+                    f
+                } else {
+                    e
+                },
+                if d == h { d } else { e },
+                if (d == h && e != i) || (h == f && e != g) {
+                    h
+                } else {
+                    e
+                },
+                if h == f { f } else { e },
+            ]
+        } else {
+            [e; 9]
+        };
+        for (n, color) in block.into_iter().enumerate() {
+            output.put_pixel(x * 3 + n as u32 % 3, y * 3 + n as u32 / 3, color);
+        }
+    }
+    output
+}
+
+fn yuv(color: Rgba<u8>) -> [f32; 3] {
+    let [r, g, b] = [color[0], color[1], color[2]].map(f32::from);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.169 * r - 0.331 * g + 0.5 * b,
+        0.5 * r - 0.419 * g - 0.081 * b,
+    ]
+}
+
+/// Whether two colors are different enough to have an edge between them, using the same
+/// thresholds as the original HQx filters.
+fn hq_different(a: Rgba<u8>, b: Rgba<u8>) -> bool {
+    let (a, b) = (yuv(a), yuv(b));
+    (a[0] - b[0]).abs() > 48.0 || (a[1] - b[1]).abs() > 7.0 || (a[2] - b[2]).abs() > 6.0
+}
+
+fn mix(a: Rgba<u8>, b: Rgba<u8>, amount: f32) -> Rgba<u8> {
+    Rgba(std::array::from_fn(|i| {
+        (a[i] as f32 + (b[i] as f32 - a[i] as f32) * amount).round() as u8
+    }))
+}
+
+/// Mixes colors with integer weights, then divides by `1 << shift`, rounding down the way the
+/// HQx filters do.
+fn blend<const N: usize>(colors: [(Rgba<u8>, u32); N], shift: u32) -> Rgba<u8> {
+    Rgba(std::array::from_fn(|i| {
+        let sum: u32 = colors
+            .iter()
+            .map(|(color, weight)| color[i] as u32 * weight)
+            .sum();
+        (sum >> shift) as u8
+    }))
+}
+
+/// A pixel and its neighbors for the HQx filters, mirrored so that the rules for the top-left
+/// corner work for all four. `w` is in reading order, so `w[4]` is the pixel, `w[0]` is
+/// diagonally across the corner and `w[1]` and `w[3]` are on either side of it.
+struct HqPixels {
+    w: [Rgba<u8>; 9],
+    /// A bit for each neighbor with an edge between it and the center, in the order of `w`
+    /// without the center.
+    pattern: u8,
+}
+
+impl HqPixels {
+    fn new(image: &RgbaImage, x: u32, y: u32, right: bool, down: bool) -> Self {
+        let pixels = Neighborhood::new(image, x, y).towards(!right, !down);
+        let w: [_; 9] = std::array::from_fn(|i| pixels.get(i as i64 % 3 - 1, i as i64 / 3 - 1));
+        let pattern = [0, 1, 2, 3, 5, 6, 7, 8]
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, i)| hq_different(w[4], w[i]))
+            .fold(0, |pattern, (bit, _)| pattern | 1 << bit);
+        Self { w, pattern }
+    }
+
+    /// Whether any `(mask, bits)` matches, meaning that of the neighbors in `mask`, exactly the
+    /// ones in `bits` have edges.
+    fn is(&self, patterns: &[(u8, u8)]) -> bool {
+        patterns
+            .iter()
+            .any(|&(mask, bits)| self.pattern & mask == bits)
+    }
+
+    fn different(&self, a: usize, b: usize) -> bool {
+        hq_different(self.w[a], self.w[b])
+    }
+}
+
+// The HQx lookup tables, condensed into the patterns that pick each way of filling in the
+// top-left corner. Their names are from how HQ2x uses them.
+const SLOPE_X: &[(u8, u8)] = &[(0xBF, 0x37), (0xDB, 0x13)];
+const SLOPE_Y: &[(u8, u8)] = &[(0xDB, 0x49), (0xEF, 0x6D)];
+const SHARP_CORNER: &[(u8, u8)] = &[(0x0B, 0x0B), (0xFE, 0x4A), (0xFE, 0x1A)];
+const BLEND_CORNER: &[(u8, u8)] = &[
+    (0x6F, 0x2A),
+    (0x5B, 0x0A),
+    (0xBF, 0x3A),
+    (0xDF, 0x5A),
+    (0x9F, 0x8A),
+    (0xCF, 0x8A),
+    (0xEF, 0x4E),
+    (0x3F, 0x0E),
+    (0xFB, 0x5A),
+    (0xBB, 0x8A),
+    (0x7F, 0x5A),
+    (0xAF, 0x8A),
+    (0xEB, 0x8A),
+];
+const TOWARDS_X: &[(u8, u8)] = &[(0x1B, 0x03), (0x4F, 0x43), (0x8B, 0x83), (0x6B, 0x43)];
+const TOWARDS_Y: &[(u8, u8)] = &[(0x4B, 0x09), (0x8B, 0x89), (0x1F, 0x19), (0x3B, 0x19)];
+const ISOLATED: &[(u8, u8)] = &[(0x2F, 0x2F)];
+const FLAT: &[(u8, u8)] = &[(0x0A, 0x00)];
+
+/// HQ2x's output for the top-left quarter of a pixel.
+fn hq2x_corner(p: &HqPixels) -> Rgba<u8> {
+    let [w0, w1, _, w3, w4, ..] = p.w;
+    if p.is(SLOPE_X) && p.different(1, 5) {
+        blend([(w4, 3), (w3, 1)], 2)
+    } else if p.is(SLOPE_Y) && p.different(7, 3) {
+        blend([(w4, 3), (w1, 1)], 2)
+    } else if p.is(SHARP_CORNER) && p.different(3, 1) {
+        w4
+    } else if p.is(BLEND_CORNER) && p.different(3, 1) {
+        blend([(w4, 3), (w0, 1)], 2)
+    } else if p.is(&[(0x0B, 0x08)]) {
+        blend([(w4, 2), (w0, 1), (w1, 1)], 2)
+    } else if p.is(&[(0x0B, 0x02)]) {
+        blend([(w4, 2), (w0, 1), (w3, 1)], 2)
+    } else if p.is(ISOLATED) {
+        blend([(w4, 14), (w3, 1), (w1, 1)], 4)
+    } else if p.is(SLOPE_X) {
+        blend([(w4, 5), (w1, 2), (w3, 1)], 3)
+    } else if p.is(SLOPE_Y) {
+        blend([(w4, 5), (w3, 2), (w1, 1)], 3)
+    } else if p.is(TOWARDS_X) {
+        blend([(w4, 3), (w3, 1)], 2)
+    } else if p.is(TOWARDS_Y) {
+        blend([(w4, 3), (w1, 1)], 2)
+    } else if p.is(&[(0x7E, 0x2A), (0xEF, 0xAB), (0xBF, 0x8F), (0x7E, 0x0E)]) {
+        blend([(w4, 2), (w3, 3), (w1, 3)], 3)
+    } else if p.is(&[
+        (0xFB, 0x6A),
+        (0x6F, 0x6E),
+        (0x3F, 0x3E),
+        (0xFB, 0xFA),
+        (0xDF, 0xDE),
+        (0xDF, 0x1E),
+    ]) {
+        blend([(w4, 3), (w0, 1)], 2)
+    } else if p.is(FLAT)
+        || p.is(&[
+            (0x4F, 0x4B),
+            (0x9F, 0x1B),
+            (0x2F, 0x0B),
+            (0xBE, 0x0A),
+            (0xEE, 0x0A),
+            (0x7E, 0x0A),
+            (0xEB, 0x4B),
+            (0x3B, 0x1B),
+        ])
+    {
+        blend([(w4, 2), (w3, 1), (w1, 1)], 2)
+    } else {
+        blend([(w4, 6), (w3, 1), (w1, 1)], 3)
+    }
+}
+
+/// HQ4x's output for the top-left 2x2 block of a pixel, in reading order.
+fn hq4x_corner(p: &HqPixels) -> [Rgba<u8>; 4] {
+    let [w0, w1, _, w3, w4, ..] = p.w;
+    let slope_x_edge = p.is(SLOPE_X) && p.different(1, 5);
+    let slope_y_edge = p.is(SLOPE_Y) && p.different(7, 3);
+    let blend_corner = p.is(BLEND_CORNER) && p.different(3, 1);
+    let sharp_sides =
+        p.is(&[(0x0F, 0x0B), (0x2B, 0x0B), (0xFE, 0x4A), (0xFE, 0x1A)]) && p.different(3, 1);
+    let step_x = p.is(&[(0x0B, 0x09)]);
+    let step_y = p.is(&[(0x0B, 0x03)]);
+    let bend_x = p.is(&[(0x7E, 0x2A), (0xEF, 0xAB)]);
+    let bend_y = p.is(&[(0xBF, 0x8F), (0x7E, 0x0E)]);
+    let half_sides = p.is(&[
+        (0x4F, 0x4B),
+        (0x9F, 0x1B),
+        (0x2F, 0x0B),
+        (0xBE, 0x0A),
+        (0xEE, 0x0A),
+        (0x7E, 0x0A),
+        (0xEB, 0x4B),
+        (0x3B, 0x1B),
+    ]);
+    let towards_corner = p.is(&[
+        (0x0B, 0x08),
+        (0xF9, 0x68),
+        (0xF3, 0x62),
+        (0x6D, 0x6C),
+        (0x67, 0x66),
+        (0x3D, 0x3C),
+        (0x37, 0x36),
+        (0xF9, 0xF8),
+        (0xDD, 0xDC),
+        (0xF3, 0xF2),
+        (0xD7, 0xD6),
+        (0xDD, 0x1C),
+        (0xD7, 0x16),
+        (0x0B, 0x02),
+    ]);
+
+    let outer = if slope_x_edge {
+        blend([(w4, 5), (w3, 3)], 3)
+    } else if slope_y_edge {
+        blend([(w4, 5), (w1, 3)], 3)
+    } else if p.is(SHARP_CORNER) && p.different(3, 1) {
+        w4
+    } else if blend_corner {
+        blend([(w4, 5), (w0, 3)], 3)
+    } else if p.is(SLOPE_Y) {
+        blend([(w4, 3), (w3, 1)], 2)
+    } else if p.is(SLOPE_X) {
+        blend([(w4, 3), (w1, 1)], 2)
+    } else if p.is(TOWARDS_X) {
+        blend([(w4, 5), (w3, 3)], 3)
+    } else if p.is(TOWARDS_Y) {
+        blend([(w4, 5), (w1, 3)], 3)
+    } else if p.is(&[
+        (0x0F, 0x0B),
+        (0x5E, 0x0A),
+        (0x2B, 0x0B),
+        (0xBE, 0x0A),
+        (0x7A, 0x0A),
+        (0xEE, 0x0A),
+    ]) {
+        blend([(w1, 1), (w3, 1)], 1)
+    } else if towards_corner {
+        blend([(w4, 5), (w0, 3)], 3)
+    } else {
+        blend([(w4, 2), (w1, 1), (w3, 1)], 2)
+    };
+
+    let beside_x = if slope_x_edge {
+        blend([(w4, 7), (w3, 1)], 3)
+    } else if sharp_sides {
+        w4
+    } else if blend_corner {
+        blend([(w4, 3), (w0, 1)], 2)
+    } else if p.is(ISOLATED) {
+        w4
+    } else if p.is(FLAT) {
+        blend([(w4, 5), (w1, 2), (w3, 1)], 3)
+    } else if p.is(&[(0x0B, 0x08)]) {
+        blend([(w4, 5), (w1, 2), (w0, 1)], 3)
+    } else if step_x {
+        blend([(w4, 5), (w1, 3)], 3)
+    } else if p.is(SLOPE_X) {
+        blend([(w1, 3), (w4, 1)], 2)
+    } else if bend_x {
+        blend([(w1, 2), (w4, 1), (w3, 1)], 2)
+    } else if bend_y {
+        blend([(w1, 5), (w3, 3)], 3)
+    } else if p.is(TOWARDS_X) {
+        blend([(w4, 7), (w3, 1)], 3)
+    } else if p.is(&[
+        (0xF3, 0x62),
+        (0x67, 0x66),
+        (0x37, 0x36),
+        (0xF3, 0xF2),
+        (0xD7, 0xD6),
+        (0xD7, 0x16),
+        (0x0B, 0x02),
+    ]) {
+        blend([(w4, 3), (w0, 1)], 2)
+    } else if half_sides {
+        blend([(w1, 1), (w4, 1)], 1)
+    } else {
+        blend([(w4, 3), (w1, 1)], 2)
+    };
+
+    let beside_y = if slope_y_edge {
+        blend([(w4, 7), (w1, 1)], 3)
+    } else if sharp_sides {
+        w4
+    } else if blend_corner {
+        blend([(w4, 3), (w0, 1)], 2)
+    } else if p.is(ISOLATED) {
+        w4
+    } else if p.is(FLAT) {
+        blend([(w4, 5), (w3, 2), (w1, 1)], 3)
+    } else if p.is(&[(0x0B, 0x02)]) {
+        blend([(w4, 5), (w3, 2), (w0, 1)], 3)
+    } else if step_y {
+        blend([(w4, 5), (w3, 3)], 3)
+    } else if p.is(SLOPE_Y) {
+        blend([(w3, 3), (w4, 1)], 2)
+    } else if bend_y {
+        blend([(w3, 2), (w4, 1), (w1, 1)], 2)
+    } else if bend_x {
+        blend([(w3, 5), (w1, 3)], 3)
+    } else if p.is(TOWARDS_Y) {
+        blend([(w4, 7), (w1, 1)], 3)
+    } else if p.is(&[
+        (0x0B, 0x08),
+        (0xF9, 0x68),
+        (0x6D, 0x6C),
+        (0x3D, 0x3C),
+        (0xF9, 0xF8),
+        (0xDD, 0xDC),
+        (0xDD, 0x1C),
+    ]) {
+        blend([(w4, 3), (w0, 1)], 2)
+    } else if half_sides {
+        blend([(w3, 1), (w4, 1)], 1)
+    } else {
+        blend([(w4, 3), (w3, 1)], 2)
+    };
+
+    let inner =
+        if p.is(&[(0x7F, 0x2B), (0xEF, 0xAB), (0xBF, 0x8F), (0x7F, 0x0F)]) && p.different(3, 1) {
+            w4
+        } else if blend_corner {
+            blend([(w4, 7), (w0, 1)], 3)
+        } else if step_y {
+            blend([(w4, 7), (w3, 1)], 3)
+        } else if step_x {
+            blend([(w4, 7), (w1, 1)], 3)
+        } else if p.is(FLAT) || bend_x || bend_y {
+            blend([(w4, 6), (w3, 1), (w1, 1)], 3)
+        } else if towards_corner {
+            blend([(w4, 7), (w0, 1)], 3)
+        } else {
+            w4
+        };
+
+    [outer, beside_x, beside_y, inner]
+}
+
+/// Maxim Stepin's HQ2x. Each pixel is compared with its neighbors using a YUV threshold, and
+/// the pattern of edges around it picks how each quarter of it is blended with them.
+pub fn hq2x(image: &RgbaImage) -> RgbaImage {
+    let mut output = RgbaImage::new(image.width() * 2, image.height() * 2);
+    for (x, y, _) in image.enumerate_pixels() {
+        for (right, down) in CORNERS {
+            let color = hq2x_corner(&HqPixels::new(image, x, y, right, down));
+            output.put_pixel(x * 2 + right as u32, y * 2 + down as u32, color);
+        }
+    }
+    output
+}
+
+/// HQ4x, which works like [`hq2x`] but fills in a 2x2 block for each quarter of the pixel.
+pub fn hq4x(image: &RgbaImage) -> RgbaImage {
+    let mut output = RgbaImage::new(image.width() * 4, image.height() * 4);
+    for (x, y, _) in image.enumerate_pixels() {
+        for (right, down) in CORNERS {
+            let block = hq4x_corner(&HqPixels::new(image, x, y, right, down));
+            for (n, color) in block.into_iter().enumerate() {
+                // The block is worked out for the top-left corner, so mirror it into place
+                let (bx, by) = (n as u32 % 2, n as u32 / 2);
+                let out_x = if right { 3 - bx } else { bx };
+                let out_y = if down { 3 - by } else { by };
+                output.put_pixel(x * 4 + out_x, y * 4 + out_y, color);
+            }
+        }
+    }
+    output
+}
+
+fn xbr_distance(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let (a, b) = (yuv(a), yuv(b));
+    48.0 * (a[0] - b[0]).abs() + 7.0 * (a[1] - b[1]).abs() + 6.0 * (a[2] - b[2]).abs()
+}
+
+/// Level 1 of Hyllian's xBR at 2x: each corner is blended towards a neighbor when the colors on
+/// either side of that corner show an edge running across it.
+pub fn xbr2x(image: &RgbaImage) -> RgbaImage {
+    let mut output = RgbaImage::new(image.width() * 2, image.height() * 2);
+    for (x, y, &e) in image.enumerate_pixels() {
+        for (right, down) in CORNERS {
+            // Written for the bottom-right corner, mirrored for the others
+            let p = Neighborhood::new(image, x, y).towards(right, down);
+            let d = xbr_distance;
+            let (b, c, f, g, h, i) = (
+                p.get(0, -1),
+                p.get(1, -1),
+                p.get(1, 0),
+                p.get(-1, 1),
+                p.get(0, 1),
+                p.get(1, 1),
+            );
+            let (d0, f4, i4, h5, i5) = (
+                p.get(-1, 0),
+                p.get(2, 0),
+                p.get(2, 1),
+                p.get(0, 2),
+                p.get(1, 2),
+            );
+
+            let across = d(e, c) + d(e, g) + d(i, f4) + d(i, h5) + 4.0 * d(h, f);
+            let along = d(h, d0) + d(h, i5) + d(f, i4) + d(f, b) + 4.0 * d(e, i);
+            let color = if e != f && e != h && across < along {
+                let neighbor = if d(e, f) <= d(e, h) { f } else { h };
+                mix(e, neighbor, 0.5)
+            } else {
+                e
+            };
+            output.put_pixel(x * 2 + right as u32, y * 2 + down as u32, color);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use image::{imageops, Rgba, RgbaImage};
+
+    use super::UpscaleFilter;
+
+    const OFF: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const ON: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    /// A diagonal line from the top-left to the bottom-right.
+    fn diagonal() -> RgbaImage {
+        RgbaImage::from_fn(4, 4, |x, y| if x == y { ON } else { OFF })
+    }
+
+    #[test]
+    fn test_solid_images_stay_solid() {
+        let image = RgbaImage::from_pixel(3, 2, ON);
+        for filter in UpscaleFilter::ALL {
+            let output = filter.apply(&image);
+            assert_eq!(
+                output.dimensions(),
+                (3 * filter.factor(), 2 * filter.factor())
+            );
+            assert!(output.pixels().all(|pixel| *pixel == ON), "{filter}");
+        }
+    }
+
+    #[test]
+    fn test_scale2x() {
+        let output = UpscaleFilter::Scale2x.apply(&diagonal());
+        // The gaps between the diagonal's pixels get filled in
+        for (x, y) in [(2, 1), (1, 2), (4, 3), (3, 4)] {
+            assert_eq!(*output.get_pixel(x, y), ON);
+        }
+        assert_eq!(*output.get_pixel(2, 2), ON);
+        assert_eq!(*output.get_pixel(5, 2), OFF);
+        assert_eq!(*output.get_pixel(0, 2), OFF);
+    }
+
+    #[test]
+    fn test_scale3x() {
+        let output = UpscaleFilter::Scale3x.apply(&diagonal());
+        assert_eq!(*output.get_pixel(4, 4), ON);
+        assert_eq!(*output.get_pixel(3, 2), ON);
+        assert_eq!(*output.get_pixel(2, 3), ON);
+        assert_eq!(*output.get_pixel(5, 0), OFF);
+    }
+
+    #[test]
+    fn test_smoothing_filters_blend_diagonals() {
+        for filter in [
+            UpscaleFilter::Hq2x,
+            UpscaleFilter::Hq4x,
+            UpscaleFilter::Xbr2x,
+        ] {
+            let output = filter.apply(&diagonal());
+            let factor = filter.factor();
+            assert!(
+                output.pixels().any(|pixel| *pixel != ON && *pixel != OFF),
+                "{filter}"
+            );
+            // The middle of each pixel on the line is untouched
+            assert_eq!(
+                *output.get_pixel(factor + factor / 2, factor + factor / 2),
+                ON,
+                "{filter}"
+            );
+        }
+    }
+
+    fn row(image: &RgbaImage, y: u32) -> Vec<u8> {
+        (0..image.width())
+            .map(|x| image.get_pixel(x, y)[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_hqx_isolated_pixel() {
+        let image = RgbaImage::from_fn(3, 3, |x, y| if (x, y) == (1, 1) { ON } else { OFF });
+        // HQ2x mixes a sixteenth of each side into every quarter
+        let hq2x = UpscaleFilter::Hq2x.apply(&image);
+        for (x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)] {
+            assert_eq!(*hq2x.get_pixel(x, y), Rgba([223, 223, 223, 255]));
+        }
+        // HQ4x only rounds off the corners
+        let hq4x = UpscaleFilter::Hq4x.apply(&image);
+        for y in 4..8 {
+            for x in 4..8 {
+                let corner = (x == 4 || x == 7) && (y == 4 || y == 7);
+                let expected = if corner {
+                    Rgba([127, 127, 127, 255])
+                } else {
+                    ON
+                };
+                assert_eq!(*hq4x.get_pixel(x, y), expected, "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn test_hqx_diagonal() {
+        // Away from the edges of the image, the line is bordered by half-blended pixels
+        let hq2x = UpscaleFilter::Hq2x.apply(&diagonal());
+        assert_eq!(row(&hq2x, 3), [0, 0, 127, 255, 127, 0, 0, 0]);
+        assert_eq!(row(&hq2x, 4), [0, 0, 0, 127, 255, 127, 0, 0]);
+        let hq4x = UpscaleFilter::Hq4x.apply(&diagonal());
+        assert_eq!(
+            row(&hq4x, 7),
+            [0, 0, 0, 0, 0, 127, 255, 255, 255, 127, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            row(&hq4x, 8),
+            [0, 0, 0, 0, 0, 0, 127, 255, 255, 255, 127, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_hqx_symmetry() {
+        // The rules are only written for the top-left corner, so turning the input around has
+        // to turn the output around with it
+        let colors = [OFF, ON, Rgba([200, 40, 40, 255])];
+        let image = RgbaImage::from_fn(7, 5, |x, y| {
+            colors[((x * 7 + y * y * 3 + x * y) % 3) as usize]
+        });
+        for filter in [UpscaleFilter::Hq2x, UpscaleFilter::Hq4x] {
+            let output = filter.apply(&image);
+            assert_eq!(
+                filter.apply(&imageops::flip_horizontal(&image)),
+                imageops::flip_horizontal(&output),
+                "{filter}"
+            );
+            assert_eq!(
+                filter.apply(&imageops::flip_vertical(&image)),
+                imageops::flip_vertical(&output),
+                "{filter}"
+            );
+            assert_eq!(
+                filter.apply(&imageops::rotate90(&image)),
+                imageops::rotate90(&output),
+                "{filter}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_ids() {
+        for filter in UpscaleFilter::ALL {
+            assert_eq!(UpscaleFilter::from_id(filter.id()), Some(filter));
+        }
+    }
+}
@@ -0,0 +1,14 @@
+#!/bin/sh
+# Downloads the ROMs from Timendus' CHIP-8 test suite into tests/roms/public, for the ignored
+# test_public_roms test in tests/golden.rs. They're GPL-3.0, so they aren't checked in.
+set -eu
+
+VERSION=v4.1
+BASE_URL="https://raw.githubusercontent.com/Timendus/chip8-test-suite/$VERSION/bin"
+DIR="$(dirname "$0")/roms/public"
+
+mkdir -p "$DIR"
+for rom in 1-chip8-logo 2-ibm-logo 3-corax+ 4-flags 5-quirks; do
+    curl --fail --silent --show-error --location --output "$DIR/$rom.ch8" "$BASE_URL/$rom.ch8"
+done
+echo "Downloaded the test suite's ROMs ($VERSION) to $DIR"
@@ -0,0 +1,118 @@
+//! Runs arbitrary ROMs on every model with arbitrary quirks and key presses, checking that the
+//! interpreter only ever reports problems as errors and never panics.
+//!
+//! Set `PROPTEST_CASES` to run more cases than the default.
+
+use arbitrary_int::u4;
+use murmur8tion::{
+    hardware::{DynamicMachine, KeyEvent, Machine},
+    instruction::{InstructionSet, Op},
+    model::{DrawWaitSetting, DynamicModel, Model, Quirks},
+    screen::Palette,
+};
+use proptest::{collection::vec, prelude::*};
+
+const FRAMES: u8 = 30;
+const IPF: u32 = 200;
+
+fn key_event() -> impl Strategy<Value = KeyEvent> {
+    prop_oneof![Just(KeyEvent::Press), Just(KeyEvent::Release)]
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    (
+        (any::<bool>(), any::<bool>(), key_event(), any::<bool>()),
+        (
+            any::<bool>(),
+            prop_oneof![
+                Just(DrawWaitSetting::Always),
+                Just(DrawWaitSetting::LoresOnly),
+                Just(DrawWaitSetting::Never),
+            ],
+            any::<bool>(),
+            any::<bool>(),
+            any::<bool>(),
+        ),
+    )
+        .prop_map(
+            |(
+                (graceful_exit_on_0000, bitshift_use_y, key_wait_trigger, inc_i_on_slice),
+                (
+                    bitwise_reset_flag,
+                    draw_wait_for_vblank,
+                    clear_screen_on_mode_switch,
+                    jump_v0_use_vx,
+                    lores_draw_large_as_small,
+                ),
+            )| Quirks {
+                graceful_exit_on_0000,
+                bitshift_use_y,
+                key_wait_trigger,
+                inc_i_on_slice,
+                bitwise_reset_flag,
+                draw_wait_for_vblank,
+                clear_screen_on_mode_switch,
+                jump_v0_use_vx,
+                lores_draw_large_as_small,
+            },
+        )
+}
+
+fn model() -> impl Strategy<Value = DynamicModel> {
+    (
+        prop_oneof![
+            Just(DynamicModel::COSMAC_VIP),
+            Just(DynamicModel::LEGACY_SCHIP),
+            Just(DynamicModel::MODERN_SCHIP),
+            Just(DynamicModel::XO_CHIP),
+        ],
+        quirks(),
+    )
+        .prop_map(|(mut model, quirks)| {
+            *model.quirks_mut() = quirks;
+            model
+        })
+}
+
+/// Replace opcodes that would stop the machine straight away with loads, so that random ROMs get
+/// further before they hit an error.
+fn runnable(words: &[u16], instruction_set: InstructionSet) -> Vec<u8> {
+    words
+        .iter()
+        .map(|&word| match Op::decode(word, instruction_set) {
+            Op::Invalid | Op::_0000 | Op::_00FD => 0x6000 | (word & 0x0FFF),
+            _ => word,
+        })
+        .flat_map(u16::to_be_bytes)
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn test_arbitrary_roms_dont_panic(
+        model in model(),
+        // Long enough to overflow the COSMAC VIP's memory
+        words in vec(any::<u16>(), 0..0x900),
+        raw in any::<bool>(),
+        keys in vec((0..FRAMES, 0..16u8, key_event()), 0..32),
+        decode_cache in any::<bool>(),
+    ) {
+        let rom = if raw {
+            words.iter().copied().flat_map(u16::to_be_bytes).collect()
+        } else {
+            runnable(&words, model.instruction_set())
+        };
+        let mut machine = DynamicMachine::new(model, &rom);
+        machine.reseed(0);
+        machine.set_decode_cache(decode_cache);
+        for frame in 0..FRAMES {
+            for (_, key, event) in keys.iter().filter(|(at, _, _)| *at == frame) {
+                machine.event(u4::new(*key), *event);
+            }
+            // Errors are fine, as long as they're returned rather than panicking
+            let _ = machine.run_frame(IPF);
+            machine.audio_frame();
+        }
+        machine.render_frame(&Palette::default());
+    }
+}
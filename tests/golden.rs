@@ -0,0 +1,209 @@
+//! Runs the test ROMs in `tests/roms` and compares their final frames with the images in
+//! `tests/golden`. Run with `BLESS=1` to write the current output as the new golden images.
+//!
+//! The ROMs here are small ones written for these tests, checking the same flags, quirks, keypad
+//! and scrolling behavior as Timendus' CHIP-8 test suite. The suite itself is GPL-3.0, which
+//! doesn't fit this MIT-licensed crate, so it isn't vendored. Each ROM is assembled from the
+//! Octo source next to it with `octo-cli` from [c-octo](https://github.com/JohnEarnest/c-octo),
+//! which has to be followed by blessing the new output:
+//!
+//! ```sh
+//! octo-cli tests/roms/flags.8o tests/roms/flags.ch8
+//! BLESS=1 cargo test --test golden
+//! ```
+//!
+//! The suite's own ROMs can still be run, as an ignored test. `tests/fetch_public_roms.sh`
+//! downloads them into `tests/roms/public`, and `cargo test --test golden -- --ignored` runs
+//! them. Their golden images aren't checked in either, so the first run has to be blessed, after
+//! checking by eye that every test passes.
+
+use std::path::{Path, PathBuf};
+
+use arbitrary_int::u4;
+use image::RgbaImage;
+use murmur8tion::{
+    hardware::{self, DynamicMachine, KeyEvent, Machine},
+    model::DynamicModel,
+    screen::Palette,
+};
+
+const ALL_MODELS: &[DynamicModel] = &[
+    DynamicModel::COSMAC_VIP,
+    DynamicModel::LEGACY_SCHIP,
+    DynamicModel::MODERN_SCHIP,
+    DynamicModel::XO_CHIP,
+];
+
+const SUPER_CHIP_MODELS: &[DynamicModel] = &[
+    DynamicModel::LEGACY_SCHIP,
+    DynamicModel::MODERN_SCHIP,
+    DynamicModel::XO_CHIP,
+];
+
+struct GoldenTest<'a> {
+    rom: &'a str,
+    models: &'static [DynamicModel],
+    frames: u64,
+    /// Key events to send before running each frame, as `(frame, key, event)`.
+    keys: &'static [(u64, u8, KeyEvent)],
+    /// Write the model's platform number to 1FF, which the test suite's ROMs read to skip their
+    /// menus.
+    platform: bool,
+}
+
+impl GoldenTest<'_> {
+    fn run(&self, model: &DynamicModel) -> Result<RgbaImage, hardware::Error> {
+        let rom = std::fs::read(test_dir().join("roms").join(format!("{}.ch8", self.rom)))
+            .expect("test ROM should exist");
+        let mut machine = DynamicMachine::new(model.clone(), &rom);
+        machine.reseed(0);
+        if self.platform {
+            machine.poke(0x1FF, &[suite_platform(model)])?;
+        }
+        for frame in 0..self.frames {
+            for (_, key, event) in self.keys.iter().filter(|(at, _, _)| *at == frame) {
+                machine.event(u4::new(*key), *event);
+            }
+            machine.run_frame(1000)?;
+        }
+        Ok(machine.render_frame(&Palette::default()))
+    }
+
+    /// Run the ROM on each model, returning a description of each mismatch.
+    fn check(&self) -> Vec<String> {
+        let bless = std::env::var_os("BLESS").is_some();
+        let mut failures = Vec::new();
+        for model in self.models {
+            let name = format!("{}-{}.png", self.rom, model.id());
+            let image = match self.run(model) {
+                Ok(image) => image,
+                Err(error) => {
+                    failures.push(format!("{name}: emulator error: {error}"));
+                    continue;
+                }
+            };
+            let golden_path = test_dir().join("golden").join(&name);
+            if bless {
+                std::fs::create_dir_all(golden_path.parent().unwrap())
+                    .expect("golden directory should be created");
+                image.save(&golden_path).expect("golden image should save");
+                continue;
+            }
+            let golden = match image::open(&golden_path) {
+                Ok(golden) => golden.to_rgba8(),
+                Err(error) => {
+                    failures.push(format!("{name}: error loading golden image: {error}"));
+                    continue;
+                }
+            };
+            if golden != image {
+                let actual_path =
+                    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name.replace('/', "-"));
+                image.save(&actual_path).expect("actual image should save");
+                failures.push(format!(
+                    "{name}: doesn't match the golden image, actual output saved to {}",
+                    actual_path.display()
+                ));
+            }
+        }
+        failures
+    }
+}
+
+fn test_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// The number the test suite uses for each platform.
+fn suite_platform(model: &DynamicModel) -> u8 {
+    match model {
+        DynamicModel::CosmacVip(_) => 1,
+        DynamicModel::ModernSuperChip(_) => 2,
+        DynamicModel::XoChip(_) => 3,
+        DynamicModel::LegacySuperChip(_) => 4,
+    }
+}
+
+fn assert_golden(test: GoldenTest<'_>) {
+    let failures = test.check();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_flags() {
+    assert_golden(GoldenTest {
+        rom: "flags",
+        models: ALL_MODELS,
+        frames: 120,
+        keys: &[],
+        platform: false,
+    });
+}
+
+#[test]
+fn test_quirks() {
+    assert_golden(GoldenTest {
+        rom: "quirks",
+        models: ALL_MODELS,
+        frames: 60,
+        keys: &[],
+        platform: false,
+    });
+}
+
+#[test]
+fn test_keypad() {
+    assert_golden(GoldenTest {
+        rom: "keypad",
+        models: ALL_MODELS,
+        frames: 60,
+        keys: &[
+            (5, 0x5, KeyEvent::Press),
+            (10, 0x5, KeyEvent::Release),
+            (20, 0xA, KeyEvent::Press),
+            (30, 0xA, KeyEvent::Release),
+        ],
+        platform: false,
+    });
+}
+
+#[test]
+fn test_scroll() {
+    assert_golden(GoldenTest {
+        rom: "scroll",
+        models: SUPER_CHIP_MODELS,
+        frames: 60,
+        keys: &[],
+        platform: false,
+    });
+}
+
+/// Timendus' test suite, fetched by `tests/fetch_public_roms.sh`.
+#[test]
+#[ignore = "needs the public test ROMs, see the module docs"]
+fn test_public_roms() {
+    assert!(
+        test_dir().join("roms").join("public").is_dir(),
+        "run tests/fetch_public_roms.sh first"
+    );
+    let mut failures = Vec::new();
+    for (rom, frames, platform) in [
+        ("1-chip8-logo", 60, false),
+        ("2-ibm-logo", 60, false),
+        ("3-corax+", 120, false),
+        ("4-flags", 120, false),
+        ("5-quirks", 600, true),
+    ] {
+        failures.extend(
+            GoldenTest {
+                rom: &format!("public/{rom}"),
+                models: ALL_MODELS,
+                frames,
+                keys: &[],
+                platform,
+            }
+            .check(),
+        );
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,973 @@
+//! Runs single instructions on every model, with every combination of the quirks that change
+//! their results, and compares the registers, memory and screen afterwards with the expected
+//! state.
+//!
+//! Each case runs its setup instructions until the PC reaches the instruction under test, ticks
+//! once, and checks the result against the state from before the tick with the PC advanced past
+//! the instruction and the case's expected changes applied.
+
+use arbitrary_int::u4;
+use murmur8tion::{
+    hardware::{Cpu, DynamicMachine, KeyEvent, Machine},
+    instruction::InstructionSet,
+    model::{DynamicModel, Model},
+    screen::{Screen, FONT_ADDRESS, SCHIP_HIRES_FONT_ADDRESS},
+};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+const ALL_MODELS: &[DynamicModel] = &[
+    DynamicModel::COSMAC_VIP,
+    DynamicModel::LEGACY_SCHIP,
+    DynamicModel::MODERN_SCHIP,
+    DynamicModel::XO_CHIP,
+];
+
+/// A 16x16 sprite with every pixel set.
+const SOLID_SPRITE: &[u16] = &[0xFFFF; 16];
+
+/// v1 = F1, v2 = 20, vF = 55
+const ARITHMETIC_SETUP: &[u16] = &[0x61F1, 0x6220, 0x6F55];
+
+/// v1 = F1, vF = 20
+const VF_OPERAND_SETUP: &[u16] = &[0x61F1, 0x6F20];
+
+/// I = 300, v0 = 11, v1 = 22, v2 = 33
+const STORE_SETUP: &[u16] = &[0xA300, 0x6011, 0x6122, 0x6233];
+
+/// Stores 11 22 33 at 300, then clears v0 to v3 and points I back at 300
+const LOAD_SETUP: &[u16] = &[
+    0xA300, 0x6011, 0x6122, 0x6233, 0xF255, 0xA300, 0x6000, 0x6100, 0x6200, 0x6300,
+];
+
+/// Switches to hires and draws the 8x5 sprite after the one-word instruction under test at 2,1
+const HIRES_SPRITE_SETUP: &[u16] = &[0x00FF, 0xA20C, 0x6002, 0x6101, 0xD015];
+
+/// The seed used for `Cxnn`.
+const SEED: u64 = 1234;
+
+type Expect = fn(&DynamicModel, &mut Cpu, &mut [u8]);
+type ExpectScreen = fn(&DynamicModel, &mut [Vec<u8>]);
+type Prepare = fn(&DynamicModel, &mut DynamicMachine);
+
+struct Case {
+    name: &'static str,
+    /// The oldest instruction set that has the instruction.
+    min: InstructionSet,
+    /// Instructions that set up the registers and memory, starting at 200.
+    setup: &'static [u16],
+    /// The instruction under test and any words after it, following the setup.
+    code: &'static [u16],
+    /// Words placed after the code, such as sprite data.
+    data: &'static [u16],
+    /// Applies the expected changes to the state from before the instruction ran.
+    expect: Expect,
+    /// Runs after the setup, just before the instruction, such as to press keys.
+    prepare: Prepare,
+    /// Applies the expected changes to the screen's palette indices, if the case checks them.
+    screen: Option<ExpectScreen>,
+}
+
+fn case(
+    name: &'static str,
+    min: InstructionSet,
+    setup: &'static [u16],
+    code: &'static [u16],
+    expect: Expect,
+) -> Case {
+    Case {
+        name,
+        min,
+        setup,
+        code,
+        data: &[],
+        expect,
+        prepare: |_, _| {},
+        screen: None,
+    }
+}
+
+impl Case {
+    fn with_data(self, data: &'static [u16]) -> Self {
+        Self { data, ..self }
+    }
+
+    fn with_prepare(self, prepare: Prepare) -> Self {
+        Self { prepare, ..self }
+    }
+
+    fn with_screen(self, screen: ExpectScreen) -> Self {
+        Self {
+            screen: Some(screen),
+            ..self
+        }
+    }
+
+    fn base(&self) -> u16 {
+        0x200 + 2 * self.setup.len() as u16
+    }
+
+    fn rom(&self) -> Vec<u8> {
+        self.setup
+            .iter()
+            .chain(self.code)
+            .chain(self.data)
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+
+    /// Run the setup on a model, leaving the machine about to run the instruction under test.
+    fn setup(&self, model: &DynamicModel) -> Result<DynamicMachine, String> {
+        let mut machine = DynamicMachine::new(model.clone(), &self.rom());
+        // Lets sprites draw straight away on models that wait for vblank
+        machine.start_frame();
+        for _ in 0..self.setup.len() {
+            if machine.cpu().pc == self.base() {
+                break;
+            }
+            machine
+                .tick()
+                .map_err(|error| format!("setup failed: {error}"))?;
+        }
+        if machine.cpu().pc != self.base() {
+            return Err(format!("setup ended at {:03X}", machine.cpu().pc));
+        }
+        (self.prepare)(model, &mut machine);
+        Ok(machine)
+    }
+
+    /// Run the case on a model, returning a description of the mismatch if there is one.
+    fn check(&self, model: &DynamicModel) -> Option<String> {
+        let mut machine = match self.setup(model) {
+            Ok(machine) => machine,
+            Err(error) => return Some(error),
+        };
+
+        let mut cpu = machine.cpu().clone();
+        let mut memory = machine.memory().to_vec();
+        let mut pixels = screen_pixels(&machine);
+        cpu.pc += 2;
+        (self.expect)(model, &mut cpu, &mut memory);
+        if let Some(expect_screen) = self.screen {
+            expect_screen(model, &mut pixels);
+        }
+
+        if let Err(error) = machine.tick() {
+            return Some(format!("error: {error}"));
+        }
+        if *machine.cpu() != cpu {
+            return Some(format!(
+                "expected {cpu:X?}\n    actual   {:X?}",
+                machine.cpu()
+            ));
+        }
+        if let Some(addr) = (0..memory.len()).find(|&addr| machine.memory()[addr] != memory[addr]) {
+            return Some(format!(
+                "expected {:02X} at {addr:03X}, found {:02X}",
+                memory[addr],
+                machine.memory()[addr]
+            ));
+        }
+        if self.screen.is_some() {
+            let actual = screen_pixels(&machine);
+            let mismatch = (0..pixels.len())
+                .flat_map(|y| (0..pixels[y].len()).map(move |x| (x, y)))
+                .find(|&(x, y)| actual[y][x] != pixels[y][x]);
+            if let Some((x, y)) = mismatch {
+                return Some(format!(
+                    "expected pixel {} at {x},{y}, found {}",
+                    pixels[y][x], actual[y][x]
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// The palette index of every pixel on the screen, one row at a time.
+fn screen_pixels(machine: &DynamicMachine) -> Vec<Vec<u8>> {
+    let screen = machine.clone_screen();
+    let width = screen.width() as usize;
+    let mut indices = vec![0; width * screen.height() as usize];
+    screen.render_indices(&mut indices);
+    indices.chunks(width).map(<[u8]>::to_vec).collect()
+}
+
+/// Set a rectangle of pixels to a palette index.
+fn fill(pixels: &mut [Vec<u8>], x: usize, y: usize, width: usize, height: usize, index: u8) {
+    for row in &mut pixels[y..y + height] {
+        row[x..x + width].fill(index);
+    }
+}
+
+fn clear(pixels: &mut [Vec<u8>]) {
+    for row in pixels {
+        row.fill(0);
+    }
+}
+
+/// Every model with every combination of the quirks that change instruction results.
+fn models() -> impl Iterator<Item = DynamicModel> {
+    ALL_MODELS.iter().flat_map(|model| {
+        (0..64).map(move |bits| {
+            let mut model = model.clone();
+            let quirks = model.quirks_mut();
+            quirks.bitshift_use_y = bits & 0b000001 != 0;
+            quirks.inc_i_on_slice = bits & 0b000010 != 0;
+            quirks.bitwise_reset_flag = bits & 0b000100 != 0;
+            quirks.jump_v0_use_vx = bits & 0b001000 != 0;
+            quirks.clear_screen_on_mode_switch = bits & 0b010000 != 0;
+            quirks.key_wait_trigger = if bits & 0b100000 != 0 {
+                KeyEvent::Release
+            } else {
+                KeyEvent::Press
+            };
+            model
+        })
+    })
+}
+
+fn check_cases(cases: &[Case]) {
+    let mut failures = Vec::new();
+    for model in models() {
+        for case in cases {
+            if model.instruction_set() < case.min {
+                continue;
+            }
+            if let Some(failure) = case.check(&model) {
+                failures.push(format!(
+                    "{} on {} with {:?}:\n    {failure}",
+                    case.name,
+                    model.id(),
+                    model.quirks()
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// The PC after skipping the instruction at `pc`, which skips an extra word over a long `F000`
+/// load on XO-CHIP.
+fn skip_long(model: &DynamicModel, pc: u16) -> u16 {
+    if model.instruction_set() >= InstructionSet::XoChip {
+        pc + 4
+    } else {
+        pc + 2
+    }
+}
+
+fn shift_source(model: &DynamicModel, x: u8, y: u8) -> u8 {
+    if model.quirks().bitshift_use_y {
+        y
+    } else {
+        x
+    }
+}
+
+fn bitwise_flag(model: &DynamicModel, flag: u8) -> u8 {
+    if model.quirks().bitwise_reset_flag {
+        0
+    } else {
+        flag
+    }
+}
+
+fn sliced_i(model: &DynamicModel, i: u16, count: u16) -> u16 {
+    if model.quirks().inc_i_on_slice {
+        i + count
+    } else {
+        i
+    }
+}
+
+fn press(machine: &mut DynamicMachine, key: u8) {
+    machine.event(u4::new(key), KeyEvent::Press);
+}
+
+fn release(machine: &mut DynamicMachine, key: u8) {
+    machine.event(u4::new(key), KeyEvent::Release);
+}
+
+fn mode_switch(model: &DynamicModel, pixels: &mut [Vec<u8>]) {
+    if model.quirks().clear_screen_on_mode_switch {
+        clear(pixels);
+    }
+}
+
+#[test]
+fn test_flow_control() {
+    use InstructionSet::*;
+    check_cases(&[
+        case(
+            "00EE",
+            CosmacVip,
+            &[0x2204, 0x0000],
+            &[0x00EE],
+            |_, cpu, _| {
+                cpu.pc = 0x202;
+                cpu.sp = u4::new(0);
+            },
+        ),
+        case("1nnn", CosmacVip, &[], &[0x1400], |_, cpu, _| {
+            cpu.pc = 0x400;
+        }),
+        case("2nnn", CosmacVip, &[0x6000], &[0x2400], |_, cpu, _| {
+            cpu.stack[1] = 0x204;
+            cpu.sp = u4::new(1);
+            cpu.pc = 0x400;
+        }),
+        case("3xnn skip", CosmacVip, &[0x6105], &[0x3105], |_, cpu, _| {
+            cpu.pc += 2;
+        }),
+        case(
+            "3xnn skip over F000",
+            CosmacVip,
+            &[0x6105],
+            &[0x3105, 0xF000, 0x1234],
+            |model, cpu, _| cpu.pc = skip_long(model, cpu.pc),
+        ),
+        case(
+            "3xnn no skip",
+            CosmacVip,
+            &[0x6105],
+            &[0x3106],
+            |_, _, _| {},
+        ),
+        case(
+            "4xnn no skip",
+            CosmacVip,
+            &[0x6105],
+            &[0x4105, 0xF000],
+            |_, _, _| {},
+        ),
+        case("4xnn skip", CosmacVip, &[0x6105], &[0x4106], |_, cpu, _| {
+            cpu.pc += 2;
+        }),
+        case(
+            "5xy0 skip",
+            CosmacVip,
+            &[0x6105, 0x6205],
+            &[0x5120, 0x0000],
+            |_, cpu, _| cpu.pc += 2,
+        ),
+        case(
+            "5xy0 no skip",
+            CosmacVip,
+            &[0x6105, 0x6206],
+            &[0x5120],
+            |_, _, _| {},
+        ),
+        case(
+            "9xy0 skip over F000",
+            CosmacVip,
+            &[0x6105],
+            &[0x9120, 0xF000, 0x1234],
+            |model, cpu, _| cpu.pc = skip_long(model, cpu.pc),
+        ),
+        case(
+            "9xy0 no skip",
+            CosmacVip,
+            &[0x6105, 0x6205],
+            &[0x9120],
+            |_, _, _| {},
+        ),
+        case("Ex9E skip", CosmacVip, &[0x6105], &[0xE19E], |_, cpu, _| {
+            cpu.pc += 2;
+        })
+        .with_prepare(|_, machine| press(machine, 5)),
+        case(
+            "Ex9E no skip",
+            CosmacVip,
+            &[0x6105],
+            &[0xE19E],
+            |_, _, _| {},
+        )
+        .with_prepare(|_, machine| press(machine, 6)),
+        case(
+            "ExA1 skip over F000",
+            CosmacVip,
+            &[0x6105],
+            &[0xE1A1, 0xF000, 0x1234],
+            |model, cpu, _| cpu.pc = skip_long(model, cpu.pc),
+        ),
+        case(
+            "ExA1 no skip",
+            CosmacVip,
+            &[0x6105],
+            &[0xE1A1],
+            |_, _, _| {},
+        )
+        .with_prepare(|_, machine| press(machine, 5)),
+        case(
+            "Bnnn",
+            CosmacVip,
+            &[0x6004, 0x6310],
+            &[0xB320],
+            |model, cpu, _| {
+                cpu.pc = if model.quirks().jump_v0_use_vx {
+                    0x330
+                } else {
+                    0x324
+                };
+            },
+        ),
+    ]);
+}
+
+#[test]
+fn test_arithmetic() {
+    use InstructionSet::*;
+    check_cases(&[
+        case("6xnn", CosmacVip, &[], &[0x6A42], |_, cpu, _| {
+            cpu.v[0xA] = 0x42
+        }),
+        case("Cxnn", CosmacVip, &[], &[0xCA3C], |_, cpu, _| {
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(SEED);
+            cpu.v[0xA] = rng.random::<u8>() & 0x3C;
+        })
+        .with_prepare(|_, machine| machine.reseed(SEED)),
+        case("7xnn", CosmacVip, &[0x6AF0], &[0x7A20], |_, cpu, _| {
+            cpu.v[0xA] = 0x10;
+        }),
+        case(
+            "8xy0",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8120],
+            |_, cpu, _| {
+                cpu.v[1] = 0x20;
+            },
+        ),
+        case(
+            "8xy1",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8121],
+            |model, cpu, _| {
+                cpu.v[1] = 0xF1;
+                cpu.v[0xF] = bitwise_flag(model, 0x55);
+            },
+        ),
+        case(
+            "8xy2",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8122],
+            |model, cpu, _| {
+                cpu.v[1] = 0x20;
+                cpu.v[0xF] = bitwise_flag(model, 0x55);
+            },
+        ),
+        case(
+            "8xy3",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8123],
+            |model, cpu, _| {
+                cpu.v[1] = 0xD1;
+                cpu.v[0xF] = bitwise_flag(model, 0x55);
+            },
+        ),
+        case(
+            "8xy4",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8124],
+            |_, cpu, _| {
+                cpu.v[1] = 0x11;
+                cpu.v[0xF] = 1;
+            },
+        ),
+        case(
+            "8xy5",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8125],
+            |_, cpu, _| {
+                cpu.v[1] = 0xD1;
+                cpu.v[0xF] = 1;
+            },
+        ),
+        case(
+            "8xy5 borrow",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8215],
+            |_, cpu, _| {
+                cpu.v[2] = 0x2F;
+                cpu.v[0xF] = 0;
+            },
+        ),
+        case(
+            "8xy6",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8126],
+            |model, cpu, _| {
+                let source = shift_source(model, 0xF1, 0x20);
+                cpu.v[1] = source >> 1;
+                cpu.v[0xF] = source & 1;
+            },
+        ),
+        case(
+            "8xy7",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8217],
+            |_, cpu, _| {
+                cpu.v[2] = 0xD1;
+                cpu.v[0xF] = 1;
+            },
+        ),
+        case(
+            "8xy7 borrow",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x8127],
+            |_, cpu, _| {
+                cpu.v[1] = 0x2F;
+                cpu.v[0xF] = 0;
+            },
+        ),
+        case(
+            "8xyE",
+            CosmacVip,
+            ARITHMETIC_SETUP,
+            &[0x812E],
+            |model, cpu, _| {
+                let source = shift_source(model, 0xF1, 0x20);
+                cpu.v[1] = source << 1;
+                cpu.v[0xF] = source >> 7;
+            },
+        ),
+        // The flag is written after the result, so it wins when vF is the destination
+        case(
+            "8Fy4",
+            CosmacVip,
+            VF_OPERAND_SETUP,
+            &[0x8F14],
+            |_, cpu, _| {
+                cpu.v[0xF] = 1;
+            },
+        ),
+        case(
+            "8xF4",
+            CosmacVip,
+            VF_OPERAND_SETUP,
+            &[0x81F4],
+            |_, cpu, _| {
+                cpu.v[1] = 0x11;
+                cpu.v[0xF] = 1;
+            },
+        ),
+        case(
+            "8Fy5",
+            CosmacVip,
+            VF_OPERAND_SETUP,
+            &[0x8F15],
+            |_, cpu, _| {
+                cpu.v[0xF] = 0;
+            },
+        ),
+        case(
+            "8xF5",
+            CosmacVip,
+            VF_OPERAND_SETUP,
+            &[0x81F5],
+            |_, cpu, _| {
+                cpu.v[1] = 0xD1;
+                cpu.v[0xF] = 1;
+            },
+        ),
+        case(
+            "8Fy6",
+            CosmacVip,
+            VF_OPERAND_SETUP,
+            &[0x8F16],
+            |model, cpu, _| {
+                cpu.v[0xF] = shift_source(model, 0x20, 0xF1) & 1;
+            },
+        ),
+        case(
+            "8xFE",
+            CosmacVip,
+            VF_OPERAND_SETUP,
+            &[0x81FE],
+            |model, cpu, _| {
+                let source = shift_source(model, 0xF1, 0x20);
+                cpu.v[1] = source << 1;
+                cpu.v[0xF] = source >> 7;
+            },
+        ),
+    ]);
+}
+
+#[test]
+fn test_memory() {
+    use InstructionSet::*;
+    check_cases(&[
+        case("Annn", CosmacVip, &[], &[0xA123], |_, cpu, _| cpu.i = 0x123),
+        case("F000", XoChip, &[], &[0xF000, 0x1234], |_, cpu, _| {
+            cpu.i = 0x1234;
+            cpu.pc += 2;
+        }),
+        case(
+            "Fx07",
+            CosmacVip,
+            &[0x6033, 0xF015],
+            &[0xF107],
+            |_, cpu, _| {
+                cpu.v[1] = 0x33;
+            },
+        ),
+        case("Fx15", CosmacVip, &[0x6033], &[0xF015], |_, cpu, _| {
+            cpu.dt = 0x33
+        }),
+        case("Fx18", CosmacVip, &[0x6005], &[0xF018], |_, cpu, _| {
+            cpu.st = 5
+        }),
+        case(
+            "Fx1E",
+            CosmacVip,
+            &[0xA1F0, 0x6A20],
+            &[0xFA1E],
+            |_, cpu, _| {
+                cpu.i = 0x210;
+            },
+        ),
+        case("Fx29", CosmacVip, &[0x6A1B], &[0xFA29], |_, cpu, _| {
+            cpu.i = FONT_ADDRESS as u16 + 0xB * 5;
+        }),
+        case("Fx30", SuperChip, &[0x6A17], &[0xFA30], |_, cpu, _| {
+            cpu.i = SCHIP_HIRES_FONT_ADDRESS as u16 + 0x7 * 10;
+        }),
+        case(
+            "Fx33",
+            CosmacVip,
+            &[0xA300, 0x6C9C],
+            &[0xFC33],
+            |_, _, memory| {
+                memory[0x300..0x303].copy_from_slice(&[1, 5, 6]);
+            },
+        ),
+        case(
+            "Fx55",
+            CosmacVip,
+            STORE_SETUP,
+            &[0xF255],
+            |model, cpu, memory| {
+                memory[0x300..0x303].copy_from_slice(&[0x11, 0x22, 0x33]);
+                cpu.i = sliced_i(model, 0x300, 3);
+            },
+        ),
+        case("Fx65", CosmacVip, LOAD_SETUP, &[0xF165], |model, cpu, _| {
+            cpu.v[..2].copy_from_slice(&[0x11, 0x22]);
+            cpu.i = sliced_i(model, 0x300, 2);
+        }),
+        case("5xy2", XoChip, STORE_SETUP, &[0x5022], |_, _, memory| {
+            memory[0x300..0x303].copy_from_slice(&[0x11, 0x22, 0x33]);
+        }),
+        case(
+            "5xy2 reversed",
+            XoChip,
+            STORE_SETUP,
+            &[0x5202],
+            |_, _, memory| {
+                memory[0x300..0x303].copy_from_slice(&[0x33, 0x22, 0x11]);
+            },
+        ),
+        case("5xy3", XoChip, LOAD_SETUP, &[0x5133], |_, cpu, _| {
+            cpu.v[1..4].copy_from_slice(&[0x11, 0x22, 0x33]);
+        }),
+        case(
+            "5xy3 reversed",
+            XoChip,
+            LOAD_SETUP,
+            &[0x5313],
+            |_, cpu, _| {
+                cpu.v[1..4].copy_from_slice(&[0x33, 0x22, 0x11]);
+            },
+        ),
+        // The flags aren't visible, but Fx85 reads them back
+        case("Fx75", SuperChip, STORE_SETUP, &[0xF275], |_, _, _| {}),
+        case(
+            "Fx85",
+            SuperChip,
+            &[0x6011, 0x6122, 0xF175, 0x6000, 0x6100],
+            &[0xF185],
+            |_, cpu, _| cpu.v[..2].copy_from_slice(&[0x11, 0x22]),
+        ),
+    ]);
+}
+
+#[test]
+fn test_sprites() {
+    use InstructionSet::*;
+    check_cases(&[
+        case(
+            "Dxyn",
+            CosmacVip,
+            &[0xA206, 0xD125],
+            &[0xD125],
+            |_, cpu, _| {
+                cpu.v[0xF] = 1;
+            },
+        )
+        .with_data(SOLID_SPRITE),
+        // SUPER-CHIP 1.1 counts the rows that collided
+        case(
+            "Dxy0 hires",
+            SuperChip,
+            &[0x00FF, 0xA208, 0xD120],
+            &[0xD120],
+            |model, cpu, _| {
+                cpu.v[0xF] = match model {
+                    DynamicModel::LegacySuperChip(_) => 16,
+                    _ => 1,
+                };
+            },
+        )
+        .with_data(SOLID_SPRITE),
+        case(
+            "Dxyn hires",
+            SuperChip,
+            &[0x00FF, 0xA208, 0xD125],
+            &[0xD125],
+            |model, cpu, _| {
+                cpu.v[0xF] = match model {
+                    DynamicModel::LegacySuperChip(_) => 5,
+                    _ => 1,
+                };
+            },
+        )
+        .with_data(SOLID_SPRITE),
+        case(
+            "Dxy0 lores",
+            SuperChip,
+            &[0xA206, 0xD120],
+            &[0xD120],
+            |_, cpu, _| {
+                cpu.v[0xF] = 1;
+            },
+        )
+        .with_data(SOLID_SPRITE),
+        // SUPER-CHIP 1.1 also counts the rows clipped off the bottom, and XO-CHIP wraps them
+        case(
+            "Dxy0 bottom edge",
+            SuperChip,
+            &[0x00FF, 0xA20A, 0x6000, 0x6138],
+            &[0xD010],
+            |model, cpu, _| {
+                cpu.v[0xF] = match model {
+                    DynamicModel::LegacySuperChip(_) => 8,
+                    _ => 0,
+                };
+            },
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|model, pixels| {
+            fill(pixels, 0, 56, 16, 8, 1);
+            if model.instruction_set() >= InstructionSet::XoChip {
+                fill(pixels, 0, 0, 16, 8, 1);
+            }
+        }),
+        case(
+            "Dxyn bottom edge",
+            SuperChip,
+            &[0x00FF, 0xA20A, 0x6000, 0x613C],
+            &[0xD018],
+            |model, cpu, _| {
+                cpu.v[0xF] = match model {
+                    DynamicModel::LegacySuperChip(_) => 4,
+                    _ => 0,
+                };
+            },
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|model, pixels| {
+            fill(pixels, 0, 60, 8, 4, 1);
+            if model.instruction_set() >= InstructionSet::XoChip {
+                fill(pixels, 0, 0, 8, 4, 1);
+            }
+        }),
+        case(
+            "Dxyn plane 2",
+            XoChip,
+            &[0xF201, 0xA206],
+            &[0xD015],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|_, pixels| fill(pixels, 0, 0, 16, 10, 2)),
+    ]);
+}
+
+#[test]
+fn test_screen() {
+    use InstructionSet::*;
+    check_cases(&[
+        case(
+            "00E0",
+            CosmacVip,
+            &[0xA206, 0xD015],
+            &[0x00E0],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|_, pixels| clear(pixels)),
+        // Only the selected planes are cleared
+        case(
+            "00E0 plane 2",
+            XoChip,
+            &[0xA208, 0xD015, 0xF201],
+            &[0x00E0],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|_, _| {}),
+        case(
+            "00Cn",
+            SuperChip,
+            HIRES_SPRITE_SETUP,
+            &[0x00C3],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|_, pixels| {
+            pixels.rotate_right(3);
+            clear(&mut pixels[..3]);
+        }),
+        case("00Dn", XoChip, HIRES_SPRITE_SETUP, &[0x00D3], |_, _, _| {})
+            .with_data(SOLID_SPRITE)
+            .with_screen(|_, pixels| {
+                pixels.rotate_left(3);
+                let height = pixels.len();
+                clear(&mut pixels[height - 3..]);
+            }),
+        case(
+            "00FB",
+            SuperChip,
+            HIRES_SPRITE_SETUP,
+            &[0x00FB],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|_, pixels| {
+            for row in pixels {
+                row.rotate_right(4);
+                row[..4].fill(0);
+            }
+        }),
+        case(
+            "00FC",
+            SuperChip,
+            HIRES_SPRITE_SETUP,
+            &[0x00FC],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(|_, pixels| {
+            for row in pixels {
+                row.rotate_left(4);
+                let width = row.len();
+                row[width - 4..].fill(0);
+            }
+        }),
+        case(
+            "00FE",
+            SuperChip,
+            HIRES_SPRITE_SETUP,
+            &[0x00FE],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(mode_switch),
+        case(
+            "00FF",
+            SuperChip,
+            &[0xA206, 0xD015],
+            &[0x00FF],
+            |_, _, _| {},
+        )
+        .with_data(SOLID_SPRITE)
+        .with_screen(mode_switch),
+    ]);
+}
+
+#[test]
+fn test_key_wait() {
+    use InstructionSet::*;
+    check_cases(&[
+        // The first tick starts waiting, and doesn't move on until a key is pressed or released
+        case("Fx0A waiting", CosmacVip, &[], &[0xF30A], |_, cpu, _| {
+            cpu.pc -= 2;
+        })
+        .with_prepare(|_, machine| machine.tick().unwrap()),
+        case("Fx0A", CosmacVip, &[], &[0xF30A], |_, cpu, _| cpu.v[3] = 7).with_prepare(
+            |model, machine| {
+                machine.tick().unwrap();
+                press(machine, 7);
+                if model.quirks().key_wait_trigger == KeyEvent::Release {
+                    release(machine, 7);
+                }
+            },
+        ),
+        // A key that was already held when the wait started can only be released
+        case(
+            "Fx0A held key",
+            CosmacVip,
+            &[],
+            &[0xF30A],
+            |model, cpu, _| match model.quirks().key_wait_trigger {
+                KeyEvent::Press => cpu.pc -= 2,
+                KeyEvent::Release => cpu.v[3] = 7,
+            },
+        )
+        .with_prepare(|_, machine| {
+            press(machine, 7);
+            machine.tick().unwrap();
+            release(machine, 7);
+        }),
+    ]);
+}
+
+#[test]
+fn test_audio_registers() {
+    let pattern = case(
+        "F002",
+        InstructionSet::XoChip,
+        &[0xA204],
+        &[0xF002],
+        |_, _, _| {},
+    )
+    .with_data(&[
+        0x0123, 0x4567, 0x89AB, 0xCDEF, 0xFEDC, 0xBA98, 0x7654, 0x3210,
+    ]);
+    let pitch = case(
+        "Fx3A",
+        InstructionSet::XoChip,
+        &[0x6A70],
+        &[0xFA3A],
+        |_, _, _| {},
+    );
+    let cases = [pattern, pitch];
+    check_cases(&cases);
+    let [pattern, pitch] = &cases;
+
+    for model in models().filter(|model| model.instruction_set() >= InstructionSet::XoChip) {
+        let mut machine = pattern.setup(&model).unwrap();
+        machine.tick().unwrap();
+        assert_eq!(
+            machine.audio_pattern(),
+            &[
+                0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0xFE, 0xDC, 0xBA, 0x98, 0x76, 0x54,
+                0x32, 0x10
+            ]
+        );
+
+        let mut machine = pitch.setup(&model).unwrap();
+        machine.tick().unwrap();
+        assert_eq!(machine.pitch(), 0x70);
+    }
+}